@@ -0,0 +1,465 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Replaces personal data with --redact-pii.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The latest version of the AAMVA DL/ID Card Design Standard (2020).
+const LATEST_VERSION: u32 = 10;
+
+const DATA_ELEMENT_SEPARATOR: char = '\n';
+const RECORD_SEPARATOR: char = '\u{1e}';
+const SEGMENT_TERMINATOR: char = '\r';
+
+/// Element ID, field name and whether the element is personal data.
+const ELEMENTS: &[(&str, &str, bool)] = &[
+    ("DCA", "vehicle_class", false),
+    ("DCB", "restrictions", false),
+    ("DCD", "endorsements", false),
+    ("DBA", "expiry_date", false),
+    ("DCS", "family_name", true),
+    ("DAC", "first_name", true),
+    ("DAD", "middle_names", true),
+    ("DBD", "issue_date", false),
+    ("DBB", "date_of_birth", true),
+    ("DBC", "sex", true),
+    ("DAY", "eye_colour", true),
+    ("DAU", "height", true),
+    ("DAG", "street", true),
+    ("DAH", "street_2", true),
+    ("DAI", "city", true),
+    ("DAJ", "jurisdiction", false),
+    ("DAK", "postal_code", true),
+    ("DAQ", "customer_id", true),
+    ("DCF", "document_discriminator", true),
+    ("DCG", "country", false),
+    ("DDE", "family_name_truncated", false),
+    ("DDF", "first_name_truncated", false),
+    ("DDG", "middle_names_truncated", false),
+    ("DAZ", "hair_colour", true),
+    ("DCI", "place_of_birth", true),
+    ("DCJ", "audit_information", true),
+    ("DCK", "inventory_control_number", true),
+    ("DBN", "alias_family_name", true),
+    ("DBG", "alias_given_name", true),
+    ("DBS", "alias_suffix", true),
+    ("DCU", "name_suffix", true),
+    ("DCE", "weight_range", true),
+    ("DCL", "race_ethnicity", true),
+    ("DCM", "standard_vehicle_class", false),
+    ("DCN", "standard_endorsements", false),
+    ("DCO", "standard_restrictions", false),
+    ("DCP", "vehicle_class_description", false),
+    ("DCQ", "endorsements_description", false),
+    ("DCR", "restrictions_description", false),
+    ("DDA", "compliance_type", false),
+    ("DDB", "card_revision_date", false),
+    ("DDC", "hazmat_endorsement_expiry_date", false),
+    ("DDD", "limited_duration", false),
+    ("DAW", "weight_pounds", true),
+    ("DAX", "weight_kilograms", true),
+    ("DDH", "under_18_until", true),
+    ("DDI", "under_19_until", true),
+    ("DDJ", "under_21_until", true),
+    ("DDK", "organ_donor", true),
+    ("DDL", "veteran", true),
+    // elements of versions 1 to 3 that were later replaced, DAB, DAE and DBH are named like
+    // the element that replaced them
+    ("DAA", "full_name", true),
+    ("DAB", "family_name", true),
+    ("DAE", "name_suffix", true),
+    ("DAF", "name_prefix", true),
+    ("DCT", "given_names", true),
+    ("DAL", "residence_street", true),
+    ("DAM", "residence_street_2", true),
+    ("DAN", "residence_city", true),
+    ("DAO", "residence_jurisdiction", true),
+    ("DAP", "residence_postal_code", true),
+    ("DAR", "licence_classification", false),
+    ("DAS", "licence_restrictions", false),
+    ("DAT", "licence_endorsements", false),
+    ("DBK", "social_security_number", true),
+    ("DBH", "organ_donor", true),
+];
+
+/// Elements every DL and ID subfile has from version 4.
+const MANDATORY: &[&str] = &[
+    "DBA", "DCS", "DAC", "DAD", "DBD", "DBB", "DBC", "DAY", "DAU", "DAG", "DAI", "DAJ", "DAK",
+    "DAQ", "DCF", "DCG", "DDE", "DDF", "DDG",
+];
+
+/// Mandatory only in driver licence subfiles.
+const MANDATORY_DL: &[&str] = &["DCA", "DCB", "DCD"];
+
+const DATES: &[&str] = &["DBA", "DBD", "DBB", "DDB", "DDC", "DDH", "DDI", "DDJ"];
+
+#[derive(Debug, Serialize)]
+pub struct Subfile {
+    /// `DL`, `ID`, or `Z` and a letter for jurisdiction specific data.
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub description: &'static str,
+    /// Named fields, with dates as YYYY-MM-DD. Elements that are not known keep their ID.
+    pub fields: Map<String, Value>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Aamva {
+    /// Issuer identification number of the jurisdiction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iin: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jurisdiction_version: Option<u32>,
+    pub subfiles: Vec<Subfile>,
+    /// Problems that make the data unusable.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    /// Deviations from the standard that were worked around.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    pub valid: bool,
+}
+
+/// Parse the text of a PDF417 symbol on a US or Canadian driver licence or ID card, as laid
+/// out by the AAMVA DL/ID Card Design Standard. With `redact_pii` personal data such as
+/// names, addresses, the date of birth and the licence number is replaced by `[REDACTED]`,
+/// as are elements that are not known and everything jurisdiction specific.
+pub fn parse(data: &str, redact_pii: bool) -> Aamva {
+    let mut aamva = Aamva {
+        iin: None,
+        version: None,
+        jurisdiction_version: None,
+        subfiles: Vec::new(),
+        errors: Vec::new(),
+        warnings: Vec::new(),
+        valid: false,
+    };
+    if let Err(error) = parse_into(&mut aamva, data, redact_pii) {
+        aamva.errors.push(error);
+    }
+    aamva.valid = aamva.errors.is_empty();
+    aamva
+}
+
+fn parse_into(aamva: &mut Aamva, data: &str, redact_pii: bool) -> Result<(), String> {
+    if !data.starts_with('@') {
+        return Err("the data does not start with the compliance indicator '@'".to_owned());
+    }
+    let separators: Vec<char> = data.chars().skip(1).take(3).collect();
+    if separators != [DATA_ELEMENT_SEPARATOR, RECORD_SEPARATOR, SEGMENT_TERMINATOR] {
+        aamva.warnings.push(format!(
+            "the header separators are {:?}, expected LF, RS and CR",
+            separators.iter().collect::<String>()
+        ));
+    }
+
+    // the file type is 'ANSI ' from version 1, 'AAMVA' before
+    let start = ["ANSI ", "AAMVA"]
+        .iter()
+        .filter_map(|file_type| data.find(file_type))
+        .min()
+        .ok_or("the header has no file type 'ANSI '")?;
+    let mut header = Reader::new(&data[start + 5..]);
+
+    let iin = header.take(6, "IIN")?;
+    if !iin.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("the IIN '{iin}' is not 6 digits"));
+    }
+    if !(iin.starts_with("636") || iin.starts_with("604")) {
+        aamva.warnings.push(format!(
+            "the IIN {iin} is outside the ranges assigned to AAMVA jurisdictions"
+        ));
+    }
+    aamva.iin = Some(iin.to_owned());
+
+    let version = header.number(2, "version")?;
+    if version > LATEST_VERSION {
+        aamva.warnings.push(format!(
+            "version {version} is newer than version {LATEST_VERSION}, the latest known"
+        ));
+    }
+    aamva.version = Some(version);
+    if version >= 2 {
+        aamva.jurisdiction_version = Some(header.number(2, "jurisdiction version")?);
+    }
+    let entries = header.number(2, "number of entries")?;
+
+    let mut designators = Vec::new();
+    for _ in 0..entries {
+        let kind = header.take(2, "subfile type")?;
+        let offset = header.number(4, "subfile offset")? as usize;
+        let length = header.number(4, "subfile length")? as usize;
+        designators.push((kind, offset, length));
+    }
+    let header_end = data.len() - header.rest.len();
+
+    for (kind, offset, length) in designators {
+        // many issuers get the offsets wrong, so fall back to searching for the type
+        let body = match data.get(offset..offset + length) {
+            Some(body) if body.starts_with(kind) => body,
+            _ => {
+                let found = data[header_end..]
+                    .find(kind)
+                    .map(|i| header_end + i)
+                    .ok_or(format!("subfile {kind} is missing"))?;
+                aamva.warnings.push(format!(
+                    "subfile {kind} is at offset {found}, not {offset} as the header says"
+                ));
+                &data[found..]
+            }
+        };
+        let body = &body[kind.len()..];
+        let body = body.split(SEGMENT_TERMINATOR).next().unwrap_or(body);
+        let subfile = parse_subfile(aamva, kind, body, version, redact_pii);
+        aamva.subfiles.push(subfile);
+    }
+    Ok(())
+}
+
+fn parse_subfile(
+    aamva: &mut Aamva,
+    kind: &str,
+    body: &str,
+    version: u32,
+    redact_pii: bool,
+) -> Subfile {
+    let mut elements: Vec<(&str, &str)> = Vec::new();
+    for element in body.split([DATA_ELEMENT_SEPARATOR, RECORD_SEPARATOR]) {
+        let element = element.trim_end();
+        if element.len() < 3 || !element.is_char_boundary(3) {
+            continue;
+        }
+        elements.push(element.split_at(3));
+    }
+
+    let is_jurisdiction_specific = kind.starts_with('Z');
+    let is_canadian = elements
+        .iter()
+        .any(|(id, value)| *id == "DCG" && value.trim() == "CAN");
+    let mut fields = Map::new();
+    for (id, value) in &elements {
+        let known = ELEMENTS.iter().find(|(element, _, _)| element == id);
+        let name = known.map_or(*id, |(_, name, _)| *name);
+        // what unknown elements and jurisdictions' own elements and subfiles hold is not
+        // known, so all of it is redacted
+        let is_pii = match known {
+            _ if is_jurisdiction_specific || id.starts_with('Z') => true,
+            Some((_, _, pii)) => *pii,
+            None => true,
+        };
+        let value = value.trim();
+
+        let value = if redact_pii && is_pii {
+            REDACTED.to_owned()
+        } else if DATES.contains(id) {
+            match parse_date(value, version, is_canadian) {
+                Some(date) => date.to_string(),
+                None => {
+                    aamva
+                        .warnings
+                        .push(format!("{id} ({name}) '{value}' is not a valid date"));
+                    value.to_owned()
+                }
+            }
+        } else if *id == "DBC" {
+            describe_sex(value).to_owned()
+        } else {
+            value.to_owned()
+        };
+        // the replaced elements share their name with the current ones, the first is kept
+        match fields.get(name) {
+            Some(Value::String(first)) => {
+                if *first != value {
+                    aamva.warnings.push(format!(
+                        "{id} ({name}) '{value}' differs from the earlier {name} '{first}' and is ignored"
+                    ));
+                }
+            }
+            _ => {
+                fields.insert(name.to_owned(), Value::String(value));
+            }
+        }
+    }
+
+    if version >= 4 && matches!(kind, "DL" | "ID") {
+        let mandatory = MANDATORY
+            .iter()
+            .chain(if kind == "DL" { MANDATORY_DL } else { &[] });
+        for id in mandatory {
+            if !elements.iter().any(|(element, _)| element == id) {
+                aamva
+                    .warnings
+                    .push(format!("subfile {kind} has no {id}, which is mandatory"));
+            }
+        }
+    }
+
+    Subfile {
+        kind: kind.to_owned(),
+        description: match kind {
+            "DL" => "driver licence",
+            "ID" => "identification card",
+            "EN" => "enhanced driver licence",
+            _ if is_jurisdiction_specific => "jurisdiction specific",
+            _ => "unknown",
+        },
+        fields,
+    }
+}
+
+/// Dates are MMDDCCYY in the US and CCYYMMDD in Canada, version 1 always used CCYYMMDD.
+fn parse_date(value: &str, version: u32, is_canadian: bool) -> Option<NaiveDate> {
+    if value.len() != 8 || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| value[range].parse::<u32>().ok();
+    let date = |year: u32, month, day| NaiveDate::from_ymd_opt(year as i32, month, day);
+    let year_first = || date(number(0..4)?, number(4..6)?, number(6..8)?);
+    let month_first = || date(number(4..8)?, number(0..2)?, number(2..4)?);
+    if version <= 1 || is_canadian {
+        year_first().or_else(month_first)
+    } else {
+        month_first().or_else(year_first)
+    }
+}
+
+fn describe_sex(value: &str) -> &str {
+    match value {
+        "1" | "M" => "male",
+        "2" | "F" => "female",
+        "9" => "not specified",
+        other => other,
+    }
+}
+
+/// Reads the fixed width fields of the header.
+struct Reader<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn new(rest: &'a str) -> Self {
+        Reader { rest }
+    }
+
+    fn take(&mut self, length: usize, what: &str) -> Result<&'a str, String> {
+        let field = self
+            .rest
+            .get(..length)
+            .ok_or(format!("the header ends before the {what}"))?;
+        self.rest = &self.rest[length..];
+        Ok(field)
+    }
+
+    fn number(&mut self, length: usize, what: &str) -> Result<u32, String> {
+        let field = self.take(length, what)?;
+        field
+            .parse()
+            .map_err(|_| format!("the {what} '{field}' is not a number"))
+    }
+}
+
+impl Aamva {
+    /// One line per field, for the text output.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "AAMVA: IIN {}, version {}",
+            self.iin.as_deref().unwrap_or("unknown"),
+            self.version.map_or("unknown".to_owned(), |v| v.to_string())
+        );
+        for subfile in &self.subfiles {
+            text.push_str(&format!("\n  {} ({}):", subfile.kind, subfile.description));
+            for (name, value) in &subfile.fields {
+                let value = value.as_str().unwrap_or_default();
+                text.push_str(&format!("\n    {name}: {}", value.escape_default()));
+            }
+        }
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        for warning in &self.warnings {
+            text.push_str(&format!("\n  warning: {warning}"));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A version 8 licence with a single DL subfile, its offset and length as in the header.
+    fn licence(elements: &str) -> String {
+        let body = format!("DL{elements}\r");
+        format!("@\n\u{1e}\rANSI 636014080001DL0031{:04}{body}", body.len())
+    }
+
+    #[test]
+    fn parse_licence() {
+        let aamva = parse(
+            &licence("DAQD1234567\nDCSDOE\nDACJANE\nDBB01311990\nDBC2\nDCGUSA"),
+            false,
+        );
+        assert_eq!(aamva.iin.as_deref(), Some("636014"));
+        assert_eq!(aamva.version, Some(8));
+        let fields = &aamva.subfiles[0].fields;
+        assert_eq!(fields["family_name"], "DOE");
+        assert_eq!(fields["date_of_birth"], "1990-01-31");
+        assert_eq!(fields["sex"], "female");
+        assert!(aamva.errors.is_empty());
+
+        let redacted = parse(&licence("DAQD1234567\nDCSDOE\nDCGUSA"), true);
+        assert_eq!(redacted.subfiles[0].fields["family_name"], REDACTED);
+    }
+
+    #[test]
+    fn redact_unknown_and_jurisdiction_elements() {
+        let licence = "DLDCSDOE\nDCAD\nDXXSECRET\nZNAJANE\r";
+        let jurisdiction = "ZNZNBDOE\nDCAC\r";
+        // the header is 21 characters and two subfile designators of 10
+        let data = format!(
+            "@\n\u{1e}\rANSI 636014080002DL0041{:04}ZN{:04}{:04}{licence}{jurisdiction}",
+            licence.len(),
+            41 + licence.len(),
+            jurisdiction.len()
+        );
+        let aamva = parse(&data, true);
+        assert_eq!(aamva.subfiles.len(), 2, "{:?}", aamva.errors);
+        assert!(!aamva
+            .warnings
+            .iter()
+            .any(|warning| warning.contains("offset")));
+        let licence = &aamva.subfiles[0].fields;
+        assert_eq!(licence["vehicle_class"], "D");
+        assert_eq!(licence["DXX"], REDACTED);
+        assert_eq!(licence["ZNA"], REDACTED);
+        // in a jurisdiction subfile even a known element is redacted
+        let jurisdiction = &aamva.subfiles[1].fields;
+        assert_eq!(jurisdiction["ZNB"], REDACTED);
+        assert_eq!(jurisdiction["vehicle_class"], REDACTED);
+
+        let aamva = parse(&data, false);
+        assert_eq!(aamva.subfiles[0].fields["DXX"], "SECRET");
+    }
+
+    #[test]
+    fn replaced_element_does_not_overwrite() {
+        let aamva = parse(&licence("DCSDOE\nDABSMITH\nDDK1\nDBHN"), false);
+        let fields = &aamva.subfiles[0].fields;
+        assert_eq!(fields["family_name"], "DOE");
+        assert_eq!(fields["organ_donor"], "1");
+        assert!(aamva
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("DAB (family_name) 'SMITH' differs")));
+    }
+
+    #[test]
+    fn missing_compliance_indicator_is_an_error() {
+        let aamva = parse("ANSI 636014080001DL00310010DLDCSDOE\r", false);
+        assert!(!aamva.valid);
+    }
+}
@@ -0,0 +1,392 @@
+use chrono::{Datelike, NaiveDate};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// The fields of one flight leg, the mandatory ones followed by the conditional ones.
+const LEG_MANDATORY: &[(&str, usize)] = &[
+    ("pnr", 7),
+    ("from", 3),
+    ("to", 3),
+    ("operating_carrier", 3),
+    ("flight_number", 5),
+    ("flight_day_of_year", 3),
+    ("compartment", 1),
+    ("seat", 4),
+    ("check_in_sequence_number", 5),
+    ("passenger_status", 1),
+];
+
+const LEG_CONDITIONAL: &[(&str, usize)] = &[
+    ("airline_numeric_code", 3),
+    ("document_serial_number", 10),
+    ("selectee_indicator", 1),
+    ("international_document_verification", 1),
+    ("marketing_carrier", 3),
+    ("frequent_flyer_airline", 3),
+    ("frequent_flyer_number", 16),
+    ("id_ad_indicator", 1),
+    ("free_baggage_allowance", 3),
+    ("fast_track", 1),
+];
+
+/// The conditional fields that appear once, in the first leg.
+const UNIQUE_CONDITIONAL: &[(&str, usize)] = &[
+    ("passenger_description", 1),
+    ("check_in_source", 1),
+    ("boarding_pass_issuance_source", 1),
+    ("issue_date_julian", 4),
+    ("document_type", 1),
+    ("boarding_pass_issuer", 3),
+    ("baggage_tag", 13),
+    ("first_non_consecutive_baggage_tag", 13),
+    ("second_non_consecutive_baggage_tag", 13),
+];
+
+#[derive(Debug, Serialize)]
+pub struct Bcbp {
+    /// The fields that appear once: the passenger, and the boarding pass itself.
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+    pub legs: Vec<Map<String, Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub security: Option<Map<String, Value>>,
+    /// Fields that are missing, too short, or whose size does not match the declared one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+/// Parse an IATA Bar Coded Boarding Pass (Resolution 792). Julian dates are converted to
+/// calendar dates relative to `today`: a flight date to the nearest matching day, the issue
+/// date, whose year only has its last digit, to the latest matching day that is not later.
+pub fn parse(data: &str, today: NaiveDate) -> Bcbp {
+    let mut bcbp = Bcbp {
+        fields: Map::new(),
+        legs: Vec::new(),
+        security: None,
+        errors: Vec::new(),
+        valid: false,
+    };
+    parse_into(&mut bcbp, data, today);
+    bcbp.valid = bcbp.errors.is_empty();
+    bcbp
+}
+
+fn parse_into(bcbp: &mut Bcbp, data: &str, today: NaiveDate) {
+    if !data.is_ascii() {
+        bcbp.errors
+            .push("the data contains characters that are not ASCII".to_owned());
+        return;
+    }
+    let mut reader = Reader::new(data, "");
+    let errors = &mut bcbp.errors;
+
+    let Some(format_code) = reader.take("format_code", 1, errors) else {
+        return;
+    };
+    if format_code != "M" {
+        errors.push(format!(
+            "format_code is '{format_code}', only M (multiple legs) is defined"
+        ));
+        return;
+    }
+    let Some(legs) = reader.take("number_of_legs", 1, errors) else {
+        return;
+    };
+    let legs = match legs.parse::<usize>() {
+        Ok(legs) if legs > 0 => legs,
+        _ => {
+            errors.push(format!(
+                "number_of_legs '{legs}' is not a digit from 1 to 9"
+            ));
+            return;
+        }
+    };
+    bcbp.fields
+        .insert("format_code".to_owned(), format_code.into());
+    bcbp.fields.insert("number_of_legs".to_owned(), legs.into());
+    reader.read_into(
+        &mut bcbp.fields,
+        &[("passenger_name", 20), ("electronic_ticket", 1)],
+        errors,
+    );
+
+    for leg_number in 1..=legs {
+        let mut leg = Map::new();
+        reader.context = format!("leg {leg_number} ");
+        if !reader.read_into(&mut leg, LEG_MANDATORY, errors) {
+            bcbp.legs.push(leg);
+            return;
+        }
+        if let Some(day) = leg["flight_day_of_year"].as_str().map(str::to_owned) {
+            match flight_date(&day, today) {
+                Some(date) => {
+                    leg.insert("flight_date".to_owned(), date.to_string().into());
+                }
+                None => errors.push(format!(
+                    "leg {leg_number} flight_day_of_year '{day}' is not a valid day of the year"
+                )),
+            }
+        }
+
+        let Some(mut variable) = reader.sized("variable_field_size", errors) else {
+            bcbp.legs.push(leg);
+            return;
+        };
+        if leg_number == 1 && variable.rest.starts_with('>') {
+            variable.skip(1);
+            variable.read_into(&mut bcbp.fields, &[("version", 1)], errors);
+            if let Some(mut unique) = variable.sized("unique_conditional_size", errors) {
+                unique.read_conditional(&mut bcbp.fields, UNIQUE_CONDITIONAL, errors);
+            }
+            let issued = bcbp.fields.get("issue_date_julian").and_then(Value::as_str);
+            if let Some(issued) = issued.map(str::to_owned) {
+                if let Some(date) = issue_date(&issued, today) {
+                    bcbp.fields
+                        .insert("issue_date".to_owned(), date.to_string().into());
+                } else if !issued.is_empty() {
+                    errors.push(format!(
+                        "issue_date_julian '{issued}' is not a year digit and a day from 001 to 366"
+                    ));
+                }
+            }
+        }
+        if !variable.rest.is_empty() {
+            if let Some(mut repeated) = variable.sized("repeated_conditional_size", errors) {
+                repeated.read_conditional(&mut leg, LEG_CONDITIONAL, errors);
+            }
+        }
+        if !variable.rest.trim().is_empty() {
+            leg.insert("airline_use".to_owned(), variable.rest.into());
+        }
+        bcbp.legs.push(leg);
+    }
+
+    reader.context.clear();
+    if reader.rest.starts_with('^') {
+        reader.skip(1);
+        let mut security = Map::new();
+        reader.read_into(&mut security, &[("type", 1)], errors);
+        if let Some(data) = reader.sized("security_data_size", errors) {
+            security.insert("data".to_owned(), data.rest.into());
+        }
+        bcbp.security = Some(security);
+    }
+    if !reader.rest.is_empty() {
+        errors.push(format!(
+            "{} characters after the last leg are not security data",
+            reader.rest.len()
+        ));
+    }
+}
+
+/// The date of a flight on `day_of_year`, in the year that puts it closest to `today`.
+fn flight_date(day_of_year: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let day: u32 = day_of_year.trim().parse().ok()?;
+    [today.year() - 1, today.year(), today.year() + 1]
+        .into_iter()
+        .filter_map(|year| NaiveDate::from_yo_opt(year, day))
+        .min_by_key(|date| (*date - today).num_days().abs())
+}
+
+/// An issue date of the last digit of the year followed by the day of the year, in the most
+/// recent matching year.
+fn issue_date(julian: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let year_digit: i32 = julian.get(..1)?.parse().ok()?;
+    let day: u32 = julian.get(1..)?.parse().ok()?;
+    let year = today.year() - (today.year() - year_digit).rem_euclid(10);
+    [year, year - 10]
+        .into_iter()
+        .filter_map(|year| NaiveDate::from_yo_opt(year, day))
+        .find(|date| *date <= today)
+}
+
+/// Reads the fixed width fields of a section, `context` names the leg in errors.
+struct Reader<'a> {
+    rest: &'a str,
+    context: String,
+}
+
+impl<'a> Reader<'a> {
+    fn new(rest: &'a str, context: &str) -> Self {
+        Reader {
+            rest,
+            context: context.to_owned(),
+        }
+    }
+
+    fn skip(&mut self, length: usize) {
+        self.rest = &self.rest[length.min(self.rest.len())..];
+    }
+
+    fn take(&mut self, name: &str, length: usize, errors: &mut Vec<String>) -> Option<&'a str> {
+        if self.rest.len() < length {
+            errors.push(format!(
+                "{}{name} needs {length} characters, only {} are left",
+                self.context,
+                self.rest.len()
+            ));
+            self.rest = "";
+            return None;
+        }
+        let (field, rest) = self.rest.split_at(length);
+        self.rest = rest;
+        Some(field)
+    }
+
+    /// Read mandatory `fields`, trimmed, and tell whether they were all there.
+    fn read_into(
+        &mut self,
+        map: &mut Map<String, Value>,
+        fields: &[(&str, usize)],
+        errors: &mut Vec<String>,
+    ) -> bool {
+        for (name, length) in fields {
+            let Some(value) = self.take(name, *length, errors) else {
+                return false;
+            };
+            map.insert((*name).to_owned(), value.trim().into());
+        }
+        true
+    }
+
+    /// Read conditional `fields`, which may end early but only at a field boundary.
+    fn read_conditional(
+        &mut self,
+        map: &mut Map<String, Value>,
+        fields: &[(&str, usize)],
+        errors: &mut Vec<String>,
+    ) {
+        for (name, length) in fields {
+            if self.rest.is_empty() {
+                return;
+            }
+            let Some(value) = self.take(name, *length, errors) else {
+                return;
+            };
+            let value = value.trim();
+            if !value.is_empty() {
+                map.insert((*name).to_owned(), value.into());
+            }
+        }
+        if !self.rest.is_empty() {
+            errors.push(format!(
+                "{}{} characters are left over after the conditional fields",
+                self.context,
+                self.rest.len()
+            ));
+        }
+    }
+
+    /// A section preceded by its size as two hexadecimal digits.
+    fn sized(&mut self, name: &str, errors: &mut Vec<String>) -> Option<Reader<'a>> {
+        let size = self.take(name, 2, errors)?;
+        let Ok(size) = usize::from_str_radix(size, 16) else {
+            errors.push(format!(
+                "{}{name} '{size}' is not a hexadecimal number",
+                self.context
+            ));
+            return None;
+        };
+        // a section cut short is still read as far as it goes
+        let section = match self.rest.get(..size) {
+            Some(section) => section,
+            None => {
+                errors.push(format!(
+                    "{}{name} is {size}, but only {} characters are left",
+                    self.context,
+                    self.rest.len()
+                ));
+                self.rest
+            }
+        };
+        self.skip(size);
+        Some(Reader::new(section, &self.context))
+    }
+}
+
+impl Bcbp {
+    /// One line per field, for the text output.
+    pub fn to_text(&self) -> String {
+        let line = |(name, value): (&String, &Value)| match value {
+            Value::String(s) => format!("{name}: {}", s.escape_default()),
+            other => format!("{name}: {other}"),
+        };
+        let mut text = "BCBP:".to_owned();
+        for field in &self.fields {
+            text.push_str(&format!("\n  {}", line(field)));
+        }
+        for (i, leg) in self.legs.iter().enumerate() {
+            text.push_str(&format!("\n  leg {}:", i + 1));
+            for field in leg {
+                text.push_str(&format!("\n    {}", line(field)));
+            }
+        }
+        if let Some(security) = &self.security {
+            text.push_str("\n  security:");
+            for field in security {
+                text.push_str(&format!("\n    {}", line(field)));
+            }
+        }
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASS: &str = "M1DESMARAIS/LUC       EABC123 YULFRAAC 0834 326J001A0025 100";
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 11, 20).unwrap()
+    }
+
+    #[test]
+    fn parse_mandatory_fields() {
+        let bcbp = parse(PASS, today());
+        assert!(bcbp.valid, "{:?}", bcbp.errors);
+        assert_eq!(bcbp.fields["passenger_name"], "DESMARAIS/LUC");
+        let leg = &bcbp.legs[0];
+        assert_eq!(leg["pnr"], "ABC123");
+        assert_eq!(leg["from"], "YUL");
+        assert_eq!(leg["to"], "FRA");
+        assert_eq!(leg["seat"], "001A");
+        assert_eq!(leg["flight_date"], "2026-11-22");
+    }
+
+    #[test]
+    fn parse_conditional_fields() {
+        let data = format!(
+            "{}1E>50B1OO3329BAC 0D0140123456789",
+            &PASS[..PASS.len() - 2]
+        );
+        let bcbp = parse(&data, today());
+        assert!(bcbp.valid, "{:?}", bcbp.errors);
+        assert_eq!(bcbp.fields["version"], "5");
+        assert_eq!(bcbp.fields["issue_date"], "2023-11-25");
+        assert_eq!(bcbp.fields["boarding_pass_issuer"], "AC");
+        assert_eq!(bcbp.legs[0]["document_serial_number"], "0123456789");
+    }
+
+    #[test]
+    fn flight_date_nearest_to_today() {
+        assert_eq!(
+            flight_date("005", today()),
+            NaiveDate::from_ymd_opt(2027, 1, 5)
+        );
+        assert_eq!(flight_date("367", today()), None);
+    }
+
+    #[test]
+    fn invalid_passes() {
+        assert!(!parse("X1DESMARAIS/LUC", today()).valid);
+        assert!(!parse("M0", today()).valid);
+        assert!(!parse(&PASS[..40], today()).valid);
+        assert!(!parse(&format!("{PASS}extra"), today()).valid);
+        assert!(!parse("M1DÉSMARAIS", today()).valid);
+    }
+}
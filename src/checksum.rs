@@ -0,0 +1,312 @@
+use sha2::{Digest, Sha256};
+
+/// ISO 13616: the country code and check digits are moved to the end, letters become 10 to
+/// 35, and the number must leave a remainder of 1 when divided by 97.
+pub fn iban_is_valid(iban: &str) -> bool {
+    iban.len() > 4 && iban.is_ascii() && mod_97(&format!("{}{}", &iban[4..], &iban[..4])) == Some(1)
+}
+
+/// The remainder of an alphanumeric reference divided by 97, letters counting as 10 to 35.
+pub fn mod_97(value: &str) -> Option<u32> {
+    value.chars().try_fold(0, |remainder, c| {
+        let digit = c.to_digit(36)?;
+        Some(if digit < 10 {
+            (remainder * 10 + digit) % 97
+        } else {
+            (remainder * 100 + digit) % 97
+        })
+    })
+}
+
+/// The two check digits of an ISO 11649 creditor reference for `reference`.
+pub fn creditor_reference_check_digits(reference: &str) -> Option<u32> {
+    if reference.is_empty() || reference.len() > 21 {
+        return None;
+    }
+    Some(98 - mod_97(&format!("{reference}RF00"))?)
+}
+
+/// What is wrong with an ISO 11649 creditor reference: RF, two check digits and up to 21
+/// letters and digits, which leave a remainder of 1 like an IBAN.
+pub fn creditor_reference_error(reference: &str) -> Option<String> {
+    let valid_form = reference.len() >= 5
+        && reference.len() <= 25
+        && reference.bytes().all(|b| b.is_ascii_alphanumeric())
+        && reference.starts_with("RF")
+        && reference[2..4].bytes().all(|b| b.is_ascii_digit());
+    if !valid_form {
+        Some(format!(
+            "the creditor reference '{reference}' is not RF, two check digits and up to 21 letters and digits"
+        ))
+    } else if !iban_is_valid(reference) {
+        Some(format!(
+            "the creditor reference '{reference}' has the wrong check digits (mod 97)"
+        ))
+    } else {
+        None
+    }
+}
+
+pub const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const BECH32_CONSTANT: u32 = 1;
+const BECH32M_CONSTANT: u32 = 0x2bc8_30a3;
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bech32Variant {
+    /// BIP 173, for segwit version 0 addresses, Lightning invoices and LNURLs.
+    Bech32,
+    /// BIP 350, for segwit version 1 and later addresses.
+    Bech32m,
+}
+
+/// The human readable part and the 5 bit data words of a bech32 or bech32m string, with the
+/// checksum checked and removed. The length is not limited, so that invoices fit.
+pub fn bech32_decode(text: &str) -> Result<(String, Vec<u8>, Bech32Variant), String> {
+    if text.bytes().any(|b| b.is_ascii_lowercase()) && text.bytes().any(|b| b.is_ascii_uppercase())
+    {
+        return Err("it mixes upper and lower case".to_owned());
+    }
+    let text = text.to_ascii_lowercase();
+    let separator = text
+        .rfind('1')
+        .ok_or("it has no separator '1'".to_owned())?;
+    let (hrp, data) = (&text[..separator], &text[separator + 1..]);
+    if hrp.is_empty() || data.len() < 6 {
+        return Err("the human readable part or the checksum is missing".to_owned());
+    }
+    let words = data
+        .bytes()
+        .map(|b| {
+            BECH32_CHARSET
+                .iter()
+                .position(|c| *c == b)
+                .map(|word| word as u8)
+                .ok_or(format!("'{}' is not a bech32 character", b as char))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values.extend(&words);
+    let variant = match bech32_polymod(&values) {
+        BECH32_CONSTANT => Bech32Variant::Bech32,
+        BECH32M_CONSTANT => Bech32Variant::Bech32m,
+        _ => return Err("the bech32 checksum is wrong".to_owned()),
+    };
+    Ok((hrp.to_owned(), words[..words.len() - 6].to_vec(), variant))
+}
+
+/// A bech32 string of `hrp` and the 5 bit `words`, for building test vectors.
+#[cfg(test)]
+pub fn bech32_encode(hrp: &str, words: &[u8]) -> String {
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values.extend(words);
+    values.extend([0; 6]);
+    let checksum = bech32_polymod(&values) ^ BECH32_CONSTANT;
+    let checksum = (0..6).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8);
+    let data: String = words
+        .iter()
+        .copied()
+        .chain(checksum)
+        .map(|word| BECH32_CHARSET[usize::from(word)] as char)
+        .collect();
+    format!("{hrp}1{data}")
+}
+
+fn bech32_polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [
+        0x3b6a_57b2,
+        0x2650_8e6d,
+        0x1ea1_19fa,
+        0x3d42_33dd,
+        0x2a14_62b3,
+    ];
+    values.iter().fold(1, |checksum, value| {
+        let top = checksum >> 25;
+        let checksum = ((checksum & 0x01ff_ffff) << 5) ^ u32::from(*value);
+        GENERATOR
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| (top >> i) & 1 == 1)
+            .fold(checksum, |checksum, (_, generator)| checksum ^ generator)
+    })
+}
+
+/// Regroup 5 bit words into bytes, failing on padding that is not zero or too long.
+pub fn five_to_eight_bits(words: &[u8]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(words.len() * 5 / 8);
+    let (mut accumulator, mut bits) = (0u32, 0);
+    for word in words {
+        accumulator = (accumulator << 5) | u32::from(*word);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+        }
+    }
+    (bits < 5 && accumulator & ((1 << bits) - 1) == 0).then_some(bytes)
+}
+
+/// The payload of a base58check string, with the double SHA-256 checksum checked and removed.
+pub fn base58check_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in text.bytes() {
+        let digit = BASE58_ALPHABET
+            .iter()
+            .position(|a| *a == c)
+            .ok_or(format!("'{}' is not a base58 character", c as char))?;
+        // bytes is a big endian number, multiplied by 58 before the digit is added
+        let mut carry = digit as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = text.bytes().take_while(|c| *c == b'1').count();
+    let mut decoded = vec![0; zeros];
+    decoded.extend(bytes);
+
+    if decoded.len() < 5 {
+        return Err("it is too short for a checksum".to_owned());
+    }
+    let (payload, checksum) = decoded.split_at(decoded.len() - 4);
+    let hash = Sha256::digest(Sha256::digest(payload));
+    if &hash[..4] != checksum {
+        return Err("the base58 checksum is wrong".to_owned());
+    }
+    Ok(payload.to_vec())
+}
+
+/// The network and type of a Bitcoin address, checking its base58check or bech32 checksum.
+pub fn bitcoin_address(address: &str) -> Result<(&'static str, &'static str), String> {
+    let lower = address.to_ascii_lowercase();
+    let segwit_network = [("bc1", "mainnet"), ("tb1", "testnet"), ("bcrt1", "regtest")]
+        .into_iter()
+        .find(|(prefix, _)| lower.starts_with(prefix));
+    if let Some((_, network)) = segwit_network {
+        if address.len() > 90 {
+            return Err("it is longer than 90 characters".to_owned());
+        }
+        let (_, words, variant) = bech32_decode(address)?;
+        let (version, program) = words.split_first().ok_or("it has no witness version")?;
+        let program = five_to_eight_bits(program).ok_or("the witness program is malformed")?;
+        let expected = if *version == 0 {
+            Bech32Variant::Bech32
+        } else {
+            Bech32Variant::Bech32m
+        };
+        if *version > 16 || variant != expected {
+            return Err(format!(
+                "witness version {version} does not match its {variant:?} checksum"
+            ));
+        }
+        return match (version, program.len()) {
+            (0, 20) => Ok((network, "p2wpkh")),
+            (0, 32) => Ok((network, "p2wsh")),
+            (1, 32) => Ok((network, "p2tr")),
+            (0, length) => Err(format!(
+                "a version 0 witness program is 20 or 32 bytes, not {length}"
+            )),
+            (_, 2..=40) => Ok((network, "segwit")),
+            (_, length) => Err(format!("a witness program is 2 to 40 bytes, not {length}")),
+        };
+    }
+
+    let payload = base58check_decode(address)?;
+    if payload.len() != 21 {
+        return Err(format!(
+            "a base58 address holds 21 bytes, not {}",
+            payload.len()
+        ));
+    }
+    match payload[0] {
+        0x00 => Ok(("mainnet", "p2pkh")),
+        0x05 => Ok(("mainnet", "p2sh")),
+        0x6f => Ok(("testnet", "p2pkh")),
+        0xc4 => Ok(("testnet", "p2sh")),
+        other => Err(format!(
+            "version byte {other:#04x} is not a Bitcoin address"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iban() {
+        assert!(iban_is_valid("GB82WEST12345698765432"));
+        assert!(iban_is_valid("CH9300762011623852957"));
+        assert!(!iban_is_valid("GB83WEST12345698765432"));
+        assert!(!iban_is_valid("GB82"));
+    }
+
+    #[test]
+    fn creditor_reference() {
+        assert_eq!(creditor_reference_check_digits("539007547034"), Some(18));
+        assert_eq!(creditor_reference_error("RF18539007547034"), None);
+        assert!(creditor_reference_error("RF19539007547034")
+            .unwrap()
+            .contains("wrong check digits"));
+        assert!(creditor_reference_error("RF1").is_some());
+    }
+
+    #[test]
+    fn bech32() {
+        let (hrp, words, variant) = bech32_decode("A12UEL5L").unwrap();
+        assert_eq!(
+            (hrp.as_str(), words.len(), variant),
+            ("a", 0, Bech32Variant::Bech32)
+        );
+        let (_, _, variant) = bech32_decode("A1LQFN3A").unwrap();
+        assert_eq!(variant, Bech32Variant::Bech32m);
+        assert!(bech32_decode("A12UEL5M").is_err());
+        // mixed case is not allowed
+        assert!(bech32_decode("A12uEL5L").is_err());
+        assert_eq!(bech32_encode("a", &[]), "a12uel5l");
+    }
+
+    #[test]
+    fn base58check() {
+        let payload = base58check_decode("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2").unwrap();
+        assert_eq!(payload.len(), 21);
+        assert!(base58check_decode("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3").is_err());
+        assert!(base58check_decode("1BvBMSEYst0").is_err());
+    }
+
+    #[test]
+    fn bitcoin_addresses() {
+        assert_eq!(
+            bitcoin_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"),
+            Ok(("mainnet", "p2pkh"))
+        );
+        assert_eq!(
+            bitcoin_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"),
+            Ok(("mainnet", "p2sh"))
+        );
+        assert_eq!(
+            bitcoin_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            Ok(("mainnet", "p2wpkh"))
+        );
+        assert_eq!(
+            bitcoin_address("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0"),
+            Ok(("mainnet", "p2tr"))
+        );
+        assert!(bitcoin_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err());
+        // a version 1 program with a bech32 instead of a bech32m checksum
+        assert!(bitcoin_address(
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,629 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+use crate::{error::CliError, uri};
+
+/// A WiFi network in the `WIFI:` format of the ZXing project, which phone cameras also read.
+#[derive(Debug, Default)]
+pub struct Wifi {
+    pub ssid: String,
+    /// WPA, WEP, WPA2-EAP or nopass.
+    pub auth: String,
+    pub password: Option<String>,
+    pub hidden: bool,
+    pub eap_method: Option<String>,
+    pub phase2: Option<String>,
+    pub identity: Option<String>,
+    pub anonymous_identity: Option<String>,
+}
+
+impl Wifi {
+    /// The payload, failing if the password or EAP method the authentication needs is missing.
+    pub fn payload(&self) -> Result<String, CliError> {
+        let mut errors = Vec::new();
+        if self.ssid.is_empty() {
+            errors.push("the SSID is empty".to_owned());
+        }
+        match self.auth.as_str() {
+            "WPA" | "WEP" if self.password.is_none() => {
+                errors.push(format!("{} needs a password", self.auth));
+            }
+            "WPA2-EAP" if self.eap_method.is_none() => {
+                errors.push("WPA2-EAP needs an EAP method".to_owned());
+            }
+            "nopass" if self.password.is_some() => {
+                errors.push("an open network (nopass) has no password".to_owned());
+            }
+            _ => {}
+        }
+        invalid("WiFi network", errors)?;
+
+        let mut payload = format!("WIFI:T:{};S:{};", self.auth, escape_wifi(&self.ssid));
+        for (field, value) in [
+            ("P", &self.password),
+            ("E", &self.eap_method),
+            ("PH2", &self.phase2),
+            ("I", &self.identity),
+            ("A", &self.anonymous_identity),
+        ] {
+            if let Some(value) = value {
+                payload.push_str(&format!("{field}:{};", escape_wifi(value)));
+            }
+        }
+        if self.hidden {
+            payload.push_str("H:true;");
+        }
+        payload.push(';');
+        Ok(payload)
+    }
+}
+
+/// A backslash before \ ; , " and :, so that a value can not end a field or start another one.
+fn escape_wifi(value: &str) -> String {
+    escape(value, &['\\', ';', ',', '"', ':'])
+}
+
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// A phone number, email address or postal address, with an optional type such as WORK.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Typed {
+    Plain(String),
+    WithType {
+        value: String,
+        #[serde(rename = "type")]
+        kind: Option<String>,
+    },
+}
+
+impl Typed {
+    fn parts(&self) -> (&str, Option<&str>) {
+        match self {
+            Typed::Plain(value) => (value, None),
+            Typed::WithType { value, kind } => (value, kind.as_deref()),
+        }
+    }
+}
+
+/// A contact read from the JSON file of --vcard-file or --mecard-file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Contact {
+    /// The full name as it is displayed.
+    pub name: String,
+    pub family_name: String,
+    pub given_name: String,
+    pub nickname: String,
+    pub org: String,
+    pub title: String,
+    pub phones: Vec<Typed>,
+    pub emails: Vec<Typed>,
+    pub addresses: Vec<Typed>,
+    pub urls: Vec<String>,
+    pub note: String,
+    /// YYYY-MM-DD.
+    pub birthday: String,
+}
+
+impl Contact {
+    fn check(&self) -> Result<Option<NaiveDate>, CliError> {
+        let mut errors = Vec::new();
+        if self.name.is_empty() && self.family_name.is_empty() && self.given_name.is_empty() {
+            errors.push("the contact has no name, family_name or given_name".to_owned());
+        }
+        let birthday = (!self.birthday.is_empty())
+            .then(|| NaiveDate::parse_from_str(&self.birthday, "%Y-%m-%d"))
+            .transpose()
+            .unwrap_or_else(|_| {
+                errors.push(format!(
+                    "the birthday '{}' is not YYYY-MM-DD",
+                    self.birthday
+                ));
+                None
+            });
+        invalid("contact", errors)?;
+        Ok(birthday)
+    }
+
+    /// The displayed name, from the parts if it is not given.
+    fn full_name(&self) -> String {
+        if self.name.is_empty() {
+            format!("{} {}", self.given_name, self.family_name)
+                .trim()
+                .to_owned()
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// A vCard 3.0, one property per line separated by line feeds as ZXing writes them.
+    pub fn vcard(&self) -> Result<String, CliError> {
+        let birthday = self.check()?;
+        let text = |value: &str| escape_vcard(value);
+        let name = if self.family_name.is_empty() && self.given_name.is_empty() {
+            text(&self.full_name())
+        } else {
+            format!("{};{};;;", text(&self.family_name), text(&self.given_name))
+        };
+        let mut lines = vec![
+            "BEGIN:VCARD".to_owned(),
+            "VERSION:3.0".to_owned(),
+            format!("N:{name}"),
+            format!("FN:{}", text(&self.full_name())),
+        ];
+        for (property, value) in [
+            ("NICKNAME", &self.nickname),
+            ("ORG", &self.org),
+            ("TITLE", &self.title),
+        ] {
+            if !value.is_empty() {
+                lines.push(format!("{property}:{}", text(value)));
+            }
+        }
+        for (property, values) in [
+            ("TEL", &self.phones),
+            ("EMAIL", &self.emails),
+            ("ADR", &self.addresses),
+        ] {
+            for typed in values {
+                let (value, kind) = typed.parts();
+                // a postal address is the street of the seven structured parts
+                let value = match property {
+                    "ADR" => format!(";;{};;;;", text(value)),
+                    _ => text(value),
+                };
+                match kind {
+                    Some(kind) => lines.push(format!("{property};TYPE={}:{value}", text(kind))),
+                    None => lines.push(format!("{property}:{value}")),
+                }
+            }
+        }
+        for url in &self.urls {
+            lines.push(format!("URL:{}", text(url)));
+        }
+        if let Some(birthday) = birthday {
+            lines.push(format!("BDAY:{}", birthday.format("%Y-%m-%d")));
+        }
+        if !self.note.is_empty() {
+            lines.push(format!("NOTE:{}", text(&self.note)));
+        }
+        lines.push("END:VCARD".to_owned());
+        Ok(lines.join("\n"))
+    }
+
+    /// A MECARD, the shorter format of NTT DoCoMo, with a name in the family,given form.
+    pub fn mecard(&self) -> Result<String, CliError> {
+        let birthday = self.check()?;
+        let text = |value: &str| escape(value, &['\\', ';', ',', ':']);
+        let name = if self.family_name.is_empty() && self.given_name.is_empty() {
+            text(&self.full_name())
+        } else {
+            format!("{},{}", text(&self.family_name), text(&self.given_name))
+        };
+        let mut payload = format!("MECARD:N:{name};");
+        if !self.org.is_empty() {
+            payload.push_str(&format!("ORG:{};", text(&self.org)));
+        }
+        for (field, values) in [
+            ("TEL", &self.phones),
+            ("EMAIL", &self.emails),
+            ("ADR", &self.addresses),
+        ] {
+            // MECARD has no types
+            for typed in values {
+                payload.push_str(&format!("{field}:{};", text(typed.parts().0)));
+            }
+        }
+        for url in &self.urls {
+            payload.push_str(&format!("URL:{};", text(url)));
+        }
+        if let Some(birthday) = birthday {
+            payload.push_str(&format!("BDAY:{};", birthday.format("%Y%m%d")));
+        }
+        if !self.note.is_empty() {
+            payload.push_str(&format!("NOTE:{};", text(&self.note)));
+        }
+        payload.push(';');
+        Ok(payload)
+    }
+}
+
+/// RFC 6350 and RFC 5545 text: a backslash before \ ; and , and line breaks as \n.
+fn escape_vcard(value: &str) -> String {
+    escape(&value.replace("\r\n", "\n"), &['\\', ';', ',']).replace(['\n', '\r'], "\\n")
+}
+
+/// An RFC 5724 `sms:` URI.
+pub fn sms(number: &str, body: Option<&str>) -> Result<String, CliError> {
+    let number = number.trim();
+    if !is_phone_number(number) {
+        invalid(
+            "SMS",
+            vec![format!(
+                "'{number}' is not a phone number of digits with an optional +"
+            )],
+        )?;
+    }
+    let query = uri::query(&[("body", body)]);
+    Ok(if query.is_empty() {
+        format!("sms:{number}")
+    } else {
+        format!("sms:{number}?{query}")
+    })
+}
+
+/// Digits with an optional leading +, and the spaces, hyphens, dots and brackets people write
+/// between them.
+fn is_phone_number(number: &str) -> bool {
+    let digits = number.strip_prefix('+').unwrap_or(number);
+    digits.bytes().any(|b| b.is_ascii_digit())
+        && digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b' ' | b'-' | b'.' | b'(' | b')'))
+}
+
+/// An RFC 6068 `mailto:` URI.
+#[derive(Debug, Default)]
+pub struct Email {
+    pub to: String,
+    pub cc: Option<String>,
+    pub bcc: Option<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+impl Email {
+    pub fn payload(&self) -> Result<String, CliError> {
+        let errors = [Some(&self.to), self.cc.as_ref(), self.bcc.as_ref()]
+            .into_iter()
+            .flatten()
+            .flat_map(|addresses| addresses.split(','))
+            .map(str::trim)
+            .filter(|address| !is_email_address(address))
+            .map(|address| format!("'{address}' is not an email address"))
+            .collect();
+        invalid("email", errors)?;
+
+        let list = |addresses: &str| {
+            addresses
+                .split(',')
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let (cc, bcc) = (self.cc.as_deref().map(list), self.bcc.as_deref().map(list));
+        let query = uri::query(&[
+            ("cc", cc.as_deref()),
+            ("bcc", bcc.as_deref()),
+            ("subject", self.subject.as_deref()),
+            ("body", self.body.as_deref()),
+        ]);
+        let to = list(&self.to)
+            .split(',')
+            .map(uri::path_encode)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut payload = format!("mailto:{to}");
+        if !query.is_empty() {
+            payload.push_str(&format!("?{query}"));
+        }
+        Ok(payload)
+    }
+}
+
+fn is_email_address(address: &str) -> bool {
+    address.split_once('@').is_some_and(|(local, domain)| {
+        !local.is_empty()
+            && domain.contains('.')
+            && !address.contains(|c: char| c.is_whitespace() || matches!(c, ',' | '?' | '&'))
+    })
+}
+
+/// An RFC 5870 `geo:` URI, with an optional search query as Android reads it.
+pub fn geo(
+    latitude: f64,
+    longitude: f64,
+    altitude: Option<f64>,
+    query: Option<&str>,
+) -> Result<String, CliError> {
+    let mut errors = Vec::new();
+    if !(-90.0..=90.0).contains(&latitude) {
+        errors.push(format!("the latitude {latitude} is not from -90 to 90"));
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        errors.push(format!("the longitude {longitude} is not from -180 to 180"));
+    }
+    invalid("location", errors)?;
+
+    let mut payload = format!("geo:{latitude},{longitude}");
+    if let Some(altitude) = altitude {
+        payload.push_str(&format!(",{altitude}"));
+    }
+    let query = uri::query(&[("q", query)]);
+    if !query.is_empty() {
+        payload.push_str(&format!("?{query}"));
+    }
+    Ok(payload)
+}
+
+/// An iCalendar event, a VEVENT on its own as ZXing writes it.
+#[derive(Debug, Default)]
+pub struct Event {
+    pub start: String,
+    pub end: Option<String>,
+    pub summary: Option<String>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Event {
+    pub fn payload(&self) -> Result<String, CliError> {
+        let mut errors = Vec::new();
+        let mut time = |property: &str, value: &str| match event_time(value) {
+            Some(time) => Some(format!("{property}{time}")),
+            None => {
+                errors.push(format!(
+                    "the {} '{value}' is not YYYY-MM-DD, YYYY-MM-DDTHH:MM[:SS] or RFC 3339",
+                    property.to_lowercase()
+                ));
+                None
+            }
+        };
+        let start = time("DTSTART", &self.start);
+        let end = self.end.as_deref().and_then(|end| time("DTEND", end));
+        invalid("event", errors)?;
+
+        let mut lines = vec!["BEGIN:VEVENT".to_owned()];
+        if let Some(summary) = &self.summary {
+            lines.push(format!("SUMMARY:{}", escape_vcard(summary)));
+        }
+        lines.extend(start);
+        lines.extend(end);
+        for (property, value) in [
+            ("LOCATION", &self.location),
+            ("DESCRIPTION", &self.description),
+        ] {
+            if let Some(value) = value {
+                lines.push(format!("{property}:{}", escape_vcard(value)));
+            }
+        }
+        lines.push("END:VEVENT".to_owned());
+        Ok(lines.join("\n"))
+    }
+}
+
+/// The value of a DTSTART or DTEND property, with its separator: a date for an all day event,
+/// a time with an offset in UTC, and a time without one as a floating local time.
+fn event_time(value: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(format!(";VALUE=DATE:{}", date.format("%Y%m%d")));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(format!(
+            ":{}",
+            time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ")
+        ));
+    }
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|time| format!(":{}", time.format("%Y%m%dT%H%M%S")))
+}
+
+fn invalid(what: &str, errors: Vec<String>) -> Result<(), CliError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::InvalidHint(format!(
+            "Invalid {what}:\n  {}",
+            errors.join("\n  ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rxing::{BarcodeFormat, RXingResult};
+
+    use super::*;
+    use crate::parsed::parsed_result;
+
+    /// The payload as a phone camera reads it.
+    fn read(payload: &str) -> serde_json::Value {
+        parsed_result(&RXingResult::new(
+            payload,
+            Vec::new(),
+            Vec::new(),
+            BarcodeFormat::QR_CODE,
+        ))
+    }
+
+    fn message(error: CliError) -> String {
+        match error {
+            CliError::InvalidHint(message) => message,
+            other => panic!("expected an invalid hint, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn wifi_escapes_special_characters() {
+        let wifi = Wifi {
+            ssid: "cafe;bar".to_owned(),
+            auth: "WPA".to_owned(),
+            password: Some(r#"a:b,c\"d"#.to_owned()),
+            hidden: true,
+            ..Default::default()
+        };
+        let payload = wifi.payload().unwrap();
+        assert_eq!(payload, r#"WIFI:T:WPA;S:cafe\;bar;P:a\:b\,c\\\"d;H:true;;"#);
+        let read = read(&payload);
+        assert_eq!(read["ssid"], "cafe;bar");
+        assert_eq!(read["password"], r#"a:b,c\"d"#);
+        assert_eq!(read["hidden"], true);
+    }
+
+    #[test]
+    fn wifi_needs_what_its_authentication_needs() {
+        let wifi = |auth: &str, password: Option<&str>| Wifi {
+            ssid: "home".to_owned(),
+            auth: auth.to_owned(),
+            password: password.map(str::to_owned),
+            ..Default::default()
+        };
+        assert_eq!(
+            message(wifi("WEP", None).payload().unwrap_err()),
+            "Invalid WiFi network:\n  WEP needs a password"
+        );
+        assert!(wifi("WPA2-EAP", Some("secret")).payload().is_err());
+        assert!(wifi("nopass", Some("secret")).payload().is_err());
+        assert_eq!(
+            wifi("nopass", None).payload().unwrap(),
+            "WIFI:T:nopass;S:home;;"
+        );
+    }
+
+    fn contact() -> Contact {
+        serde_json::from_str(
+            r#"{
+                "family_name": "Doe",
+                "given_name": "Jane",
+                "org": "Acme, Inc.",
+                "phones": ["+41 44 123", {"value": "+41 79 456", "type": "CELL"}],
+                "addresses": ["Main Street 1"],
+                "birthday": "1990-01-31",
+                "note": "first line\nsecond; line"
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn vcard() {
+        let vcard = contact().vcard().unwrap();
+        assert_eq!(
+            vcard,
+            "BEGIN:VCARD\nVERSION:3.0\nN:Doe;Jane;;;\nFN:Jane Doe\nORG:Acme\\, Inc.\n\
+             TEL:+41 44 123\nTEL;TYPE=CELL:+41 79 456\nADR:;;Main Street 1;;;;\n\
+             BDAY:1990-01-31\nNOTE:first line\\nsecond\\; line\nEND:VCARD"
+        );
+        let read = read(&vcard);
+        assert_eq!(read["type"], "addressbook");
+        assert_eq!(read["names"], serde_json::json!(["Jane Doe"]));
+    }
+
+    #[test]
+    fn mecard() {
+        let mecard = contact().mecard().unwrap();
+        assert_eq!(
+            mecard,
+            "MECARD:N:Doe,Jane;ORG:Acme\\, Inc.;TEL:+41 44 123;TEL:+41 79 456;\
+             ADR:Main Street 1;BDAY:19900131;NOTE:first line\nsecond\\; line;;"
+        );
+        assert_eq!(read(&mecard)["type"], "addressbook");
+    }
+
+    #[test]
+    fn contact_errors() {
+        let contact: Contact = serde_json::from_str(r#"{"birthday": "31.01.1990"}"#).unwrap();
+        assert_eq!(
+            message(contact.vcard().unwrap_err()),
+            "Invalid contact:\n  the contact has no name, family_name or given_name\n  \
+             the birthday '31.01.1990' is not YYYY-MM-DD"
+        );
+        assert!(serde_json::from_str::<Contact>(r#"{"fax": "1"}"#).is_err());
+    }
+
+    #[test]
+    fn sms_and_phone_numbers() {
+        assert_eq!(
+            sms(" +41 (44) 123-45.67 ", None).unwrap(),
+            "sms:+41 (44) 123-45.67"
+        );
+        assert_eq!(
+            sms("123", Some("hi there")).unwrap(),
+            format!("sms:123?{}", uri::query(&[("body", Some("hi there"))]))
+        );
+        for number in ["", "+", "call me", "12a"] {
+            assert!(sms(number, None).is_err(), "{number}");
+        }
+    }
+
+    #[test]
+    fn email() {
+        let email = Email {
+            to: "a@example.com, b@example.com".to_owned(),
+            subject: Some("Hello".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            email.payload().unwrap(),
+            "mailto:a@example.com,b@example.com?subject=Hello"
+        );
+        let email = Email {
+            to: "a@example.com".to_owned(),
+            cc: Some("nobody, c@localhost".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            message(email.payload().unwrap_err()),
+            "Invalid email:\n  'nobody' is not an email address\n  'c@localhost' is not an email address"
+        );
+    }
+
+    #[test]
+    fn geo_uri() {
+        assert_eq!(
+            geo(46.5, -6.25, Some(400.0), None).unwrap(),
+            "geo:46.5,-6.25,400"
+        );
+        assert_eq!(
+            message(geo(91.0, 181.0, None, None).unwrap_err()),
+            "Invalid location:\n  the latitude 91 is not from -90 to 90\n  the longitude 181 is not from -180 to 180"
+        );
+    }
+
+    #[test]
+    fn event_times() {
+        assert_eq!(
+            event_time("2024-02-29").as_deref(),
+            Some(";VALUE=DATE:20240229")
+        );
+        assert_eq!(
+            event_time("2024-02-29T10:30:00+01:00").as_deref(),
+            Some(":20240229T093000Z")
+        );
+        assert_eq!(
+            event_time("2024-02-29T10:30").as_deref(),
+            Some(":20240229T103000")
+        );
+        assert_eq!(event_time("2023-02-29"), None);
+
+        let event = Event {
+            start: "2024-02-29".to_owned(),
+            end: Some("tomorrow".to_owned()),
+            ..Default::default()
+        };
+        assert!(message(event.payload().unwrap_err()).contains("the dtend 'tomorrow'"));
+        let event = Event {
+            start: "2024-02-29T10:30:00Z".to_owned(),
+            summary: Some("Launch, finally".to_owned()),
+            ..Default::default()
+        };
+        let payload = event.payload().unwrap();
+        assert_eq!(
+            payload,
+            "BEGIN:VEVENT\nSUMMARY:Launch\\, finally\nDTSTART:20240229T103000Z\nEND:VEVENT"
+        );
+        assert_eq!(read(&payload)["summary"], "Launch, finally");
+    }
+}
@@ -0,0 +1,255 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::CliError;
+
+/// Luminance difference below which most scanners fail, ISO/IEC 15416 grades a symbol
+/// contrast under 20% as F.
+const MIN_CONTRAST: f64 = 0.2;
+
+/// Luminance difference below which scanning becomes unreliable (grade C and worse).
+const LOW_CONTRAST: f64 = 0.4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Colour {
+    pub const BLACK: Colour = Colour::opaque(0, 0, 0);
+    pub const WHITE: Colour = Colour::opaque(u8::MAX, u8::MAX, u8::MAX);
+
+    pub const fn opaque(r: u8, g: u8, b: u8) -> Self {
+        Colour {
+            r,
+            g,
+            b,
+            a: u8::MAX,
+        }
+    }
+
+    pub fn rgba(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// `#rrggbb`, without the alpha channel, for SVG fills.
+    pub fn hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+
+    pub fn opacity(self) -> f64 {
+        self.a as f64 / u8::MAX as f64
+    }
+
+    /// Relative luminance (as defined by WCAG) of the colour printed on white, as transparent
+    /// parts show whatever they are placed on.
+    fn luminance(self) -> f64 {
+        let channel = |c: u8| {
+            let over_white = (c as f64 * self.opacity() + u8::MAX as f64 * (1.0 - self.opacity()))
+                / u8::MAX as f64;
+            if over_white <= 0.04045 {
+                over_white / 12.92
+            } else {
+                ((over_white + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(self.r) + 0.7152 * channel(self.g) + 0.0722 * channel(self.b)
+    }
+}
+
+/// Accepts `#rgb`, `#rgba`, `#rrggbb` and `#rrggbbaa` (the `#` is optional) or decimal
+/// `r,g,b` and `r,g,b,a`.
+impl FromStr for Colour {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{s}' is not a colour, expected #rrggbb, #rrggbbaa or r,g,b[,a]");
+
+        if s.contains(',') {
+            let channels = s
+                .split(',')
+                .map(|c| c.trim().parse::<u8>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid())?;
+            return match channels[..] {
+                [r, g, b] => Ok(Colour::opaque(r, g, b)),
+                [r, g, b, a] => Ok(Colour { r, g, b, a }),
+                _ => Err(invalid()),
+            };
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        // expand the short forms, so that #f80 is #ff8800
+        let hex = match hex.len() {
+            3 | 4 => hex.chars().flat_map(|c| [c, c]).collect(),
+            6 | 8 => hex.to_owned(),
+            _ => return Err(invalid()),
+        };
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+        Ok(Colour {
+            r: channel(0)?,
+            g: channel(2)?,
+            b: channel(4)?,
+            a: if hex.len() == 8 { channel(6)? } else { u8::MAX },
+        })
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.hex())?;
+        if self.a != u8::MAX {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+/// The colours of the dark and light modules of an encoded image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub foreground: Colour,
+    pub background: Colour,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            foreground: Colour::BLACK,
+            background: Colour::WHITE,
+        }
+    }
+}
+
+impl Palette {
+    pub fn is_default(&self) -> bool {
+        *self == Palette::default()
+    }
+
+    pub fn has_transparency(&self) -> bool {
+        self.foreground.a != u8::MAX || self.background.a != u8::MAX
+    }
+
+    /// Refuse colours that scanners can not tell apart, and warn about ones that are risky.
+    pub fn check_contrast(&self) -> Result<Vec<String>, CliError> {
+        let foreground = self.foreground.luminance();
+        let background = self.background.luminance();
+        let contrast = background - foreground;
+        let mut warnings = Vec::new();
+
+        if contrast.abs() < MIN_CONTRAST {
+            return Err(CliError::InvalidHint(format!(
+                "--foreground {} and --background {} differ in luminance by {:.0}%, at least {:.0}% is needed for the barcode to scan",
+                self.foreground,
+                self.background,
+                contrast.abs() * 100.0,
+                MIN_CONTRAST * 100.0
+            )));
+        }
+        if contrast < 0.0 {
+            warnings.push(format!(
+                "Warning, the foreground {} is lighter than the background {}, many scanners can not read inverted barcodes",
+                self.foreground, self.background
+            ));
+        }
+        if contrast.abs() < LOW_CONTRAST {
+            warnings.push(format!(
+                "Warning, --foreground {} and --background {} differ in luminance by only {:.0}%, some scanners may fail to read the barcode",
+                self.foreground,
+                self.background,
+                contrast.abs() * 100.0
+            ));
+        }
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colour(s: &str) -> Colour {
+        s.parse().unwrap()
+    }
+
+    fn palette(foreground: &str, background: &str) -> Palette {
+        Palette {
+            foreground: colour(foreground),
+            background: colour(background),
+        }
+    }
+
+    #[test]
+    fn parse_hex() {
+        let orange = Colour::opaque(0xff, 0x88, 0x00);
+        assert_eq!(colour("#ff8800"), orange);
+        assert_eq!(colour("FF8800"), orange);
+        assert_eq!(colour("#f80"), orange);
+        assert_eq!(colour("#f808").a, 0x88);
+        assert_eq!(colour("#ff880080").a, 0x80);
+        for invalid in ["", "#", "#ff880", "#ff88001", "#gg8800", "#ff 800"] {
+            assert!(invalid.parse::<Colour>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parse_decimal() {
+        assert_eq!(colour("255, 136, 0"), Colour::opaque(0xff, 0x88, 0x00));
+        assert_eq!(colour("0,0,0,0").a, 0);
+        for invalid in ["1,2", "1,2,3,4,5", "256,0,0", "-1,0,0", "a,b,c"] {
+            assert_eq!(
+                invalid.parse::<Colour>().unwrap_err(),
+                format!("'{invalid}' is not a colour, expected #rrggbb, #rrggbbaa or r,g,b[,a]")
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for s in ["#ff8800", "#ff880080", "#000000"] {
+            assert_eq!(colour(s).to_string(), s);
+        }
+    }
+
+    #[test]
+    fn contrast_thresholds() {
+        assert_eq!(
+            Palette::default().check_contrast().unwrap(),
+            Vec::<String>::new()
+        );
+        assert!(palette("#777", "#fff").check_contrast().unwrap().is_empty());
+
+        // grade C and worse, but above the minimum
+        let warnings = palette("#ccc", "#fff").check_contrast().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].contains("differ in luminance by only 40%"),
+            "{warnings:?}"
+        );
+
+        let error = palette("#eee", "#fff").check_contrast().unwrap_err();
+        assert!(matches!(error, CliError::InvalidHint(_)));
+        assert!(
+            error.to_string().contains("at least 20% is needed"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn inverted_and_transparent() {
+        let warnings = palette("#fff", "#000").check_contrast().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("is lighter than the background"));
+
+        // a transparent foreground shows the white it is printed on
+        assert!(palette("#0000", "#fff").check_contrast().is_err());
+        let transparent = palette("#000", "#fff0");
+        assert!(transparent.has_transparency());
+        assert!(transparent.check_contrast().unwrap().is_empty());
+    }
+}
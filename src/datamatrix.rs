@@ -0,0 +1,256 @@
+use rxing::{
+    common::BitMatrix,
+    datamatrix::encoder::{error_correction, DefaultPlacement, SymbolInfo, SymbolInfoLookup},
+};
+
+use crate::{error::CliError, gs1::GROUP_SEPARATOR};
+
+/// FNC1, marking the symbol as GS1 in first position and separating elements after that.
+const FNC1: u32 = 232;
+
+/// Shifts the next codeword to the upper half of the extended ASCII range.
+const UPPER_SHIFT: u32 = 235;
+
+/// The first pad codeword, later pads are randomised.
+const PAD: u32 = 129;
+
+/// Encode a GS1 element string, with group separators between the elements, as a GS1 Data
+/// Matrix symbol of one pixel per module without a quiet zone.
+///
+/// The rxing writer puts ECI codewords instead of FNC1 where the separators are, so the
+/// codewords are built here in ASCII encodation and only the error correction and module
+/// placement are left to rxing.
+pub fn encode_gs1(element_string: &str) -> Result<BitMatrix, CliError> {
+    let mut codewords = vec![FNC1];
+    let mut chars = element_string.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            GROUP_SEPARATOR => codewords.push(FNC1),
+            '0'..='9' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                let pair = c.to_digit(10).unwrap_or_default() * 10
+                    + chars
+                        .next()
+                        .and_then(|d| d.to_digit(10))
+                        .unwrap_or_default();
+                codewords.push(130 + pair);
+            }
+            '\0'..='\u{7f}' => codewords.push(c as u32 + 1),
+            '\u{80}'..='\u{ff}' => codewords.extend([UPPER_SHIFT, c as u32 - 127]),
+            _ => {
+                return Err(CliError::InvalidHint(format!(
+                    "'{c}' can not be encoded in a GS1 DATAMATRIX symbol"
+                )))
+            }
+        }
+    }
+
+    let symbol_info = SymbolInfoLookup::new()
+        .lookup(codewords.len() as u32)
+        .map_err(CliError::from_encode_error)?
+        .ok_or_else(|| {
+            CliError::EncodeFailure("Couldn't encode: no DATAMATRIX symbol is large enough".into())
+        })?;
+    let capacity = symbol_info.getDataCapacity() as usize;
+    if codewords.len() < capacity {
+        codewords.push(PAD);
+    }
+    while codewords.len() < capacity {
+        codewords.push(randomised_pad(codewords.len() as u32 + 1));
+    }
+
+    let data: String = codewords.into_iter().filter_map(char::from_u32).collect();
+    let with_ecc =
+        error_correction::encodeECC200(&data, symbol_info).map_err(CliError::from_encode_error)?;
+    let mut placement = DefaultPlacement::new(
+        with_ecc,
+        symbol_data_width(symbol_info)? as usize,
+        symbol_data_height(symbol_info)? as usize,
+    );
+    placement.place().map_err(CliError::from_encode_error)?;
+
+    draw(&placement, symbol_info)
+}
+
+/// The 253-state randomising algorithm for the pad codeword at 1-based `position`.
+fn randomised_pad(position: u32) -> u32 {
+    let pseudo_random = (149 * position) % 253 + 1;
+    let pad = PAD + pseudo_random;
+    if pad <= 254 {
+        pad
+    } else {
+        pad - 254
+    }
+}
+
+fn symbol_data_width(symbol_info: &SymbolInfo) -> Result<u32, CliError> {
+    symbol_info
+        .getSymbolDataWidth()
+        .map_err(CliError::from_encode_error)
+}
+
+fn symbol_data_height(symbol_info: &SymbolInfo) -> Result<u32, CliError> {
+    symbol_info
+        .getSymbolDataHeight()
+        .map_err(CliError::from_encode_error)
+}
+
+/// Lay the data regions out with their finder and timing patterns, the solid L on the left
+/// and bottom and alternating modules on the top and right of every region.
+fn draw(placement: &DefaultPlacement, symbol_info: &SymbolInfo) -> Result<BitMatrix, CliError> {
+    let data_width = symbol_data_width(symbol_info)?;
+    let data_height = symbol_data_height(symbol_info)?;
+    let width = symbol_info
+        .getSymbolWidth()
+        .map_err(CliError::from_encode_error)?;
+    let height = symbol_info
+        .getSymbolHeight()
+        .map_err(CliError::from_encode_error)?;
+    // every region adds two modules of patterns in each direction
+    let region_width = data_width / ((width - data_width) / 2);
+    let region_height = data_height / ((height - data_height) / 2);
+
+    let mut matrix = BitMatrix::new(width, height).map_err(CliError::from_encode_error)?;
+    let mut set = |x: u32, y: u32, dark: bool| {
+        if dark {
+            matrix.set(x, y);
+        }
+    };
+
+    let mut matrix_y = 0;
+    for y in 0..data_height {
+        if y % region_height == 0 {
+            for x in 0..width {
+                set(x, matrix_y, x % 2 == 0);
+            }
+            matrix_y += 1;
+        }
+        let mut matrix_x = 0;
+        for x in 0..data_width {
+            if x % region_width == 0 {
+                set(matrix_x, matrix_y, true);
+                matrix_x += 1;
+            }
+            set(matrix_x, matrix_y, placement.getBit(x as usize, y as usize));
+            matrix_x += 1;
+            if x % region_width == region_width - 1 {
+                set(matrix_x, matrix_y, y % 2 == 0);
+                matrix_x += 1;
+            }
+        }
+        matrix_y += 1;
+        if y % region_height == region_height - 1 {
+            for x in 0..width {
+                set(x, matrix_y, true);
+            }
+            matrix_y += 1;
+        }
+    }
+    Ok(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rxing::{BarcodeFormat, DecodeHintType, DecodeHintValue, RXingResult};
+
+    use super::*;
+    use crate::gs1;
+
+    /// Pixels per module and modules of quiet zone of the image the reader gets.
+    const SCALE: u32 = 4;
+    const QUIET_ZONE: u32 = 2;
+
+    fn read(matrix: &BitMatrix) -> RXingResult {
+        let (width, height) = (
+            (matrix.getWidth() + 2 * QUIET_ZONE) * SCALE,
+            (matrix.getHeight() + 2 * QUIET_ZONE) * SCALE,
+        );
+        let mut luma = vec![255; (width * height) as usize];
+        for y in 0..height {
+            for x in 0..width {
+                let (module_x, module_y) = (x / SCALE, y / SCALE);
+                let dark = (QUIET_ZONE..QUIET_ZONE + matrix.getWidth()).contains(&module_x)
+                    && (QUIET_ZONE..QUIET_ZONE + matrix.getHeight()).contains(&module_y)
+                    && matrix.get(module_x - QUIET_ZONE, module_y - QUIET_ZONE);
+                if dark {
+                    luma[(y * width + x) as usize] = 0;
+                }
+            }
+        }
+        let mut hints =
+            HashMap::from([(DecodeHintType::TRY_HARDER, DecodeHintValue::TryHarder(true))]);
+        rxing::helpers::detect_in_luma_with_hints(
+            luma,
+            width,
+            height,
+            Some(BarcodeFormat::DATA_MATRIX),
+            &mut hints,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fnc1_and_digit_pairs() {
+        let element_string = "0109506000134352\u{1d}10AB1";
+        let matrix = encode_gs1(element_string).unwrap();
+        let result = read(&matrix);
+        assert_eq!(
+            gs1::strip_symbology_identifier(result.getText()),
+            element_string
+        );
+        // FNC1 first, the GTIN in digit pairs, FNC1 after it as in the input, then the batch
+        // with its digit pair and letters and the last digit on its own
+        let codewords: Vec<u32> = result.getRawBytes().iter().map(|b| u32::from(*b)).collect();
+        assert_eq!(
+            codewords[..15],
+            [FNC1, 131, 139, 180, 190, 130, 143, 173, 182, FNC1, 140, 66, 67, 50, PAD]
+        );
+    }
+
+    #[test]
+    fn separator_between_elements() {
+        let element_string = "10ABC\u{1d}21XYZ";
+        let result = read(&encode_gs1(element_string).unwrap());
+        assert_eq!(
+            gs1::strip_symbology_identifier(result.getText()),
+            element_string
+        );
+        assert!(result.getRawBytes()[1..].contains(&(FNC1 as u8)));
+    }
+
+    #[test]
+    fn multi_region_symbol() {
+        let element_string = "0109506000134352\u{1d}17260101\u{1d}10BATCH-2026-OCTOBER\u{1d}21SERIAL0123456789ABCD\u{1d}240PART-NUMBER-42\u{1d}3103000750";
+        let matrix = encode_gs1(element_string).unwrap();
+        assert!(
+            matrix.getWidth() > 26,
+            "{}x{}",
+            matrix.getWidth(),
+            matrix.getHeight()
+        );
+        let result = read(&matrix);
+        assert_eq!(
+            gs1::strip_symbology_identifier(result.getText()),
+            element_string
+        );
+    }
+
+    #[test]
+    fn upper_shift_and_unsupported_characters() {
+        let element_string = "10Ä1";
+        let result = read(&encode_gs1(element_string).unwrap());
+        assert_eq!(
+            gs1::strip_symbology_identifier(result.getText()),
+            element_string
+        );
+        assert!(encode_gs1("10€").is_err());
+    }
+
+    #[test]
+    fn randomised_pads() {
+        assert_eq!(randomised_pad(3), 129 + (149 * 3) % 253 + 1 - 254);
+        assert_eq!(randomised_pad(1), 129 + 150 - 254);
+        assert!((1..=1558).all(|position| (1..=254).contains(&randomised_pad(position))));
+    }
+}
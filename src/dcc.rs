@@ -0,0 +1,600 @@
+use std::{io::Read, path::Path};
+
+use base64::Engine;
+use chrono::DateTime;
+use ciborium::Value as Cbor;
+use p256::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::Sha256;
+use x509_cert::{
+    der::{Decode, DecodePem, Encode},
+    Certificate,
+};
+
+use crate::error::CliError;
+
+pub const PREFIX: &str = "HC1:";
+
+const BASE45: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// COSE header labels and algorithms.
+const HEADER_ALG: i128 = 1;
+const HEADER_KID: i128 = 4;
+const ES256: i128 = -7;
+const PS256: i128 = -37;
+
+/// CWT claims, and the health certificate claim with its EU DCC entry.
+const CLAIM_ISSUER: i128 = 1;
+const CLAIM_EXPIRES: i128 = 4;
+const CLAIM_ISSUED_AT: i128 = 6;
+const CLAIM_HCERT: i128 = -260;
+const HCERT_EU_DCC: i128 = 1;
+
+/// A document signer public key, for ES256 or PS256 signatures.
+pub enum PublicKey {
+    Ec(p256::ecdsa::VerifyingKey),
+    Rsa(rsa::RsaPublicKey),
+}
+
+impl PublicKey {
+    /// A DER SubjectPublicKeyInfo or X.509 certificate.
+    fn from_der(der: &[u8]) -> Option<Self> {
+        let spki = match Certificate::from_der(der) {
+            Ok(certificate) => certificate
+                .tbs_certificate
+                .subject_public_key_info
+                .to_der()
+                .ok()?,
+            Err(_) => der.to_vec(),
+        };
+        if let Ok(key) = p256::PublicKey::from_public_key_der(&spki) {
+            return Some(PublicKey::Ec(key.into()));
+        }
+        rsa::RsaPublicKey::from_public_key_der(&spki)
+            .ok()
+            .map(PublicKey::Rsa)
+    }
+
+    /// A PEM public key or certificate, or the base64 of either in DER.
+    fn from_text(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.contains("-----BEGIN CERTIFICATE-----") {
+            let der = Certificate::from_pem(text).ok()?.to_der().ok()?;
+            return Self::from_der(&der);
+        }
+        if text.contains("-----BEGIN PUBLIC KEY-----") {
+            if let Ok(key) = p256::PublicKey::from_public_key_pem(text) {
+                return Some(PublicKey::Ec(key.into()));
+            }
+            return rsa::RsaPublicKey::from_public_key_pem(text)
+                .ok()
+                .map(PublicKey::Rsa);
+        }
+        let der = base64::engine::general_purpose::STANDARD
+            .decode(text.replace(['\n', '\r'], ""))
+            .ok()?;
+        Self::from_der(&der)
+    }
+
+    fn verify(&self, alg: i128, message: &[u8], signature: &[u8]) -> Result<(), String> {
+        match (self, alg) {
+            (PublicKey::Ec(key), ES256) => {
+                let signature = p256::ecdsa::Signature::from_slice(signature)
+                    .map_err(|e| format!("the ES256 signature is malformed: {e}"))?;
+                key.verify(message, &signature)
+                    .map_err(|_| "the ES256 signature does not match".to_owned())
+            }
+            (PublicKey::Rsa(key), PS256) => {
+                let signature = rsa::pss::Signature::try_from(signature)
+                    .map_err(|e| format!("the PS256 signature is malformed: {e}"))?;
+                rsa::pss::VerifyingKey::<Sha256>::new(key.clone())
+                    .verify(message, &signature)
+                    .map_err(|_| "the PS256 signature does not match".to_owned())
+            }
+            _ => Err(format!(
+                "the key does not fit the {} algorithm",
+                alg_name(alg)
+            )),
+        }
+    }
+}
+
+/// Keys to check signatures with: from a trust list, found by their key ID, and keys given
+/// on their own, which are tried for every certificate.
+#[derive(Default)]
+pub struct TrustStore {
+    by_kid: Vec<(Vec<u8>, PublicKey)>,
+    any: Vec<PublicKey>,
+}
+
+impl TrustStore {
+    pub fn is_empty(&self) -> bool {
+        self.by_kid.is_empty() && self.any.is_empty()
+    }
+
+    /// Load a trust list, a JSON object of base64 key IDs to keys or certificates (PEM or
+    /// base64 DER), or an array of objects with a `kid` and a `rawData`, `certificate` or
+    /// `publicKeyPem`, the layout of the EU gateway's document signer lists.
+    pub fn add_trust_list(&mut self, path: &Path) -> Result<(), CliError> {
+        let name = path.to_string_lossy();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CliError::Io(format!("{name} cannot be read: {e}")))?;
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|e| CliError::InvalidHint(format!("{name} is not a JSON trust list: {e}")))?;
+
+        let entries: Vec<(&str, &Value)> = match &json {
+            Value::Object(keys) => keys.iter().map(|(kid, key)| (kid.as_str(), key)).collect(),
+            Value::Array(entries) => entries
+                .iter()
+                .filter_map(|entry| {
+                    let kid = entry.get("kid")?.as_str()?;
+                    let key = ["rawData", "certificate", "publicKeyPem"]
+                        .iter()
+                        .find_map(|field| entry.get(*field))?;
+                    Some((kid, key))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        for (kid, key) in entries {
+            let kid_bytes = base64::engine::general_purpose::STANDARD
+                .decode(kid)
+                .map_err(|_| {
+                    CliError::InvalidHint(format!("{name}: the key ID '{kid}' is not base64"))
+                })?;
+            // a key ID may list several keys
+            let texts: Vec<&str> = match key {
+                Value::String(text) => vec![text],
+                Value::Array(texts) => texts.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            for text in texts {
+                let key = PublicKey::from_text(text).ok_or_else(|| {
+                    CliError::InvalidHint(format!(
+                        "{name}: the key of '{kid}' is not a P-256 or RSA public key or certificate"
+                    ))
+                })?;
+                self.by_kid.push((kid_bytes.clone(), key));
+            }
+        }
+        if self.by_kid.is_empty() {
+            return Err(CliError::InvalidHint(format!("{name} contains no keys")));
+        }
+        Ok(())
+    }
+
+    /// Load a PEM public key or certificate.
+    pub fn add_key(&mut self, path: &Path) -> Result<(), CliError> {
+        let name = path.to_string_lossy();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CliError::Io(format!("{name} cannot be read: {e}")))?;
+        let key = PublicKey::from_text(&text).ok_or_else(|| {
+            CliError::InvalidHint(format!(
+                "{name} is not a PEM P-256 or RSA public key or certificate"
+            ))
+        })?;
+        self.any.push(key);
+        Ok(())
+    }
+
+    fn keys_for<'a>(&'a self, kid: Option<&'a [u8]>) -> impl Iterator<Item = &'a PublicKey> {
+        self.by_kid
+            .iter()
+            .filter(move |(key_id, _)| Some(key_id.as_slice()) == kid)
+            .map(|(_, key)| key)
+            .chain(&self.any)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Header {
+    /// The key ID, base64.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Dcc {
+    pub header: Header,
+    /// The CWT claims, with times in RFC 3339.
+    pub claims: Map<String, Value>,
+    /// The EU DCC itself: name, date of birth and the vaccination, test or recovery entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub certificate: Option<Value>,
+    /// `valid`, `invalid`, `unknown key` or `not checked` when no keys were given.
+    pub signature: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl Dcc {
+    /// Whether keys were given but none of them verifies the signature.
+    pub fn failed_verification(&self) -> bool {
+        matches!(self.signature, "invalid" | "unknown key")
+    }
+
+    /// The header, signature and claims, followed by the certificate as indented JSON.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "DCC: kid {}, alg {}\n  signature: {}",
+            self.header.kid.as_deref().unwrap_or("none"),
+            self.header.alg.as_deref().unwrap_or("none"),
+            self.signature
+        );
+        for (name, value) in &self.claims {
+            let value = value.as_str().map_or(value.to_string(), str::to_owned);
+            text.push_str(&format!("\n  {name}: {value}"));
+        }
+        if let Some(certificate) = &self.certificate {
+            let json = serde_json::to_string_pretty(certificate).unwrap_or_default();
+            text.push_str("\n  certificate: ");
+            text.push_str(&json.replace('\n', "\n  "));
+        }
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        text
+    }
+}
+
+/// Decode an EU Digital COVID Certificate, `HC1:` followed by Base45 of the zlib compressed
+/// COSE_Sign1 of the CBOR claims, and check its signature with the keys in `trust`.
+pub fn decode(text: &str, trust: &TrustStore) -> Dcc {
+    let mut dcc = Dcc {
+        header: Header {
+            kid: None,
+            alg: None,
+        },
+        claims: Map::new(),
+        certificate: None,
+        signature: "not checked",
+        errors: Vec::new(),
+        valid: false,
+    };
+    if let Err(error) = decode_into(&mut dcc, text, trust) {
+        dcc.errors.push(error);
+    }
+    dcc.valid = dcc.errors.is_empty() && !dcc.failed_verification();
+    dcc
+}
+
+fn decode_into(dcc: &mut Dcc, text: &str, trust: &TrustStore) -> Result<(), String> {
+    let base45 = text
+        .strip_prefix(PREFIX)
+        .ok_or(format!("the text does not start with '{PREFIX}'"))?;
+    let compressed = base45_decode(base45)?;
+    // the payload is zlib compressed, in theory it may also be left uncompressed
+    let cose = if compressed.first() == Some(&0x78) {
+        let mut cose = Vec::new();
+        flate2::read::ZlibDecoder::new(compressed.as_slice())
+            .read_to_end(&mut cose)
+            .map_err(|e| format!("the zlib data is damaged: {e}"))?;
+        cose
+    } else {
+        compressed
+    };
+
+    let cose: Cbor = ciborium::de::from_reader(cose.as_slice())
+        .map_err(|e| format!("the COSE message is not CBOR: {e}"))?;
+    // COSE_Sign1 is tag 18, which is optional
+    let cose = match cose {
+        Cbor::Tag(18, inner) => *inner,
+        other => other,
+    };
+    let Cbor::Array(parts) = cose else {
+        return Err("the COSE message is not a COSE_Sign1 array".to_owned());
+    };
+    let [Cbor::Bytes(protected), unprotected, Cbor::Bytes(payload), Cbor::Bytes(signature)] =
+        parts.as_slice()
+    else {
+        return Err("the COSE_Sign1 does not have the four expected parts".to_owned());
+    };
+
+    let protected_map = if protected.is_empty() {
+        Cbor::Map(Vec::new())
+    } else {
+        ciborium::de::from_reader(protected.as_slice())
+            .map_err(|e| format!("the protected header is not CBOR: {e}"))?
+    };
+    // the protected header takes precedence over the unprotected one
+    let header =
+        |label: i128| map_get(&protected_map, label).or_else(|| map_get(unprotected, label));
+    let alg = header(HEADER_ALG).and_then(as_integer);
+    let kid = header(HEADER_KID).and_then(|kid| match kid {
+        Cbor::Bytes(kid) => Some(kid.clone()),
+        _ => None,
+    });
+    dcc.header = Header {
+        kid: kid
+            .as_ref()
+            .map(|kid| base64::engine::general_purpose::STANDARD.encode(kid)),
+        alg: alg.map(alg_name),
+    };
+
+    let claims: Cbor = ciborium::de::from_reader(payload.as_slice())
+        .map_err(|e| format!("the claims are not CBOR: {e}"))?;
+    let Cbor::Map(entries) = &claims else {
+        return Err("the claims are not a CBOR map".to_owned());
+    };
+    for (key, value) in entries {
+        let Some(label) = as_integer(key) else {
+            continue;
+        };
+        match label {
+            CLAIM_ISSUER => {
+                dcc.claims.insert("issuer".to_owned(), to_json(value));
+            }
+            CLAIM_ISSUED_AT | CLAIM_EXPIRES => {
+                let name = if label == CLAIM_ISSUED_AT {
+                    "issued_at"
+                } else {
+                    "expires"
+                };
+                let time = as_integer(value)
+                    .and_then(|seconds| DateTime::from_timestamp(seconds as i64, 0))
+                    .map_or(to_json(value), |time| time.to_rfc3339().into());
+                dcc.claims.insert(name.to_owned(), time);
+            }
+            CLAIM_HCERT => {
+                dcc.certificate = map_get(value, HCERT_EU_DCC).map(to_json);
+            }
+            other => {
+                dcc.claims.insert(other.to_string(), to_json(value));
+            }
+        }
+    }
+    if dcc.certificate.is_none() {
+        dcc.errors
+            .push("the claims have no EU DCC health certificate (-260)".to_owned());
+    }
+
+    if !trust.is_empty() {
+        let alg = alg.ok_or("the header has no algorithm")?;
+        let mut signed = Vec::new();
+        ciborium::ser::into_writer(
+            &Cbor::Array(vec![
+                Cbor::Text("Signature1".to_owned()),
+                Cbor::Bytes(protected.clone()),
+                Cbor::Bytes(Vec::new()),
+                Cbor::Bytes(payload.clone()),
+            ]),
+            &mut signed,
+        )
+        .map_err(|e| format!("the signed structure can not be built: {e}"))?;
+
+        let mut keys = trust.keys_for(kid.as_deref()).peekable();
+        if keys.peek().is_none() {
+            dcc.signature = "unknown key";
+            return Err(format!(
+                "no key with the ID {} was given",
+                dcc.header.kid.as_deref().unwrap_or("none")
+            ));
+        }
+        let mut failures = Vec::new();
+        for key in keys {
+            match key.verify(alg, &signed, signature) {
+                Ok(()) => {
+                    dcc.signature = "valid";
+                    return Ok(());
+                }
+                Err(failure) => failures.push(failure),
+            }
+        }
+        dcc.signature = "invalid";
+        failures.dedup();
+        return Err(failures.join(", "));
+    }
+    Ok(())
+}
+
+fn base45_decode(text: &str) -> Result<Vec<u8>, String> {
+    let values = text
+        .bytes()
+        .map(|b| {
+            BASE45
+                .iter()
+                .position(|c| *c == b)
+                .map(|v| v as u32)
+                .ok_or(format!("'{}' is not a Base45 character", b as char))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut bytes = Vec::with_capacity(values.len() * 2 / 3);
+    for chunk in values.chunks(3) {
+        match *chunk {
+            [c, d, e] => {
+                let n = c + d * 45 + e * 45 * 45;
+                if n > 0xffff {
+                    return Err("the Base45 data is out of range".to_owned());
+                }
+                bytes.extend([(n >> 8) as u8, n as u8]);
+            }
+            [c, d] => {
+                let n = c + d * 45;
+                if n > 0xff {
+                    return Err("the Base45 data is out of range".to_owned());
+                }
+                bytes.push(n as u8);
+            }
+            _ => return Err("the Base45 data has a dangling character".to_owned()),
+        }
+    }
+    Ok(bytes)
+}
+
+fn alg_name(alg: i128) -> String {
+    match alg {
+        ES256 => "ES256".to_owned(),
+        PS256 => "PS256".to_owned(),
+        other => other.to_string(),
+    }
+}
+
+fn as_integer(value: &Cbor) -> Option<i128> {
+    match value {
+        Cbor::Integer(integer) => Some(i128::from(*integer)),
+        _ => None,
+    }
+}
+
+fn map_get(map: &Cbor, label: i128) -> Option<&Cbor> {
+    let Cbor::Map(entries) = map else {
+        return None;
+    };
+    entries
+        .iter()
+        .find(|(key, _)| as_integer(key) == Some(label))
+        .map(|(_, value)| value)
+}
+
+/// CBOR as JSON: byte strings become base64 and map keys strings.
+fn to_json(value: &Cbor) -> Value {
+    match value {
+        Cbor::Integer(integer) => {
+            let integer = i128::from(*integer);
+            i64::try_from(integer).map_or_else(|_| integer.to_string().into(), Value::from)
+        }
+        Cbor::Bytes(bytes) => base64::engine::general_purpose::STANDARD
+            .encode(bytes)
+            .into(),
+        Cbor::Float(float) => Value::from(*float),
+        Cbor::Text(text) => text.clone().into(),
+        Cbor::Bool(b) => (*b).into(),
+        Cbor::Null => Value::Null,
+        Cbor::Tag(_, inner) => to_json(inner),
+        Cbor::Array(items) => items.iter().map(to_json).collect(),
+        Cbor::Map(entries) => entries
+            .iter()
+            .map(|(key, value)| {
+                let key = match key {
+                    Cbor::Text(text) => text.clone(),
+                    other => to_json(other).to_string(),
+                };
+                (key, to_json(value))
+            })
+            .collect::<Map<_, _>>()
+            .into(),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+
+    use super::*;
+
+    fn base45_encode(bytes: &[u8]) -> String {
+        let mut text = String::new();
+        for chunk in bytes.chunks(2) {
+            let (mut n, digits) = match *chunk {
+                [a, b] => ((a as u32) << 8 | b as u32, 3),
+                [a] => (a as u32, 2),
+                _ => unreachable!(),
+            };
+            for _ in 0..digits {
+                text.push(BASE45[(n % 45) as usize] as char);
+                n /= 45;
+            }
+        }
+        text
+    }
+
+    fn cbor(value: &Cbor) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(value, &mut bytes).unwrap();
+        bytes
+    }
+
+    /// An HC1 certificate for a vaccination, signed by `key`.
+    fn certificate(key: &SigningKey) -> String {
+        let protected = cbor(&Cbor::Map(vec![
+            (HEADER_ALG.into(), ES256.into()),
+            (HEADER_KID.into(), Cbor::Bytes(b"kid".to_vec())),
+        ]));
+        let payload = cbor(&Cbor::Map(vec![
+            (CLAIM_ISSUER.into(), "AT".into()),
+            (CLAIM_ISSUED_AT.into(), 1_622_505_600.into()),
+            (
+                CLAIM_HCERT.into(),
+                Cbor::Map(vec![(
+                    HCERT_EU_DCC.into(),
+                    Cbor::Map(vec![("ver".into(), "1.3.0".into())]),
+                )]),
+            ),
+        ]));
+        let signed = cbor(&Cbor::Array(vec![
+            "Signature1".into(),
+            Cbor::Bytes(protected.clone()),
+            Cbor::Bytes(Vec::new()),
+            Cbor::Bytes(payload.clone()),
+        ]));
+        let signature: Signature = key.sign(&signed);
+        let cose = cbor(&Cbor::Tag(
+            18,
+            Box::new(Cbor::Array(vec![
+                Cbor::Bytes(protected),
+                Cbor::Map(Vec::new()),
+                Cbor::Bytes(payload),
+                Cbor::Bytes(signature.to_bytes().to_vec()),
+            ])),
+        ));
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(&cose).unwrap();
+        format!("{PREFIX}{}", base45_encode(&zlib.finish().unwrap()))
+    }
+
+    fn signing_key(byte: u8) -> SigningKey {
+        SigningKey::from_bytes(&[byte; 32].into()).unwrap()
+    }
+
+    #[test]
+    fn base45() {
+        assert_eq!(base45_decode("BB8").unwrap(), b"AB");
+        assert_eq!(base45_decode("%69 VD92EX0").unwrap(), b"Hello!!");
+        assert_eq!(base45_decode("QED8WEX0").unwrap(), b"ietf!");
+        assert!(base45_decode("GGW").is_err());
+        assert!(base45_decode("BB8A").is_err());
+        assert!(base45_decode("bb8").is_err());
+    }
+
+    #[test]
+    fn decode_without_keys() {
+        let dcc = decode(&certificate(&signing_key(1)), &TrustStore::default());
+        assert!(dcc.valid, "{:?}", dcc.errors);
+        assert_eq!(dcc.signature, "not checked");
+        assert_eq!(dcc.claims["issuer"], "AT");
+        assert_eq!(dcc.claims["issued_at"], "2021-06-01T00:00:00+00:00");
+        assert_eq!(dcc.certificate.unwrap()["ver"], "1.3.0");
+        assert_eq!(dcc.header.alg.as_deref(), Some("ES256"));
+    }
+
+    #[test]
+    fn check_signature() {
+        let key = signing_key(1);
+        let trust = |key: &SigningKey| TrustStore {
+            by_kid: Vec::new(),
+            any: vec![PublicKey::Ec(*key.verifying_key())],
+        };
+        let dcc = decode(&certificate(&key), &trust(&key));
+        assert_eq!(dcc.signature, "valid");
+        assert!(dcc.valid);
+
+        let dcc = decode(&certificate(&key), &trust(&signing_key(2)));
+        assert_eq!(dcc.signature, "invalid");
+        assert!(!dcc.valid);
+    }
+
+    #[test]
+    fn not_a_certificate() {
+        assert!(!decode("HC2:BB8", &TrustStore::default()).valid);
+        assert!(!decode("HC1:BB8", &TrustStore::default()).valid);
+    }
+}
@@ -0,0 +1,473 @@
+use crate::{
+    gs1::{self, AiSpec, Content, Element, ElementString},
+    uri,
+};
+
+/// The GS1 global resolver.
+pub const DEFAULT_RESOLVER: &str = "https://id.gs1.org";
+
+/// The primary keys a Digital Link is built around, with the key qualifiers that may follow
+/// them in the path, in the order they must appear.
+const PRIMARY_KEYS: &[(&str, &[&str])] = &[
+    ("01", &["22", "10", "21"]),
+    ("8006", &["22", "10", "21"]),
+    ("8010", &["8011"]),
+    ("414", &["254"]),
+    ("8017", &["8019"]),
+    ("8018", &["8019"]),
+    ("00", &[]),
+    ("253", &[]),
+    ("255", &[]),
+    ("401", &[]),
+    ("402", &[]),
+    ("417", &[]),
+    ("8003", &[]),
+    ("8004", &[]),
+    ("8013", &[]),
+];
+
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Build the Digital Link URI for `elements` on `resolver`: the primary key and its
+/// qualifiers form the path and the other AIs the query, e.g.
+/// `https://id.gs1.org/01/09506000134352/10/ABC?17=260101`.
+///
+/// The `compressed` form packs all AIs into one base64url path segment, as described in the
+/// GS1 Digital Link compression standard (without its optimisation codes).
+pub fn to_uri(
+    elements: &ElementString,
+    resolver: &str,
+    compressed: bool,
+) -> Result<String, String> {
+    if !(resolver.starts_with("https://") || resolver.starts_with("http://")) {
+        return Err(format!("the resolver '{resolver}' is not an http(s) URL"));
+    }
+    let resolver = resolver.trim_end_matches('/');
+    let (path, query) = arrange(&elements.elements)?;
+
+    if compressed {
+        let mut bits = Bits::default();
+        for element in path.iter().chain(&query) {
+            compress_element(&mut bits, element);
+        }
+        return Ok(format!("{resolver}/{}", bits.to_base64url()));
+    }
+
+    let mut uri = resolver.to_owned();
+    for element in &path {
+        uri.push_str(&format!(
+            "/{}/{}",
+            element.ai,
+            uri::percent_encode(&element.value)
+        ));
+    }
+    let query: Vec<_> = query
+        .iter()
+        .map(|element| format!("{}={}", element.ai, uri::percent_encode(&element.value)))
+        .collect();
+    if !query.is_empty() {
+        uri.push('?');
+        uri.push_str(&query.join("&"));
+    }
+    Ok(uri)
+}
+
+/// Split the elements into the path (primary key and its qualifiers, in order) and the query
+/// (data attributes).
+fn arrange(elements: &[Element]) -> Result<(Vec<&Element>, Vec<&Element>), String> {
+    let mut keys = elements
+        .iter()
+        .filter(|e| PRIMARY_KEYS.iter().any(|(key, _)| *key == e.ai));
+    let Some(key) = keys.next() else {
+        let names: Vec<_> = PRIMARY_KEYS
+            .iter()
+            .map(|(key, _)| format!("({key})"))
+            .collect();
+        return Err(format!("it needs one primary key: {}", names.join(", ")));
+    };
+    if let Some(other) = keys.next() {
+        return Err(format!(
+            "it can only have one primary key, ({}) and ({}) were given",
+            key.ai, other.ai
+        ));
+    }
+    let qualifiers = qualifiers_of(&key.ai);
+
+    let mut path = vec![key];
+    path.extend(
+        qualifiers
+            .iter()
+            .filter_map(|q| elements.iter().find(|e| e.ai == *q)),
+    );
+    let query = elements
+        .iter()
+        .filter(|e| !path.iter().any(|p| p.ai == e.ai))
+        .collect();
+    Ok((path, query))
+}
+
+fn qualifiers_of(key: &str) -> &'static [&'static str] {
+    PRIMARY_KEYS
+        .iter()
+        .find(|(k, _)| *k == key)
+        .map_or(&[], |(_, qualifiers)| qualifiers)
+}
+
+fn is_primary_key(ai: &str) -> bool {
+    PRIMARY_KEYS.iter().any(|(key, _)| *key == ai)
+}
+
+/// Expand a Digital Link URI into its AIs, or `None` if `text` is not one. Both the plain
+/// form and the compressed form are recognised, query parameters that are not AIs (such as
+/// `linkType`) are left out. The primary key has to be valid, the other elements are
+/// reported with their errors.
+pub fn parse(text: &str) -> Option<ElementString> {
+    let rest = text
+        .strip_prefix("https://")
+        .or_else(|| text.strip_prefix("http://"))?;
+    let rest = rest.split('#').next().unwrap_or(rest);
+    let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+    // the host, and any prefix of the resolver's own, come before the primary key
+    let segments: Vec<_> = location
+        .split('/')
+        .skip(1)
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    // a path segment that only looks like a primary key, e.g. /01/ of a web page, does not
+    // make the URL a Digital Link
+    let Some(start) = (0..segments.len().saturating_sub(1)).find(|&i| {
+        is_primary_key(segments[i])
+            && element(segments[i], &uri::percent_decode(segments[i + 1]))
+                .is_some_and(|key| key.errors.is_empty())
+    }) else {
+        return segments.last().and_then(|last| decompress(last));
+    };
+
+    let mut elements = Vec::new();
+    for pair in segments[start..].chunks(2) {
+        let [ai, value] = pair else {
+            return None;
+        };
+        elements.push(element(ai, &uri::percent_decode(value))?);
+    }
+    for parameter in query.split('&') {
+        let Some((ai, value)) = parameter.split_once('=') else {
+            continue;
+        };
+        if let Some(element) = element(ai, &uri::percent_decode(value)) {
+            elements.push(element);
+        }
+    }
+    Some(ElementString::new(elements, Vec::new()))
+}
+
+/// The validated element for a complete, known `ai`.
+fn element(ai: &str, value: &str) -> Option<Element> {
+    let spec = spec_of(ai)?;
+    Some(gs1::validate(spec, ai, value))
+}
+
+fn spec_of(ai: &str) -> Option<&'static AiSpec> {
+    gs1::lookup(ai).filter(|spec| spec.ai_length == ai.len())
+}
+
+/// The parts a value is compressed in, following the format of its AI.
+#[derive(Clone, Copy)]
+enum Component {
+    FixedNumeric(usize),
+    VariableNumeric(usize),
+    Alphanumeric(usize),
+}
+
+fn components(spec: &AiSpec) -> Vec<Component> {
+    use Component::*;
+
+    match (spec.content, spec.fixed) {
+        (Content::Alphanumeric, _) => vec![Alphanumeric(spec.max)],
+        (Content::CheckDigitThenText(n), _) => vec![FixedNumeric(n), Alphanumeric(spec.max - n)],
+        // the amount after the currency code of (391n) and (393n) is numeric
+        (Content::DigitsThenText(n), _) if spec.code.starts_with("39") => {
+            vec![FixedNumeric(n), VariableNumeric(spec.max - n)]
+        }
+        (Content::DigitsThenText(n), _) => vec![FixedNumeric(n), Alphanumeric(spec.max - n)],
+        (_, Some(length)) => vec![FixedNumeric(length)],
+        (_, None) => vec![VariableNumeric(spec.max)],
+    }
+}
+
+/// Encodings of alphanumeric values, written as a 3 bit indicator.
+const NUMERIC: u64 = 0;
+const LOWER_HEX: u64 = 1;
+const UPPER_HEX: u64 = 2;
+const URL_SAFE_BASE64: u64 = 3;
+const ASCII: u64 = 4;
+
+/// Digits that fit into the 64 bits used for numbers.
+const MAX_NUMERIC_DIGITS: usize = 19;
+
+/// Bits needed for a number of `digits` decimal digits.
+fn numeric_bits(digits: usize) -> usize {
+    (digits as f64 * 10f64.log2()).ceil() as usize
+}
+
+/// Bits needed for a length of up to `max`.
+fn length_bits(max: usize) -> usize {
+    (usize::BITS - max.leading_zeros()) as usize
+}
+
+fn compress_element(bits: &mut Bits, element: &Element) {
+    for digit in element.ai.bytes() {
+        bits.push((digit - b'0') as u64, 4);
+    }
+    let Some(spec) = spec_of(&element.ai) else {
+        return;
+    };
+    let mut value = element.value.as_str();
+    for component in components(spec) {
+        match component {
+            Component::FixedNumeric(length) => {
+                let (digits, rest) = value.split_at(length.min(value.len()));
+                bits.push(digits.parse().unwrap_or_default(), numeric_bits(length));
+                value = rest;
+            }
+            Component::VariableNumeric(max) => {
+                bits.push(value.len() as u64, length_bits(max));
+                bits.push(value.parse().unwrap_or_default(), numeric_bits(value.len()));
+                value = "";
+            }
+            Component::Alphanumeric(max) => {
+                compress_text(bits, value, max);
+                value = "";
+            }
+        }
+    }
+}
+
+fn compress_text(bits: &mut Bits, text: &str, max: usize) {
+    let all = |allowed: fn(&u8) -> bool| text.bytes().all(|b| allowed(&b));
+    let encoding = if all(u8::is_ascii_digit) && text.len() <= MAX_NUMERIC_DIGITS {
+        NUMERIC
+    } else if all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(b)) {
+        LOWER_HEX
+    } else if all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(b)) {
+        UPPER_HEX
+    } else if all(|b| BASE64URL.contains(b)) {
+        URL_SAFE_BASE64
+    } else {
+        ASCII
+    };
+
+    bits.push(encoding, 3);
+    bits.push(text.len() as u64, length_bits(max));
+    match encoding {
+        NUMERIC => bits.push(text.parse().unwrap_or_default(), numeric_bits(text.len())),
+        LOWER_HEX | UPPER_HEX => {
+            for c in text.chars() {
+                bits.push(c.to_digit(16).unwrap_or_default() as u64, 4);
+            }
+        }
+        URL_SAFE_BASE64 => {
+            for b in text.bytes() {
+                let index = BASE64URL.iter().position(|c| *c == b).unwrap_or_default();
+                bits.push(index as u64, 6);
+            }
+        }
+        _ => {
+            for b in text.bytes() {
+                bits.push((b & 0x7f) as u64, 7);
+            }
+        }
+    }
+}
+
+/// Unpack a compressed Digital Link path segment, which must start with a primary key.
+fn decompress(segment: &str) -> Option<ElementString> {
+    let mut bits = Bits::from_base64url(segment)?;
+    let mut elements = Vec::new();
+
+    // what is left after the last element is padding to whole base64url characters
+    while bits.remaining() >= 8 {
+        let mut ai = String::new();
+        let spec = loop {
+            let digit = bits.read(4)?;
+            if digit > 9 || ai.len() == 4 {
+                // optimisation codes and AIs that are not known can not be expanded
+                return None;
+            }
+            ai.push(char::from(b'0' + digit as u8));
+            if let Some(spec) = (ai.len() >= 2).then(|| spec_of(&ai)).flatten() {
+                break spec;
+            }
+        };
+
+        let mut value = String::new();
+        for component in components(spec) {
+            match component {
+                Component::FixedNumeric(length) => {
+                    value.push_str(&read_number(&mut bits, length)?);
+                }
+                Component::VariableNumeric(max) => {
+                    let length = bits.read(length_bits(max))? as usize;
+                    value.push_str(&read_number(&mut bits, length)?);
+                }
+                Component::Alphanumeric(max) => {
+                    value.push_str(&decompress_text(&mut bits, max)?);
+                }
+            }
+        }
+        elements.push(gs1::validate(spec, &ai, &value));
+    }
+
+    let starts_with_key = elements.first().is_some_and(|e| is_primary_key(&e.ai));
+    let is_valid = elements.iter().all(|e| e.errors.is_empty());
+    (starts_with_key && is_valid).then(|| ElementString::new(elements, Vec::new()))
+}
+
+fn read_number(bits: &mut Bits, digits: usize) -> Option<String> {
+    if digits > MAX_NUMERIC_DIGITS {
+        return None;
+    }
+    let number = bits.read(numeric_bits(digits))?;
+    let text = format!("{number:0digits$}");
+    (text.len() == digits).then_some(text)
+}
+
+fn decompress_text(bits: &mut Bits, max: usize) -> Option<String> {
+    let encoding = bits.read(3)?;
+    let length = bits.read(length_bits(max))? as usize;
+    let mut text = String::new();
+    match encoding {
+        NUMERIC => text = read_number(bits, length)?,
+        LOWER_HEX | UPPER_HEX => {
+            for _ in 0..length {
+                let digit = char::from_digit(bits.read(4)? as u32, 16)?;
+                text.push(if encoding == UPPER_HEX {
+                    digit.to_ascii_uppercase()
+                } else {
+                    digit
+                });
+            }
+        }
+        URL_SAFE_BASE64 => {
+            for _ in 0..length {
+                text.push(BASE64URL[bits.read(6)? as usize] as char);
+            }
+        }
+        ASCII => {
+            for _ in 0..length {
+                text.push(bits.read(7)? as u8 as char);
+            }
+        }
+        _ => return None,
+    }
+    Some(text)
+}
+
+/// A string of bits, most significant first.
+#[derive(Default)]
+struct Bits {
+    bits: Vec<bool>,
+    position: usize,
+}
+
+impl Bits {
+    fn push(&mut self, value: u64, count: usize) {
+        self.bits
+            .extend((0..count).rev().map(|i| i < 64 && value >> i & 1 == 1));
+    }
+
+    fn read(&mut self, count: usize) -> Option<u64> {
+        if count > 64 || self.remaining() < count {
+            return None;
+        }
+        let value = self.bits[self.position..self.position + count]
+            .iter()
+            .fold(0, |value, bit| value << 1 | *bit as u64);
+        self.position += count;
+        Some(value)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bits.len() - self.position
+    }
+
+    /// Padded with zero bits to whole characters.
+    fn to_base64url(&self) -> String {
+        self.bits
+            .chunks(6)
+            .map(|chunk| {
+                let index = (0..6).fold(0, |index, i| {
+                    index << 1 | *chunk.get(i).unwrap_or(&false) as usize
+                });
+                BASE64URL[index] as char
+            })
+            .collect()
+    }
+
+    fn from_base64url(text: &str) -> Option<Self> {
+        let mut bits = Bits::default();
+        for b in text.bytes() {
+            let index = BASE64URL.iter().position(|c| *c == b)?;
+            bits.push(index as u64, 6);
+        }
+        Some(bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_plain_uri() {
+        let element_string =
+            parse("https://id.gs1.org/01/09506000134352/10/ABC?17=260101&linkType=gs1:pip")
+                .unwrap();
+        assert_eq!(element_string.hri, "(01)09506000134352(10)ABC(17)260101");
+        assert!(element_string.valid);
+    }
+
+    #[test]
+    fn web_url_with_key_like_segment_is_not_a_digital_link() {
+        assert!(parse("https://example.com/blog/01/2024-review").is_none());
+        assert!(parse("https://example.com/00/home").is_none());
+        // a wrong check digit in the GTIN
+        assert!(parse("https://id.gs1.org/01/09506000134353").is_none());
+    }
+
+    #[test]
+    fn build_plain_uri() {
+        let element_string = gs1::parse_hri("(17)260101(10)AB C(01)09506000134352", false).unwrap();
+        assert_eq!(
+            to_uri(&element_string, "https://example.com/gs1/", false).unwrap(),
+            "https://example.com/gs1/01/09506000134352/10/AB%20C?17=260101"
+        );
+        assert!(to_uri(&element_string, "ftp://example.com", false).is_err());
+
+        let without_key = gs1::parse_hri("(10)ABC", false).unwrap();
+        assert!(to_uri(&without_key, DEFAULT_RESOLVER, false).is_err());
+    }
+
+    #[test]
+    fn compression_round_trip() {
+        let element_string =
+            gs1::parse_hri("(01)09506000134352(10)abc-12(3103)000750(17)260101", false).unwrap();
+        let uri = to_uri(&element_string, DEFAULT_RESOLVER, true).unwrap();
+        let segment = uri.rsplit('/').next().unwrap();
+        assert!(segment
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+        let parsed = parse(&uri).unwrap();
+        assert_eq!(
+            parsed.hri,
+            "(01)09506000134352(10)abc-12(3103)000750(17)260101"
+        );
+    }
+
+    #[test]
+    fn compressed_segment_that_is_not_a_digital_link() {
+        assert!(parse("https://example.com/AQnYUc1gmiA").is_none());
+        assert!(parse("https://example.com/index.html").is_none());
+    }
+}
@@ -0,0 +1,1368 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use clap::{Arg, ArgGroup, Command, Subcommand};
+use rxing::BarcodeFormat;
+
+use crate::{
+    client_payload::{Email, Event, Wifi},
+    colour::Colour,
+    encode_options::{format_name, RawEncodeOptions},
+    epc::Transfer,
+    otpauth::{self, Key},
+    payment_uri::{Bitcoin, Upi},
+    stdio::ImageFormat,
+    structured_append,
+    swiss_qr_bill::{Address, Bill},
+    units::{self, Length},
+};
+
+/// Flags shared by every barcode format.
+#[derive(clap::Args)]
+#[command(group(
+    ArgGroup::new("data_source")
+    .required(true)
+    .args(["data", "data_file"]),
+))]
+pub struct CommonEncodeArgs {
+    /// Maximum width of the image, in pixels or with a physical unit (mm, cm, in, mil) and
+    /// --dpi. Without --module-size the largest whole number of pixels per module that fits
+    /// is used.
+    #[arg(long, verbatim_doc_comment)]
+    pub width: Option<Length>,
+
+    /// Maximum height of the image, like --width. One dimensional barcodes are drawn this tall.
+    #[arg(long)]
+    pub height: Option<Length>,
+
+    /// Size of one module (the narrowest bar, or one square of a 2D symbol) in pixels.
+    /// Defaults to 4 when neither --width nor --height is given.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), verbatim_doc_comment)]
+    pub module_size: Option<u32>,
+
+    /// Width of one module as printed, e.g. 0.33mm or 13mil. Requires --dpi, the module size
+    /// is rounded to whole printer dots.
+    #[arg(long, value_parser = units::parse_physical, requires = "dpi", conflicts_with = "module_size", verbatim_doc_comment)]
+    pub x_dimension: Option<f64>,
+
+    /// Printer resolution in dots per inch, used to convert physical sizes to pixels. It is
+    /// also stored in PNG, JPEG and SVG files so they print at the intended size.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..65536), verbatim_doc_comment)]
+    pub dpi: Option<u32>,
+
+    /// Width of the blank border around the symbol, in modules. Defaults to the minimum
+    /// required by the format, e.g. 4 for QR codes and 10 for one dimensional barcodes.
+    #[arg(long, verbatim_doc_comment)]
+    pub quiet_zone: Option<u32>,
+
+    /// Border in pixels instead of the quiet zone, what --margin meant for the single encode
+    /// command. Only given by `rewrite_legacy_args`.
+    #[arg(long, hide = true, conflicts_with = "quiet_zone")]
+    pub margin_pixels: Option<u32>,
+
+    /// Colour of the bars and dark modules, as #rrggbb, #rrggbbaa or r,g,b[,a].
+    #[arg(long, default_value_t = Colour::BLACK)]
+    pub foreground: Colour,
+
+    /// Colour of the spaces, light modules and quiet zone, like --foreground.
+    #[arg(long, default_value_t = Colour::WHITE)]
+    pub background: Colour,
+
+    /// Make the background fully transparent. The contrast is checked as if the image is
+    /// placed on white.
+    #[arg(long, verbatim_doc_comment)]
+    pub transparent: bool,
+
+    /// Decode the rendered image before saving it and fail, without writing anything, unless
+    /// it decodes to exactly the input. The raw bytes are compared too where the reader
+    /// returns them (QR Code, Data Matrix and Aztec byte mode).
+    #[arg(long, verbatim_doc_comment)]
+    pub verify: bool,
+
+    /// String input for the encoder.
+    #[arg(short, long)]
+    pub data: Option<String>,
+
+    /// A file containing the text to be encoded.
+    #[arg(long)]
+    pub data_file: Option<PathBuf>,
+
+    /// Format of the written image. Required when writing to stdout ('-'), otherwise the
+    /// format is inferred from the file extension.
+    #[arg(long, value_enum, verbatim_doc_comment)]
+    pub image_format: Option<ImageFormat>,
+}
+
+/// Bracketed GS1 input, for the formats that can carry GS1 data.
+#[derive(clap::Args)]
+pub struct Gs1Args {
+    /// Read the data as GS1 application identifiers and values in the bracketed form printed
+    /// under barcodes, e.g. (01)09506000134352(17)260101(10)ABC. The values are validated
+    /// and FNC1 separators are inserted after values whose length is not predefined.
+    #[arg(long, verbatim_doc_comment)]
+    pub gs1: bool,
+
+    /// With --gs1, append the check digit to a GTIN, SSCC, GLN or other key that is one digit
+    /// short.
+    #[arg(long, requires = "gs1", verbatim_doc_comment)]
+    pub gs1_check_digit: bool,
+}
+
+/// GS1 Digital Link URIs, which are encoded as plain QR codes.
+#[derive(clap::Args)]
+pub struct DigitalLinkArgs {
+    /// With --gs1, encode the AIs as a GS1 Digital Link URI, e.g.
+    /// https://id.gs1.org/01/09506000134352/10/ABC?17=260101, instead of an element string.
+    #[arg(long, requires = "gs1", verbatim_doc_comment)]
+    pub digital_link: bool,
+
+    /// The resolver the Digital Link points to, optionally with a path prefix, e.g.
+    /// https://example.com/gs1 [default: https://id.gs1.org]
+    #[arg(long, requires = "digital_link", verbatim_doc_comment)]
+    pub resolver: Option<String>,
+
+    /// Pack the AIs of the Digital Link into one compressed path segment, for a smaller symbol.
+    #[arg(long, requires = "digital_link")]
+    pub compress: bool,
+}
+
+/// Payloads the client result parsers of readers understand, built with the escaping they
+/// expect. Each first flag replaces --data.
+#[derive(clap::Args)]
+pub struct ContentArgs {
+    /// Encode a WiFi network with this SSID.
+    #[arg(long, group = "data_source", conflicts_with = "gs1")]
+    pub wifi_ssid: Option<String>,
+
+    /// The WiFi authentication [default: WPA with --wifi-password, nopass without]
+    #[arg(long, requires = "wifi_ssid", value_parser = ["WPA", "WEP", "WPA2-EAP", "nopass"])]
+    pub wifi_auth: Option<String>,
+
+    /// The WiFi password or passphrase.
+    #[arg(long, requires = "wifi_ssid")]
+    pub wifi_password: Option<String>,
+
+    /// The WiFi network does not broadcast its SSID.
+    #[arg(long, requires = "wifi_ssid")]
+    pub wifi_hidden: bool,
+
+    /// The EAP method of a WPA2-EAP network, e.g. PEAP or TTLS.
+    #[arg(long, requires = "wifi_ssid")]
+    pub wifi_eap_method: Option<String>,
+
+    /// The phase 2 method of a WPA2-EAP network, e.g. MSCHAPV2.
+    #[arg(long, requires = "wifi_ssid")]
+    pub wifi_phase2: Option<String>,
+
+    /// The user name of a WPA2-EAP network.
+    #[arg(long, requires = "wifi_ssid")]
+    pub wifi_identity: Option<String>,
+
+    /// The anonymous outer identity of a WPA2-EAP network.
+    #[arg(long, requires = "wifi_ssid")]
+    pub wifi_anonymous_identity: Option<String>,
+
+    /// Encode a contact as a vCard 3.0, read from a JSON file with name (or family_name and
+    /// given_name), nickname, org, title, phones, emails, addresses, urls, note and birthday.
+    /// Phones, emails and addresses are strings or objects with a value and a type.
+    #[arg(
+        long,
+        group = "data_source",
+        conflicts_with = "gs1",
+        verbatim_doc_comment
+    )]
+    pub vcard_file: Option<PathBuf>,
+
+    /// Encode a contact from a JSON file like --vcard-file as a MECARD, which is shorter.
+    #[arg(long, group = "data_source", conflicts_with = "gs1")]
+    pub mecard_file: Option<PathBuf>,
+
+    /// Encode an SMS to this phone number.
+    #[arg(long, group = "data_source", conflicts_with = "gs1")]
+    pub sms_number: Option<String>,
+
+    /// The text of the SMS.
+    #[arg(long, requires = "sms_number")]
+    pub sms_body: Option<String>,
+
+    /// Encode an email to these addresses, separated by commas.
+    #[arg(long, group = "data_source", conflicts_with = "gs1")]
+    pub email_to: Option<String>,
+
+    /// Addresses to send a copy of the email to, separated by commas.
+    #[arg(long, requires = "email_to")]
+    pub email_cc: Option<String>,
+
+    /// Addresses to send a blind copy of the email to, separated by commas.
+    #[arg(long, requires = "email_to")]
+    pub email_bcc: Option<String>,
+
+    /// The subject of the email.
+    #[arg(long, requires = "email_to")]
+    pub email_subject: Option<String>,
+
+    /// The text of the email.
+    #[arg(long, requires = "email_to")]
+    pub email_body: Option<String>,
+
+    /// Encode a location at this latitude in degrees, e.g. 46.9481.
+    #[arg(
+        long,
+        group = "data_source",
+        conflicts_with = "gs1",
+        requires = "geo_longitude",
+        allow_negative_numbers = true
+    )]
+    pub geo_latitude: Option<f64>,
+
+    /// The longitude of the location in degrees, e.g. 7.4474.
+    #[arg(long, requires = "geo_latitude", allow_negative_numbers = true)]
+    pub geo_longitude: Option<f64>,
+
+    /// The altitude of the location in metres.
+    #[arg(long, requires = "geo_latitude", allow_negative_numbers = true)]
+    pub geo_altitude: Option<f64>,
+
+    /// A place to search for near the location, e.g. Federal Palace.
+    #[arg(long, requires = "geo_latitude")]
+    pub geo_query: Option<String>,
+
+    /// Encode a calendar event starting at this time: YYYY-MM-DD for an all day event,
+    /// YYYY-MM-DDTHH:MM[:SS] in local time, or RFC 3339 with an offset.
+    #[arg(
+        long,
+        group = "data_source",
+        conflicts_with = "gs1",
+        verbatim_doc_comment
+    )]
+    pub event_start: Option<String>,
+
+    /// The end of the event, like --event-start.
+    #[arg(long, requires = "event_start")]
+    pub event_end: Option<String>,
+
+    /// The title of the event.
+    #[arg(long, requires = "event_start")]
+    pub event_summary: Option<String>,
+
+    /// Where the event takes place.
+    #[arg(long, requires = "event_start")]
+    pub event_location: Option<String>,
+
+    /// A longer description of the event.
+    #[arg(long, requires = "event_start")]
+    pub event_description: Option<String>,
+}
+
+impl ContentArgs {
+    /// Whether one of the payload builders replaces --data.
+    pub fn is_used(&self) -> bool {
+        self.wifi_ssid.is_some()
+            || self.vcard_file.is_some()
+            || self.mecard_file.is_some()
+            || self.sms_number.is_some()
+            || self.email_to.is_some()
+            || self.geo_latitude.is_some()
+            || self.event_start.is_some()
+    }
+
+    pub fn wifi(&self) -> Option<Wifi> {
+        let ssid = self.wifi_ssid.clone()?;
+        let auth = self.wifi_auth.clone().unwrap_or_else(|| {
+            if self.wifi_password.is_some() {
+                "WPA"
+            } else {
+                "nopass"
+            }
+            .to_owned()
+        });
+        Some(Wifi {
+            ssid,
+            auth,
+            password: self.wifi_password.clone(),
+            hidden: self.wifi_hidden,
+            eap_method: self.wifi_eap_method.clone(),
+            phase2: self.wifi_phase2.clone(),
+            identity: self.wifi_identity.clone(),
+            anonymous_identity: self.wifi_anonymous_identity.clone(),
+        })
+    }
+
+    pub fn email(&self) -> Option<Email> {
+        Some(Email {
+            to: self.email_to.clone()?,
+            cc: self.email_cc.clone(),
+            bcc: self.email_bcc.clone(),
+            subject: self.email_subject.clone(),
+            body: self.email_body.clone(),
+        })
+    }
+
+    pub fn event(&self) -> Option<Event> {
+        Some(Event {
+            start: self.event_start.clone()?,
+            end: self.event_end.clone(),
+            summary: self.event_summary.clone(),
+            location: self.event_location.clone(),
+            description: self.event_description.clone(),
+        })
+    }
+}
+
+#[derive(clap::Args)]
+pub struct QrCodeArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    #[command(flatten)]
+    pub gs1_input: Gs1Args,
+
+    #[command(flatten)]
+    pub digital_link: DigitalLinkArgs,
+
+    #[command(flatten)]
+    pub content: ContentArgs,
+
+    /// Specifies what degree of error correction to use (L, M, Q, H).
+    #[arg(long)]
+    pub error_correction: Option<String>,
+
+    /// Specifies what character encoding to use.
+    #[arg(long)]
+    pub character_set: Option<String>,
+
+    /**
+     Specifies the exact version of QR code to be encoded (1 to 40).
+    */
+    #[arg(long)]
+    pub qr_version: Option<String>,
+
+    /// Specifies the QR code mask pattern to be used. Allowed values are
+    /// 0..8. By default the code will automatically select
+    /// the optimal mask pattern.
+    #[arg(long, verbatim_doc_comment)]
+    pub qr_mask_pattern: Option<String>,
+
+    /// Specifies whether to use compact mode for QR code.
+    /// Please note that when compaction is performed, the most compact character encoding is chosen
+    /// for characters in the input that are not in the ISO-8859-1 character set. Based on experience,
+    /// some scanners do not support encodings like cp-1256 (Arabic). In such cases the encoding can
+    /// be forced to UTF-8 by means of the #CHARACTER_SET encoding hint.
+    #[arg(long, verbatim_doc_comment)]
+    pub qr_compact: Option<bool>,
+
+    /**
+     Specifies whether the data should be encoded to the GS1 standard/
+    */
+    #[arg(long)]
+    pub gs1_format: Option<bool>,
+
+    /// Split the data over COUNT symbols (2 to 16) of a Structured Append sequence, for data
+    /// too big for one QR code. Every symbol carries its position, the count and the parity
+    /// of the whole data, and holds a part of the text that also reads on its own.
+    #[arg(
+        long,
+        value_name = "COUNT",
+        value_parser = clap::value_parser!(u8).range(2..=structured_append::MAX_SYMBOLS as i64),
+        conflicts_with_all = ["gs1", "qr_compact", "gs1_format"],
+        verbatim_doc_comment
+    )]
+    pub structured_append: Option<u8>,
+
+    /// How to write the symbols of --structured-append: one file per symbol, numbered like
+    /// name-01-of-05.png, or all of them in one image.
+    #[arg(
+        long,
+        value_enum,
+        default_value_t,
+        requires = "structured_append",
+        verbatim_doc_comment
+    )]
+    pub structured_append_layout: structured_append::Layout,
+}
+
+#[derive(clap::Args)]
+pub struct Pdf417Args {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// Specifies what degree of error correction to use, valid values being 0 to 8.
+    #[arg(long)]
+    pub error_correction: Option<String>,
+
+    /// Specifies what character encoding to use.
+    #[arg(long)]
+    pub character_set: Option<String>,
+
+    /**
+     Specifies whether to use compact mode for PDF417.
+    */
+    #[arg(long)]
+    pub pdf_417_compact: Option<bool>,
+
+    /**
+     Specifies what compaction mode to use for PDF417
+     AUTO = 0,
+     TEXT = 1,
+     BYTE = 2,
+     NUMERIC = 3
+    */
+    #[arg(long)]
+    pub pdf_417_compaction: Option<String>,
+
+    /// Specifies whether to automatically insert ECIs when encoding PDF417.
+    /// Please note that in that case, the most compact character encoding is chosen for characters in
+    /// the input that are not in the ISO-8859-1 character set. Based on experience, some scanners do not
+    /// support encodings like cp-1256 (Arabic). In such cases the encoding can be forced to UTF-8 by
+    /// means of the #CHARACTER_SET encoding hint.
+    #[arg(long, verbatim_doc_comment)]
+    pub pdf_417_auto_eci: Option<bool>,
+}
+
+#[derive(clap::Args)]
+pub struct AztecArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// Specifies the minimal percentage of error correction words.
+    /// Note: an Aztec symbol should have a minimum of 25% EC words.
+    #[arg(long, verbatim_doc_comment)]
+    pub error_correction: Option<String>,
+
+    /// Specifies what character encoding to use.
+    #[arg(long)]
+    pub character_set: Option<String>,
+
+    /// Specifies the required number of layers for an Aztec code.
+    /// A negative number (-1, -2, -3, -4) specifies a compact Aztec code.
+    /// 0 indicates to use the minimum number of layers (the default).
+    /// A positive number (1, 2, .. 32) specifies a normal (non-compact) Aztec code.
+    #[arg(long, verbatim_doc_comment, allow_negative_numbers = true)]
+    pub aztec_layers: Option<i32>,
+}
+
+#[derive(clap::Args)]
+#[command(group(
+    ArgGroup::new("data_matrix_encoding")
+    .required(false)
+    .args(["data_matrix_compact","force_c40"]),
+))]
+pub struct DataMatrixArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    #[command(flatten)]
+    pub gs1_input: Gs1Args,
+
+    /// Specifies what character encoding to use, only used with --data-matrix-compact.
+    #[arg(long)]
+    pub character_set: Option<String>,
+
+    /// Specifies whether to use compact mode for Data Matrix.
+    /// The compact encoding mode also supports the encoding of characters that are not in the ISO-8859-1
+    /// character set via ECIs.
+    /// Please note that in that case, the most compact character encoding is chosen for characters in
+    /// the input that are not in the ISO-8859-1 character set. Based on experience, some scanners do not
+    /// support encodings like cp-1256 (Arabic). In such cases the encoding can be forced to UTF-8 by
+    /// means of the #CHARACTER_SET encoding hint.
+    /// Compact encoding also provides GS1-FNC1 support when #GS1_FORMAT is selected. In this case
+    /// group-separator character (ASCII 29 decimal) can be used to encode the positions of FNC1 codewords
+    /// for the purpose of delimiting AIs.
+    #[arg(long, verbatim_doc_comment)]
+    pub data_matrix_compact: Option<bool>,
+
+    /**
+     Forces C40 encoding for data-matrix. This
+    */
+    #[arg(long)]
+    pub force_c40: Option<bool>,
+
+    /**
+     Specifies whether the data should be encoded to the GS1 standard/
+    */
+    #[arg(long)]
+    pub gs1_format: Option<bool>,
+}
+
+#[derive(clap::Args)]
+#[command(group(
+    ArgGroup::new("code_set_rules")
+    .required(false)
+    .args(["code_128_compact", "force_code_set"]),
+))]
+pub struct Code128Args {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    #[command(flatten)]
+    pub gs1_input: Gs1Args,
+
+    /// Forces which encoding will be used.
+    /// Valid values are "A", "B", "C".
+    #[arg(long, verbatim_doc_comment)]
+    pub force_code_set: Option<String>,
+
+    /**
+     Specifies whether to use compact mode for Code-128 code.
+     This can yield slightly smaller bar codes.
+    */
+    #[arg(long)]
+    pub code_128_compact: Option<bool>,
+}
+
+/// A Swiss QR-bill, built from a JSON file or from the flags below, or given as a ready SPC
+/// payload with --data.
+#[derive(clap::Args)]
+pub struct SwissQrBillArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// A JSON file with the bill: "iban", "creditor" and "debtor" objects of "name", "street",
+    /// "building_number", "postal_code", "town" and "country", "amount", "currency",
+    /// "reference", "message", "bill_information" and "alternative_schemes".
+    #[arg(long, group = "data_source", verbatim_doc_comment)]
+    pub bill_file: Option<PathBuf>,
+
+    /// The creditor's IBAN or QR-IBAN (CH or LI), spaces are allowed.
+    #[arg(long, group = "data_source", requires_all = ["creditor_name", "creditor_postal_code", "creditor_town"])]
+    pub iban: Option<String>,
+
+    /// The creditor's name.
+    #[arg(long, requires = "iban")]
+    pub creditor_name: Option<String>,
+
+    #[arg(long, requires = "iban")]
+    pub creditor_street: Option<String>,
+
+    #[arg(long, requires = "iban")]
+    pub creditor_building_number: Option<String>,
+
+    #[arg(long, requires = "iban")]
+    pub creditor_postal_code: Option<String>,
+
+    #[arg(long, requires = "iban")]
+    pub creditor_town: Option<String>,
+
+    /// Two letter ISO 3166 country code.
+    #[arg(long, requires = "iban", default_value = "CH")]
+    pub creditor_country: String,
+
+    /// The amount, e.g. 1949.75. Without it the payer fills in the amount.
+    #[arg(long, requires = "iban")]
+    pub amount: Option<String>,
+
+    /// CHF or EUR.
+    #[arg(long, requires = "iban", default_value = "CHF")]
+    pub currency: String,
+
+    /// A QR reference for a QR-IBAN, 26 digits to have the check digit added or all 27, or
+    /// an ISO 11649 creditor reference, with its RF check digits or without to have them added.
+    #[arg(long, requires = "iban", verbatim_doc_comment)]
+    pub reference: Option<String>,
+
+    /// The unstructured message to the creditor.
+    #[arg(long, requires = "iban")]
+    pub message: Option<String>,
+
+    /// Structured billing information, e.g. //S1/10/10201409/11/190512.
+    #[arg(long, requires = "iban")]
+    pub bill_information: Option<String>,
+
+    /// The debtor's name, the debtor's address is only added when this is given.
+    #[arg(long, requires = "iban")]
+    pub debtor_name: Option<String>,
+
+    #[arg(long, requires = "debtor_name")]
+    pub debtor_street: Option<String>,
+
+    #[arg(long, requires = "debtor_name")]
+    pub debtor_building_number: Option<String>,
+
+    #[arg(long, requires = "debtor_name")]
+    pub debtor_postal_code: Option<String>,
+
+    #[arg(long, requires = "debtor_name")]
+    pub debtor_town: Option<String>,
+
+    #[arg(long, requires = "debtor_name", default_value = "CH")]
+    pub debtor_country: String,
+}
+
+impl SwissQrBillArgs {
+    /// The bill given with the flags.
+    pub fn bill(&self) -> Bill {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        Bill {
+            iban: text(&self.iban),
+            creditor: Address {
+                name: text(&self.creditor_name),
+                street: text(&self.creditor_street),
+                building_number: text(&self.creditor_building_number),
+                postal_code: text(&self.creditor_postal_code),
+                town: text(&self.creditor_town),
+                country: self.creditor_country.clone(),
+                ..Address::default()
+            },
+            amount: self.amount.clone(),
+            currency: self.currency.clone(),
+            debtor: self.debtor_name.as_ref().map(|name| Address {
+                name: name.clone(),
+                street: text(&self.debtor_street),
+                building_number: text(&self.debtor_building_number),
+                postal_code: text(&self.debtor_postal_code),
+                town: text(&self.debtor_town),
+                country: self.debtor_country.clone(),
+                ..Address::default()
+            }),
+            reference: text(&self.reference),
+            message: text(&self.message),
+            bill_information: text(&self.bill_information),
+            ..Bill::default()
+        }
+    }
+}
+
+/// An EPC QR code (GiroCode) for a SEPA credit transfer, built from the flags below, or given
+/// as a ready payload with --data.
+#[derive(clap::Args)]
+pub struct EpcArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// The beneficiary's IBAN, spaces are allowed.
+    #[arg(long, group = "data_source", requires = "name")]
+    pub iban: Option<String>,
+
+    /// The beneficiary's name, at most 70 characters.
+    #[arg(long, requires = "iban")]
+    pub name: Option<String>,
+
+    /// The BIC of the beneficiary's bank, required with --epc-version 001.
+    #[arg(long, requires = "iban")]
+    pub bic: Option<String>,
+
+    /// The amount in euro, e.g. 12.30. Without it the payer fills in the amount.
+    #[arg(long, requires = "iban")]
+    pub amount: Option<String>,
+
+    /// A four character ISO 20022 purpose code, e.g. CHAR for a charitable payment.
+    #[arg(long, requires = "iban")]
+    pub purpose: Option<String>,
+
+    /// Structured remittance information, usually an ISO 11649 creditor reference (RF...), at
+    /// most 35 characters.
+    #[arg(
+        long,
+        requires = "iban",
+        conflicts_with = "remittance_text",
+        verbatim_doc_comment
+    )]
+    pub reference: Option<String>,
+
+    /// Unstructured remittance information, at most 140 characters.
+    #[arg(long, requires = "iban")]
+    pub remittance_text: Option<String>,
+
+    /// Beneficiary to originator information shown to the payer, at most 70 characters.
+    #[arg(long, requires = "iban")]
+    pub information: Option<String>,
+
+    /// The version of the standard: 002 makes the BIC optional within the EEA.
+    #[arg(long, requires = "iban", default_value = "002", value_parser = ["001", "002"])]
+    pub epc_version: String,
+}
+
+impl EpcArgs {
+    /// The transfer given with the flags.
+    pub fn transfer(&self) -> Transfer {
+        let text = |value: &Option<String>| value.clone().unwrap_or_default();
+        Transfer {
+            bic: text(&self.bic),
+            name: text(&self.name),
+            iban: text(&self.iban),
+            amount: self.amount.clone(),
+            purpose: text(&self.purpose),
+            reference: text(&self.reference),
+            remittance_text: text(&self.remittance_text),
+            information: text(&self.information),
+            ..Transfer::new(&self.epc_version)
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct BitcoinArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// The Bitcoin address to pay, checked against its base58 or bech32 checksum.
+    #[arg(long, group = "data_source")]
+    pub address: Option<String>,
+
+    /// The amount in bitcoin, e.g. 0.0015, with at most eight decimals.
+    #[arg(long, requires = "address")]
+    pub amount: Option<String>,
+
+    /// A label for the recipient, shown by the wallet.
+    #[arg(long, requires = "address")]
+    pub label: Option<String>,
+
+    /// A message describing the payment, shown by the wallet.
+    #[arg(long, requires = "address")]
+    pub message: Option<String>,
+
+    /// A BOLT 11 invoice that wallets with Lightning support pay instead.
+    #[arg(long, requires = "address")]
+    pub lightning: Option<String>,
+}
+
+impl BitcoinArgs {
+    /// The payment request given with the flags.
+    pub fn request(&self) -> Bitcoin {
+        Bitcoin {
+            address: self.address.clone().unwrap_or_default(),
+            amount: self.amount.clone(),
+            label: self.label.clone(),
+            message: self.message.clone(),
+            lightning: self.lightning.clone(),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct LightningArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// A BOLT 11 invoice (lnbc...) or an LNURL, checked against its bech32 checksum.
+    #[arg(long, group = "data_source")]
+    pub invoice: Option<String>,
+}
+
+#[derive(clap::Args)]
+pub struct UpiArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// The payee's virtual payment address, e.g. shop@okaxis.
+    #[arg(long, group = "data_source", requires = "payee_name")]
+    pub vpa: Option<String>,
+
+    /// The payee's name.
+    #[arg(long, requires = "vpa")]
+    pub payee_name: Option<String>,
+
+    /// The amount in rupees, e.g. 250 or 99.50. Without it the payer fills in the amount.
+    #[arg(long, requires = "vpa")]
+    pub amount: Option<String>,
+
+    /// A note shown to the payer.
+    #[arg(long, requires = "vpa")]
+    pub note: Option<String>,
+
+    /// The payee's reference for the transaction, e.g. an order number.
+    #[arg(long, requires = "vpa")]
+    pub transaction_ref: Option<String>,
+
+    /// The four digit merchant category code of a merchant payee.
+    #[arg(long, requires = "vpa")]
+    pub merchant_code: Option<String>,
+}
+
+impl UpiArgs {
+    /// The payment given with the flags, always in rupees.
+    pub fn payment(&self) -> Upi {
+        Upi {
+            vpa: self.vpa.clone().unwrap_or_default(),
+            name: self.payee_name.clone(),
+            amount: self.amount.clone(),
+            currency: "INR".to_owned(),
+            note: self.note.clone(),
+            transaction_ref: self.transaction_ref.clone(),
+            merchant_code: self.merchant_code.clone(),
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct OtpAuthArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+
+    /// The account the secret is for, usually a user name or email address.
+    #[arg(long, group = "data_source")]
+    pub account: Option<String>,
+
+    /// The provider or service the account belongs to, e.g. Example Corp.
+    #[arg(long, requires = "account")]
+    pub issuer: Option<String>,
+
+    /// The shared secret in Base32, spaces and lower case are allowed. Without it a random
+    /// secret as long as the hash of --algorithm is generated and printed.
+    #[arg(long, requires = "account", verbatim_doc_comment)]
+    pub secret: Option<String>,
+
+    /// Time based (RFC 6238) or counter based (RFC 4226) codes.
+    #[arg(long, requires = "account", default_value = "totp", value_parser = ["totp", "hotp"])]
+    pub otp_type: String,
+
+    /// The HMAC hash function.
+    #[arg(long, requires = "account", default_value = "SHA1", value_parser = ["SHA1", "SHA256", "SHA512"])]
+    pub algorithm: String,
+
+    /// The number of digits of a code.
+    #[arg(long, requires = "account", default_value_t = 6, value_parser = clap::value_parser!(u32).range(6..=8))]
+    pub digits: u32,
+
+    /// The seconds a TOTP code is valid for.
+    #[arg(long, requires = "account", default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+    pub period: u64,
+
+    /// The initial counter of an HOTP key.
+    #[arg(long, requires = "account", default_value_t = 0)]
+    pub counter: u64,
+}
+
+impl OtpAuthArgs {
+    /// The key given with the flags, `secret` being --secret or a generated one.
+    pub fn key(&self, secret: String) -> Key {
+        let key = otpauth::new_key(&self.otp_type, self.account.as_deref().unwrap_or_default());
+        Key {
+            issuer: self.issuer.clone(),
+            secret,
+            algorithm: self.algorithm.clone(),
+            digits: self.digits,
+            period: key.period.map(|_| self.period),
+            counter: key.counter.map(|_| self.counter),
+            ..key
+        }
+    }
+}
+
+/// Options for the one dimensional formats without any of their own.
+#[derive(clap::Args)]
+pub struct OneDArgs {
+    #[command(flatten)]
+    pub common: CommonEncodeArgs,
+}
+
+// parsed once from the command line, so the size of the variants does not matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum EncodeFormat {
+    /// QR Code
+    #[command(name = "qrcode")]
+    QrCode(QrCodeArgs),
+    /// PDF 417
+    #[command(name = "pdf417")]
+    Pdf417(Pdf417Args),
+    /// Aztec
+    #[command(name = "aztec")]
+    Aztec(AztecArgs),
+    /// Data Matrix
+    #[command(name = "datamatrix")]
+    DataMatrix(DataMatrixArgs),
+    /// Code 128
+    #[command(name = "code128")]
+    Code128(Code128Args),
+    /// Codabar
+    #[command(name = "codabar")]
+    Codabar(OneDArgs),
+    /// Code 39
+    #[command(name = "code39")]
+    Code39(OneDArgs),
+    /// Code 93
+    #[command(name = "code93")]
+    Code93(OneDArgs),
+    /// EAN-8
+    #[command(name = "ean8")]
+    Ean8(OneDArgs),
+    /// EAN-13
+    #[command(name = "ean13")]
+    Ean13(OneDArgs),
+    /// Interleaved 2 of 5
+    #[command(name = "itf")]
+    Itf(OneDArgs),
+    /// UPC-A
+    #[command(name = "upca")]
+    UpcA(OneDArgs),
+    /// UPC-E
+    #[command(name = "upce")]
+    UpcE(OneDArgs),
+    /// Swiss QR-bill: a QR code with the SPC payload, error correction M and the Swiss cross
+    #[command(name = "swiss-qr-bill")]
+    SwissQrBill(SwissQrBillArgs),
+    /// EPC QR code (GiroCode): a SEPA credit transfer with error correction M
+    #[command(name = "epc")]
+    Epc(EpcArgs),
+    /// Bitcoin payment request: a QR code with a BIP 21 bitcoin: URI
+    #[command(name = "bitcoin")]
+    Bitcoin(BitcoinArgs),
+    /// Lightning payment: a QR code with a lightning: URI of an invoice or LNURL
+    #[command(name = "lightning")]
+    Lightning(LightningArgs),
+    /// UPI payment: a QR code with a upi://pay URI
+    #[command(name = "upi")]
+    Upi(UpiArgs),
+    /// Two factor authentication key: a QR code with an otpauth:// URI
+    #[command(name = "otpauth")]
+    OtpAuth(OtpAuthArgs),
+}
+
+impl EncodeFormat {
+    pub fn barcode_format(&self) -> BarcodeFormat {
+        match self {
+            EncodeFormat::QrCode(_)
+            | EncodeFormat::SwissQrBill(_)
+            | EncodeFormat::Epc(_)
+            | EncodeFormat::Bitcoin(_)
+            | EncodeFormat::Lightning(_)
+            | EncodeFormat::Upi(_)
+            | EncodeFormat::OtpAuth(_) => BarcodeFormat::QR_CODE,
+            EncodeFormat::Pdf417(_) => BarcodeFormat::PDF_417,
+            EncodeFormat::Aztec(_) => BarcodeFormat::AZTEC,
+            EncodeFormat::DataMatrix(_) => BarcodeFormat::DATA_MATRIX,
+            EncodeFormat::Code128(_) => BarcodeFormat::CODE_128,
+            EncodeFormat::Codabar(_) => BarcodeFormat::CODABAR,
+            EncodeFormat::Code39(_) => BarcodeFormat::CODE_39,
+            EncodeFormat::Code93(_) => BarcodeFormat::CODE_93,
+            EncodeFormat::Ean8(_) => BarcodeFormat::EAN_8,
+            EncodeFormat::Ean13(_) => BarcodeFormat::EAN_13,
+            EncodeFormat::Itf(_) => BarcodeFormat::ITF,
+            EncodeFormat::UpcA(_) => BarcodeFormat::UPC_A,
+            EncodeFormat::UpcE(_) => BarcodeFormat::UPC_E,
+        }
+    }
+
+    pub fn common(&self) -> &CommonEncodeArgs {
+        match self {
+            EncodeFormat::QrCode(args) => &args.common,
+            EncodeFormat::Pdf417(args) => &args.common,
+            EncodeFormat::Aztec(args) => &args.common,
+            EncodeFormat::DataMatrix(args) => &args.common,
+            EncodeFormat::Code128(args) => &args.common,
+            EncodeFormat::SwissQrBill(args) => &args.common,
+            EncodeFormat::Epc(args) => &args.common,
+            EncodeFormat::Bitcoin(args) => &args.common,
+            EncodeFormat::Lightning(args) => &args.common,
+            EncodeFormat::Upi(args) => &args.common,
+            EncodeFormat::OtpAuth(args) => &args.common,
+            EncodeFormat::Codabar(args)
+            | EncodeFormat::Code39(args)
+            | EncodeFormat::Code93(args)
+            | EncodeFormat::Ean8(args)
+            | EncodeFormat::Ean13(args)
+            | EncodeFormat::Itf(args)
+            | EncodeFormat::UpcA(args)
+            | EncodeFormat::UpcE(args) => &args.common,
+        }
+    }
+
+    /// The GS1 input flags, for the formats that have them and only when --gs1 is given.
+    pub fn gs1_input(&self) -> Option<&Gs1Args> {
+        match self {
+            EncodeFormat::QrCode(QrCodeArgs { gs1_input, .. })
+            | EncodeFormat::DataMatrix(DataMatrixArgs { gs1_input, .. })
+            | EncodeFormat::Code128(Code128Args { gs1_input, .. }) => Some(gs1_input),
+            _ => None,
+        }
+        .filter(|args| args.gs1)
+    }
+
+    /// The Digital Link flags, only when --digital-link is given.
+    pub fn digital_link(&self) -> Option<&DigitalLinkArgs> {
+        match self {
+            EncodeFormat::QrCode(args) => Some(&args.digital_link),
+            _ => None,
+        }
+        .filter(|args| args.digital_link)
+    }
+
+    /// The symbol count and layout, only when --structured-append is given.
+    pub fn structured_append(&self) -> Option<(u8, structured_append::Layout)> {
+        match self {
+            EncodeFormat::QrCode(QrCodeArgs {
+                structured_append: Some(count),
+                structured_append_layout,
+                ..
+            }) => Some((*count, *structured_append_layout)),
+            _ => None,
+        }
+    }
+
+    pub fn raw_options(&self) -> RawEncodeOptions<'_> {
+        match self {
+            EncodeFormat::QrCode(args) => RawEncodeOptions {
+                error_correction: args.error_correction.as_deref(),
+                // readers guess the character set of text without an ECI, often wrongly
+                character_set: args
+                    .character_set
+                    .as_deref()
+                    .or(args.content.is_used().then_some("UTF-8")),
+                qr_version: args.qr_version.as_deref(),
+                qr_mask_pattern: args.qr_mask_pattern.as_deref(),
+                qr_compact: args.qr_compact,
+                // a Digital Link is a plain URI, not GS1 data
+                gs1_format: args
+                    .gs1_format
+                    .or((args.gs1_input.gs1 && !args.digital_link.digital_link).then_some(true)),
+                ..Default::default()
+            },
+            EncodeFormat::Pdf417(args) => RawEncodeOptions {
+                error_correction: args.error_correction.as_deref(),
+                character_set: args.character_set.as_deref(),
+                pdf_417_compact: args.pdf_417_compact,
+                pdf_417_compaction: args.pdf_417_compaction.as_deref(),
+                pdf_417_auto_eci: args.pdf_417_auto_eci,
+                ..Default::default()
+            },
+            EncodeFormat::Aztec(args) => RawEncodeOptions {
+                error_correction: args.error_correction.as_deref(),
+                character_set: args.character_set.as_deref(),
+                aztec_layers: args.aztec_layers,
+                ..Default::default()
+            },
+            EncodeFormat::DataMatrix(args) => RawEncodeOptions {
+                character_set: args.character_set.as_deref(),
+                data_matrix_compact: args.data_matrix_compact,
+                force_c40: args.force_c40,
+                gs1_format: args.gs1_format,
+                ..Default::default()
+            },
+            EncodeFormat::Code128(args) => RawEncodeOptions {
+                force_code_set: args.force_code_set.as_deref(),
+                code_128_compact: args.code_128_compact,
+                ..Default::default()
+            },
+            // the payloads are UTF-8, and the standards ask for level M
+            EncodeFormat::SwissQrBill(_) | EncodeFormat::Epc(_) => RawEncodeOptions {
+                error_correction: Some("M"),
+                character_set: Some("UTF-8"),
+                ..Default::default()
+            },
+            // the URIs are ASCII, so the defaults suit them
+            EncodeFormat::Bitcoin(_)
+            | EncodeFormat::Lightning(_)
+            | EncodeFormat::Upi(_)
+            | EncodeFormat::OtpAuth(_) => RawEncodeOptions::default(),
+            EncodeFormat::Codabar(_)
+            | EncodeFormat::Code39(_)
+            | EncodeFormat::Code93(_)
+            | EncodeFormat::Ean8(_)
+            | EncodeFormat::Ean13(_)
+            | EncodeFormat::Itf(_)
+            | EncodeFormat::UpcA(_)
+            | EncodeFormat::UpcE(_) => RawEncodeOptions::default(),
+        }
+    }
+}
+
+/// Name of the `encode` subcommand for a barcode format, if it can be encoded.
+fn subcommand_name(format: BarcodeFormat) -> Option<&'static str> {
+    match format {
+        BarcodeFormat::QR_CODE => Some("qrcode"),
+        BarcodeFormat::PDF_417 => Some("pdf417"),
+        BarcodeFormat::AZTEC => Some("aztec"),
+        BarcodeFormat::DATA_MATRIX => Some("datamatrix"),
+        BarcodeFormat::CODE_128 => Some("code128"),
+        BarcodeFormat::CODABAR => Some("codabar"),
+        BarcodeFormat::CODE_39 => Some("code39"),
+        BarcodeFormat::CODE_93 => Some("code93"),
+        BarcodeFormat::EAN_8 => Some("ean8"),
+        BarcodeFormat::EAN_13 => Some("ean13"),
+        BarcodeFormat::ITF => Some("itf"),
+        BarcodeFormat::UPC_A => Some("upca"),
+        BarcodeFormat::UPC_E => Some("upce"),
+        _ => None,
+    }
+}
+
+/// The format named by the positional argument of the single `encode` command: the names
+/// `BarcodeFormat` prints, e.g. CODE_128, and the other spellings the command accepted.
+fn legacy_format(name: &str) -> Option<BarcodeFormat> {
+    let name = name.to_ascii_lowercase().replace([' ', '-'], "_");
+    Some(match name.as_str() {
+        "qr_code" | "qrcode" => BarcodeFormat::QR_CODE,
+        "pdf_417" | "pdf417" | "iso_15438" => BarcodeFormat::PDF_417,
+        "aztec" | "aztec_code" => BarcodeFormat::AZTEC,
+        "data_matrix" | "datamatrix" => BarcodeFormat::DATA_MATRIX,
+        "code_128" | "code128" | "code_129" => BarcodeFormat::CODE_128,
+        "codabar" | "coda" | "coda_bar" | "cod_a_bar" | "cod_a" => BarcodeFormat::CODABAR,
+        "code_39" | "code39" | "alpha39" | "code_3_of_9" | "uss_39" | "usd_3" => {
+            BarcodeFormat::CODE_39
+        }
+        "code_93" | "code93" => BarcodeFormat::CODE_93,
+        "ean_8" | "ean8" => BarcodeFormat::EAN_8,
+        "ean_13" | "ean13" => BarcodeFormat::EAN_13,
+        "itf" | "itf_code" | "itf14" | "itf_14" | "interleaved_2_of_5" => BarcodeFormat::ITF,
+        "upc_a" | "upca" => BarcodeFormat::UPC_A,
+        "upc_e" | "upce" => BarcodeFormat::UPC_E,
+        _ => return None,
+    })
+}
+
+/// Compatibility shim for the single `encode` command that took the barcode format as a
+/// positional argument after any of its flags, e.g. `encode --width 5 --qr-version 3 QR_CODE`.
+///
+/// The format is moved in front of the flags, so that it selects the matching subcommand, and
+/// is translated from the names the old command accepted. Flags of other formats, which the
+/// old command accepted and ignored, are dropped with a warning. --margin, which was in
+/// pixels, becomes the hidden --margin-pixels rather than a quiet zone in modules.
+pub fn rewrite_legacy_args(command: &Command, args: Vec<OsString>) -> Vec<OsString> {
+    let Some(encode_command) = command.find_subcommand("encode") else {
+        return args;
+    };
+    // the file names come first, so `encode` can not be the first argument
+    let Some(encode_index) = args
+        .iter()
+        .skip(2)
+        .position(|a| a == "encode")
+        .map(|i| i + 2)
+    else {
+        return args;
+    };
+
+    let takes_value = |flag: &str| {
+        encode_command.get_subcommands().any(|sub| {
+            sub.get_arguments().any(|arg| {
+                (flag
+                    .strip_prefix("--")
+                    .is_some_and(|long| has_long(arg, long))
+                    || arg.get_short().map(|s| format!("-{s}")).as_deref() == Some(flag))
+                    && arg.get_action().takes_values()
+            })
+        })
+    };
+
+    // --margin was in pixels, it becomes --margin-pixels rather than a quiet zone in modules
+    let mut args = args;
+    let mut index = encode_index + 1;
+    while index < args.len() {
+        let text = args[index].to_string_lossy().into_owned();
+        if text == "--" {
+            break;
+        }
+        let margin = text
+            .strip_prefix("--margin")
+            .filter(|value| value.is_empty() || value.starts_with('='));
+        if let Some(value) = margin {
+            args[index] = format!("--margin-pixels{value}").into();
+        }
+        index += if text == "--margin" || (!text.contains('=') && takes_value(&text)) {
+            2
+        } else {
+            1
+        };
+    }
+
+    // locate the format, the first argument that is neither a flag nor a flag's value
+    let mut format_index = None;
+    let mut index = encode_index + 1;
+    while index < args.len() {
+        let arg = args[index].to_string_lossy();
+        if arg == "--" {
+            break;
+        }
+        if arg.starts_with('-') && arg.len() > 1 {
+            index += if !arg.contains('=') && takes_value(&arg) {
+                2
+            } else {
+                1
+            };
+            continue;
+        }
+        format_index = Some(index);
+        break;
+    }
+    let Some(format_index) = format_index else {
+        return args;
+    };
+    // already in the new form, let clap reject flags of other formats
+    if format_index == encode_index + 1
+        && encode_command
+            .find_subcommand(args[format_index].to_string_lossy().as_ref())
+            .is_some()
+    {
+        return args;
+    }
+
+    let mut format = args.remove(format_index);
+    let legacy_format = legacy_format(&format.to_string_lossy());
+    if let Some(name) = legacy_format.and_then(subcommand_name) {
+        format = name.into();
+    }
+    args.insert(encode_index + 1, format.clone());
+
+    let Some(format_command) = encode_command.find_subcommand(format.to_string_lossy().as_ref())
+    else {
+        return args;
+    };
+    let is_known_elsewhere = |long: &str| {
+        encode_command
+            .get_subcommands()
+            .any(|sub| sub.get_arguments().any(|arg| has_long(arg, long)))
+    };
+
+    let mut rewritten = args[..encode_index + 2].to_vec();
+    let mut rest = args[encode_index + 2..].iter();
+    while let Some(arg) = rest.next() {
+        let text = arg.to_string_lossy();
+        if text == "--" {
+            rewritten.push(arg.clone());
+            rewritten.extend(rest.cloned());
+            break;
+        }
+        let long = text
+            .strip_prefix("--")
+            .map(|flag| flag.split('=').next().unwrap_or(flag));
+        match long {
+            Some(long)
+                if format_command
+                    .get_arguments()
+                    .all(|arg| !has_long(arg, long))
+                    && is_known_elsewhere(long) =>
+            {
+                eprintln!(
+                    "Warning, --{long} does not apply to {} and is ignored",
+                    legacy_format.map_or(format_command.get_name().to_owned(), |format| {
+                        format_name(&format)
+                    })
+                );
+                if !text.contains('=') && takes_value(&text) {
+                    rest.next();
+                }
+            }
+            _ => rewritten.push(arg.clone()),
+        }
+    }
+    rewritten
+}
+
+fn has_long(arg: &Arg, long: &str) -> bool {
+    arg.get_long_and_visible_aliases()
+        .is_some_and(|longs| longs.contains(&long))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser};
+
+    use super::*;
+    use crate::Args;
+
+    fn rewrite(args: &[&str]) -> Vec<String> {
+        let args = ["rxing-cli", "out.png"]
+            .iter()
+            .chain(args)
+            .map(OsString::from);
+        rewrite_legacy_args(&Args::command(), args.collect())
+            .into_iter()
+            .skip(2)
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    fn parses(args: &[String]) -> bool {
+        let program = ["rxing-cli", "out.png"].into_iter().map(str::to_owned);
+        Args::try_parse_from(program.chain(args.iter().cloned())).is_ok()
+    }
+
+    #[test]
+    fn legacy_format_names() {
+        for (legacy, subcommand) in [
+            ("QR_CODE", "qrcode"),
+            ("PDF_417", "pdf417"),
+            ("AZTEC", "aztec"),
+            ("DATA_MATRIX", "datamatrix"),
+            ("CODE_128", "code128"),
+            ("CODABAR", "codabar"),
+            ("CODE_39", "code39"),
+            ("CODE_93", "code93"),
+            ("EAN_8", "ean8"),
+            ("EAN_13", "ean13"),
+            ("ITF", "itf"),
+            ("UPC_A", "upca"),
+            ("UPC_E", "upce"),
+            ("code 128", "code128"),
+            ("Interleaved 2 of 5", "itf"),
+            ("qr-code", "qrcode"),
+        ] {
+            let args = rewrite(&["encode", legacy, "--data", "1"]);
+            assert_eq!(args, ["encode", subcommand, "--data", "1"], "{legacy}");
+            assert!(parses(&args), "{legacy}");
+        }
+        assert_eq!(legacy_format("MAXICODE"), None);
+    }
+
+    #[test]
+    fn format_after_the_flags() {
+        let args = rewrite(&[
+            "encode",
+            "--width",
+            "300",
+            "--qr-version",
+            "3",
+            "QR_CODE",
+            "--data",
+            "x",
+        ]);
+        assert_eq!(
+            args,
+            [
+                "encode",
+                "qrcode",
+                "--width",
+                "300",
+                "--qr-version",
+                "3",
+                "--data",
+                "x"
+            ]
+        );
+        assert!(parses(&args));
+    }
+
+    #[test]
+    fn flags_of_other_formats_are_dropped() {
+        let args = rewrite(&[
+            "encode",
+            "QR_CODE",
+            "--pdf-417-compact",
+            "true",
+            "--force-c40=true",
+            "--data",
+            "x",
+        ]);
+        assert_eq!(args, ["encode", "qrcode", "--data", "x"]);
+    }
+
+    #[test]
+    fn margin_stays_in_pixels() {
+        let args = rewrite(&["encode", "CODE_128", "--margin", "10", "--data", "1"]);
+        assert_eq!(
+            args,
+            ["encode", "code128", "--margin-pixels", "10", "--data", "1"]
+        );
+        assert!(parses(&args));
+        let args = rewrite(&["encode", "qrcode", "--margin=3", "--data", "--margin"]);
+        assert_eq!(
+            args,
+            [
+                "encode",
+                "qrcode",
+                "--margin-pixels=3",
+                "--data",
+                "--margin"
+            ]
+        );
+        let args = rewrite(&["encode", "qrcode", "--data", "x", "--", "--margin"]);
+        assert_eq!(args, ["encode", "qrcode", "--data", "x", "--", "--margin"]);
+    }
+
+    #[test]
+    fn new_form_is_left_alone() {
+        let args = ["encode", "qrcode", "--qr-version", "3", "--data", "x"];
+        assert_eq!(rewrite(&args), args);
+        let args = ["decode", "--try-harder"];
+        assert_eq!(rewrite(&args), args);
+    }
+}
@@ -0,0 +1,589 @@
+use std::collections::HashMap;
+
+use rxing::{BarcodeFormat, EncodeHintType, EncodeHintValue, EncodingHintDictionary};
+
+use crate::error::CliError;
+
+/// Encoder options exactly as they were given on the command line.
+#[derive(Default)]
+pub struct RawEncodeOptions<'a> {
+    pub error_correction: Option<&'a str>,
+    pub character_set: Option<&'a str>,
+    pub data_matrix_compact: Option<bool>,
+    pub pdf_417_compact: Option<bool>,
+    pub pdf_417_compaction: Option<&'a str>,
+    pub pdf_417_auto_eci: Option<bool>,
+    pub aztec_layers: Option<i32>,
+    pub qr_version: Option<&'a str>,
+    pub qr_mask_pattern: Option<&'a str>,
+    pub qr_compact: Option<bool>,
+    pub gs1_format: Option<bool>,
+    pub force_code_set: Option<&'a str>,
+    pub force_c40: Option<bool>,
+    pub code_128_compact: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QrErrorCorrection {
+    L,
+    M,
+    Q,
+    H,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pdf417Compaction {
+    Auto = 0,
+    Text = 1,
+    Byte = 2,
+    Numeric = 3,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Code128CodeSet {
+    A,
+    B,
+    C,
+}
+
+/// Options understood by the writer of one family of barcode formats.
+#[derive(Debug)]
+pub enum SymbologyOptions {
+    QrCode {
+        error_correction: Option<QrErrorCorrection>,
+        version: Option<u32>,
+        mask_pattern: Option<u32>,
+        compact: Option<bool>,
+        gs1_format: Option<bool>,
+    },
+    Pdf417 {
+        error_correction: Option<u32>,
+        compact: Option<bool>,
+        compaction: Option<Pdf417Compaction>,
+        auto_eci: Option<bool>,
+    },
+    Aztec {
+        error_correction_percent: Option<u32>,
+        layers: Option<i32>,
+    },
+    DataMatrix {
+        compact: Option<bool>,
+        force_c40: Option<bool>,
+        gs1_format: Option<bool>,
+    },
+    Code128 {
+        force_code_set: Option<Code128CodeSet>,
+        compact: Option<bool>,
+    },
+    /// Every other one dimensional format, which have no options of their own.
+    OneD,
+}
+
+/// Encoder options validated against the barcode format they will be used with.
+#[derive(Debug)]
+pub struct EncodeOptions {
+    pub character_set: Option<String>,
+    pub symbology: SymbologyOptions,
+}
+
+/// Short, human readable name for a format in messages.
+pub fn format_name(format: &BarcodeFormat) -> String {
+    format.to_string().to_uppercase()
+}
+
+impl EncodeOptions {
+    /// Validate `raw` for `format`. Invalid values are an error explaining the valid range,
+    /// valid but risky combinations are reported in the returned warnings.
+    pub fn parse(
+        format: &BarcodeFormat,
+        raw: &RawEncodeOptions,
+    ) -> Result<(Self, Vec<String>), CliError> {
+        let name = format_name(format);
+        let mut warnings = Vec::new();
+
+        let symbology = match format {
+            BarcodeFormat::QR_CODE => SymbologyOptions::QrCode {
+                error_correction: raw
+                    .error_correction
+                    .map(parse_qr_error_correction)
+                    .transpose()?,
+                version: raw
+                    .qr_version
+                    .map(|v| parse_in_range("qr-version", &name, v, 1, 40))
+                    .transpose()?,
+                mask_pattern: raw
+                    .qr_mask_pattern
+                    .map(|v| parse_in_range("qr-mask-pattern", &name, v, 0, 7))
+                    .transpose()?,
+                compact: raw.qr_compact,
+                gs1_format: raw.gs1_format,
+            },
+            BarcodeFormat::PDF_417 => SymbologyOptions::Pdf417 {
+                error_correction: raw
+                    .error_correction
+                    .map(|v| parse_in_range("error-correction", &name, v, 0, 8))
+                    .transpose()?,
+                compact: raw.pdf_417_compact,
+                compaction: raw
+                    .pdf_417_compaction
+                    .map(parse_pdf_417_compaction)
+                    .transpose()?,
+                auto_eci: raw.pdf_417_auto_eci,
+            },
+            BarcodeFormat::AZTEC => SymbologyOptions::Aztec {
+                error_correction_percent: raw
+                    .error_correction
+                    .map(|v| parse_in_range("error-correction", &name, v, 0, 100))
+                    .transpose()?,
+                layers: raw.aztec_layers.map(parse_aztec_layers).transpose()?,
+            },
+            BarcodeFormat::DATA_MATRIX => SymbologyOptions::DataMatrix {
+                compact: raw.data_matrix_compact,
+                force_c40: raw.force_c40,
+                gs1_format: raw.gs1_format,
+            },
+            BarcodeFormat::CODE_128 => SymbologyOptions::Code128 {
+                force_code_set: raw.force_code_set.map(parse_code_set).transpose()?,
+                compact: raw.code_128_compact,
+            },
+            _ => SymbologyOptions::OneD,
+        };
+
+        if let SymbologyOptions::QrCode {
+            compact: Some(true),
+            ..
+        } = symbology
+        {
+            warnings.push("Warning, QRCompact can generate unreadable barcodes".to_owned());
+        }
+        if let SymbologyOptions::DataMatrix {
+            compact,
+            gs1_format: Some(true),
+            ..
+        } = symbology
+        {
+            if compact != Some(true) {
+                warnings.push(
+                    "Warning, --gs1-format is only used by DATAMATRIX together with --data-matrix-compact"
+                        .to_owned(),
+                );
+            }
+        }
+        if let SymbologyOptions::Aztec {
+            error_correction_percent: Some(percent),
+            ..
+        } = symbology
+        {
+            if percent < 25 {
+                warnings.push(format!(
+                    "Warning, an AZTEC symbol should have a minimum of 25% error correction words, got {percent}%"
+                ));
+            }
+        }
+
+        Ok((
+            Self {
+                character_set: raw.character_set.map(str::to_owned),
+                symbology,
+            },
+            warnings,
+        ))
+    }
+
+    /// The hints to hand to `MultiFormatWriter`.
+    pub fn hints(&self) -> EncodingHintDictionary {
+        let mut hints: EncodingHintDictionary = HashMap::new();
+
+        if let Some(character_set) = &self.character_set {
+            hints.insert(
+                EncodeHintType::CHARACTER_SET,
+                EncodeHintValue::CharacterSet(character_set.to_owned()),
+            );
+        }
+
+        // the quiet zone is added when the symbol is scaled, see `sizing::layout`
+        hints.insert(
+            EncodeHintType::MARGIN,
+            EncodeHintValue::Margin("0".to_owned()),
+        );
+
+        match &self.symbology {
+            SymbologyOptions::QrCode {
+                error_correction,
+                version,
+                mask_pattern,
+                compact,
+                gs1_format,
+            } => {
+                if let Some(ec) = error_correction {
+                    hints.insert(
+                        EncodeHintType::ERROR_CORRECTION,
+                        EncodeHintValue::ErrorCorrection(format!("{ec:?}")),
+                    );
+                }
+                if let Some(version) = version {
+                    hints.insert(
+                        EncodeHintType::QR_VERSION,
+                        EncodeHintValue::QrVersion(version.to_string()),
+                    );
+                }
+                if let Some(mask_pattern) = mask_pattern {
+                    hints.insert(
+                        EncodeHintType::QR_MASK_PATTERN,
+                        EncodeHintValue::QrMaskPattern(mask_pattern.to_string()),
+                    );
+                }
+                if let Some(compact) = compact {
+                    hints.insert(
+                        EncodeHintType::QR_COMPACT,
+                        EncodeHintValue::QrCompact(compact.to_string()),
+                    );
+                }
+                if let Some(gs1_format) = gs1_format {
+                    hints.insert(
+                        EncodeHintType::GS1_FORMAT,
+                        EncodeHintValue::Gs1Format(*gs1_format),
+                    );
+                }
+            }
+            SymbologyOptions::Pdf417 {
+                error_correction,
+                compact,
+                compaction,
+                auto_eci,
+            } => {
+                if let Some(ec) = error_correction {
+                    hints.insert(
+                        EncodeHintType::ERROR_CORRECTION,
+                        EncodeHintValue::ErrorCorrection(ec.to_string()),
+                    );
+                }
+                if let Some(compact) = compact {
+                    hints.insert(
+                        EncodeHintType::PDF417_COMPACT,
+                        EncodeHintValue::Pdf417Compact(compact.to_string()),
+                    );
+                }
+                if let Some(compaction) = compaction {
+                    hints.insert(
+                        EncodeHintType::PDF417_COMPACTION,
+                        EncodeHintValue::Pdf417Compaction((*compaction as u8).to_string()),
+                    );
+                }
+                if let Some(auto_eci) = auto_eci {
+                    hints.insert(
+                        EncodeHintType::PDF417_AUTO_ECI,
+                        EncodeHintValue::Pdf417AutoEci(auto_eci.to_string()),
+                    );
+                }
+            }
+            SymbologyOptions::Aztec {
+                error_correction_percent,
+                layers,
+            } => {
+                if let Some(percent) = error_correction_percent {
+                    hints.insert(
+                        EncodeHintType::ERROR_CORRECTION,
+                        EncodeHintValue::ErrorCorrection(percent.to_string()),
+                    );
+                }
+                if let Some(layers) = layers {
+                    hints.insert(
+                        EncodeHintType::AZTEC_LAYERS,
+                        EncodeHintValue::AztecLayers(*layers),
+                    );
+                }
+            }
+            SymbologyOptions::DataMatrix {
+                compact,
+                force_c40,
+                gs1_format,
+            } => {
+                if let Some(compact) = compact {
+                    hints.insert(
+                        EncodeHintType::DATA_MATRIX_COMPACT,
+                        EncodeHintValue::DataMatrixCompact(*compact),
+                    );
+                }
+                if let Some(force_c40) = force_c40 {
+                    hints.insert(
+                        EncodeHintType::FORCE_C40,
+                        EncodeHintValue::ForceC40(*force_c40),
+                    );
+                }
+                if let Some(gs1_format) = gs1_format {
+                    hints.insert(
+                        EncodeHintType::GS1_FORMAT,
+                        EncodeHintValue::Gs1Format(*gs1_format),
+                    );
+                }
+            }
+            SymbologyOptions::Code128 {
+                force_code_set,
+                compact,
+            } => {
+                if let Some(code_set) = force_code_set {
+                    hints.insert(
+                        EncodeHintType::FORCE_CODE_SET,
+                        EncodeHintValue::ForceCodeSet(format!("{code_set:?}")),
+                    );
+                }
+                if let Some(compact) = compact {
+                    hints.insert(
+                        EncodeHintType::CODE128_COMPACT,
+                        EncodeHintValue::Code128Compact(*compact),
+                    );
+                }
+            }
+            SymbologyOptions::OneD => {}
+        }
+
+        hints
+    }
+}
+
+fn parse_in_range(
+    flag: &str,
+    name: &str,
+    value: &str,
+    min: u32,
+    max: u32,
+) -> Result<u32, CliError> {
+    match value.trim().parse::<u32>() {
+        Ok(parsed) if (min..=max).contains(&parsed) => Ok(parsed),
+        _ => Err(CliError::InvalidHint(format!(
+            "--{flag} '{value}' is not valid for {name}, expected a whole number from {min} to {max}"
+        ))),
+    }
+}
+
+fn parse_qr_error_correction(value: &str) -> Result<QrErrorCorrection, CliError> {
+    match value.trim().to_uppercase().as_str() {
+        "L" => Ok(QrErrorCorrection::L),
+        "M" => Ok(QrErrorCorrection::M),
+        "Q" => Ok(QrErrorCorrection::Q),
+        "H" => Ok(QrErrorCorrection::H),
+        _ => Err(CliError::InvalidHint(format!(
+            "--error-correction '{value}' is not valid for QRCODE, expected one of L (~7%), M (~15%), Q (~25%) or H (~30%)"
+        ))),
+    }
+}
+
+fn parse_pdf_417_compaction(value: &str) -> Result<Pdf417Compaction, CliError> {
+    match value.trim().to_uppercase().as_str() {
+        "0" | "AUTO" => Ok(Pdf417Compaction::Auto),
+        "1" | "TEXT" => Ok(Pdf417Compaction::Text),
+        "2" | "BYTE" => Ok(Pdf417Compaction::Byte),
+        "3" | "NUMERIC" => Ok(Pdf417Compaction::Numeric),
+        _ => Err(CliError::InvalidHint(format!(
+            "--pdf-417-compaction '{value}' is not valid for PDF 417, expected AUTO (0), TEXT (1), BYTE (2) or NUMERIC (3)"
+        ))),
+    }
+}
+
+fn parse_aztec_layers(layers: i32) -> Result<i32, CliError> {
+    if (-4..=32).contains(&layers) {
+        Ok(layers)
+    } else {
+        Err(CliError::InvalidHint(format!(
+            "--aztec-layers '{layers}' is not valid for AZTEC, expected -4 to -1 for a compact symbol, 0 for the minimum number of layers or 1 to 32 for a full size symbol"
+        )))
+    }
+}
+
+fn parse_code_set(value: &str) -> Result<Code128CodeSet, CliError> {
+    match value.trim().to_uppercase().as_str() {
+        "A" => Ok(Code128CodeSet::A),
+        "B" => Ok(Code128CodeSet::B),
+        "C" => Ok(Code128CodeSet::C),
+        _ => Err(CliError::InvalidHint(format!(
+            "--force-code-set '{value}' is not valid for CODE 128, expected one of A, B or C"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(
+        format: BarcodeFormat,
+        raw: RawEncodeOptions,
+    ) -> Result<(EncodeOptions, Vec<String>), CliError> {
+        EncodeOptions::parse(&format, &raw)
+    }
+
+    fn invalid(result: Result<(EncodeOptions, Vec<String>), CliError>) -> String {
+        match result {
+            Err(CliError::InvalidHint(message)) => message,
+            other => panic!("expected an invalid hint, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn qr_code_options() {
+        let raw = RawEncodeOptions {
+            error_correction: Some("q"),
+            qr_version: Some(" 40 "),
+            qr_mask_pattern: Some("0"),
+            ..Default::default()
+        };
+        let (options, warnings) = parse(BarcodeFormat::QR_CODE, raw).unwrap();
+        assert!(matches!(
+            options.symbology,
+            SymbologyOptions::QrCode {
+                error_correction: Some(QrErrorCorrection::Q),
+                version: Some(40),
+                mask_pattern: Some(0),
+                ..
+            }
+        ));
+        assert!(warnings.is_empty());
+        let hints = options.hints();
+        assert!(matches!(
+            &hints[&EncodeHintType::ERROR_CORRECTION],
+            EncodeHintValue::ErrorCorrection(ec) if ec == "Q"
+        ));
+        assert!(matches!(
+            &hints[&EncodeHintType::MARGIN],
+            EncodeHintValue::Margin(margin) if margin == "0"
+        ));
+
+        let message = invalid(parse(
+            BarcodeFormat::QR_CODE,
+            RawEncodeOptions {
+                error_correction: Some("X"),
+                ..Default::default()
+            },
+        ));
+        assert!(message.contains("L (~7%)"), "{message}");
+        let message = invalid(parse(
+            BarcodeFormat::QR_CODE,
+            RawEncodeOptions {
+                qr_version: Some("41"),
+                ..Default::default()
+            },
+        ));
+        assert_eq!(
+            message,
+            "--qr-version '41' is not valid for QRCODE, expected a whole number from 1 to 40"
+        );
+    }
+
+    #[test]
+    fn error_correction_depends_on_the_format() {
+        let raw = || RawEncodeOptions {
+            error_correction: Some("8"),
+            ..Default::default()
+        };
+        assert!(parse(BarcodeFormat::PDF_417, raw()).is_ok());
+        assert!(parse(BarcodeFormat::AZTEC, raw()).is_ok());
+        invalid(parse(BarcodeFormat::QR_CODE, raw()));
+        let high = RawEncodeOptions {
+            error_correction: Some("9"),
+            ..Default::default()
+        };
+        invalid(parse(BarcodeFormat::PDF_417, high));
+    }
+
+    #[test]
+    fn names_and_numbers() {
+        for (value, compaction) in [
+            ("auto", Pdf417Compaction::Auto),
+            ("1", Pdf417Compaction::Text),
+            ("BYTE", Pdf417Compaction::Byte),
+            ("3", Pdf417Compaction::Numeric),
+        ] {
+            let raw = RawEncodeOptions {
+                pdf_417_compaction: Some(value),
+                ..Default::default()
+            };
+            let (options, _) = parse(BarcodeFormat::PDF_417, raw).unwrap();
+            assert!(
+                matches!(options.symbology, SymbologyOptions::Pdf417 { compaction: Some(c), .. } if c == compaction),
+                "{value}"
+            );
+        }
+        let raw = RawEncodeOptions {
+            force_code_set: Some("c"),
+            ..Default::default()
+        };
+        let (options, _) = parse(BarcodeFormat::CODE_128, raw).unwrap();
+        assert!(matches!(
+            &options.hints()[&EncodeHintType::FORCE_CODE_SET],
+            EncodeHintValue::ForceCodeSet(code_set) if code_set == "C"
+        ));
+        let raw = RawEncodeOptions {
+            force_code_set: Some("D"),
+            ..Default::default()
+        };
+        invalid(parse(BarcodeFormat::CODE_128, raw));
+    }
+
+    #[test]
+    fn aztec_layers() {
+        for layers in [-4, 0, 32] {
+            let raw = RawEncodeOptions {
+                aztec_layers: Some(layers),
+                ..Default::default()
+            };
+            assert!(parse(BarcodeFormat::AZTEC, raw).is_ok(), "{layers}");
+        }
+        for layers in [-5, 33] {
+            let raw = RawEncodeOptions {
+                aztec_layers: Some(layers),
+                ..Default::default()
+            };
+            invalid(parse(BarcodeFormat::AZTEC, raw));
+        }
+    }
+
+    #[test]
+    fn warnings() {
+        let raw = RawEncodeOptions {
+            qr_compact: Some(true),
+            ..Default::default()
+        };
+        let (_, warnings) = parse(BarcodeFormat::QR_CODE, raw).unwrap();
+        assert_eq!(
+            warnings,
+            ["Warning, QRCompact can generate unreadable barcodes"]
+        );
+
+        let raw = RawEncodeOptions {
+            error_correction: Some("10"),
+            ..Default::default()
+        };
+        let (_, warnings) = parse(BarcodeFormat::AZTEC, raw).unwrap();
+        assert_eq!(warnings.len(), 1);
+
+        let gs1 = |compact| RawEncodeOptions {
+            gs1_format: Some(true),
+            data_matrix_compact: compact,
+            ..Default::default()
+        };
+        let (_, warnings) = parse(BarcodeFormat::DATA_MATRIX, gs1(None)).unwrap();
+        assert_eq!(warnings.len(), 1);
+        let (_, warnings) = parse(BarcodeFormat::DATA_MATRIX, gs1(Some(true))).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn one_dimensional_formats_ignore_the_options() {
+        let raw = RawEncodeOptions {
+            error_correction: Some("anything"),
+            qr_version: Some("99"),
+            character_set: Some("ISO-8859-1"),
+            ..Default::default()
+        };
+        let (options, warnings) = parse(BarcodeFormat::EAN_13, raw).unwrap();
+        assert!(matches!(options.symbology, SymbologyOptions::OneD));
+        assert!(warnings.is_empty());
+        let hints = options.hints();
+        assert_eq!(hints.len(), 2);
+        assert!(matches!(
+            &hints[&EncodeHintType::CHARACTER_SET],
+            EncodeHintValue::CharacterSet(character_set) if character_set == "ISO-8859-1"
+        ));
+    }
+}
@@ -0,0 +1,426 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{checksum, error::CliError};
+
+pub const SERVICE_TAG: &str = "BCD";
+/// SEPA credit transfer, the only function defined.
+const IDENTIFICATION: &str = "SCT";
+/// Character set 1, UTF-8, which is what the builder writes.
+const UTF_8: &str = "1";
+const MAX_PAYLOAD_BYTES: usize = 331;
+
+/// The lines from the service tag to the beneficiary to originator information.
+const MAX_LINES: usize = 12;
+/// The lines up to the IBAN, the last mandatory field.
+const MIN_LINES: usize = 7;
+
+/// A SEPA credit transfer of an EPC069-12 QR code, also known as GiroCode.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Transfer {
+    /// 001, where the BIC is mandatory, or 002.
+    pub version: String,
+    /// 1 for UTF-8 to 8 for ISO 8859-15.
+    pub character_set: String,
+    pub identification: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub bic: String,
+    /// The beneficiary's name.
+    pub name: String,
+    pub iban: String,
+    /// The amount in euro, without the EUR prefix.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    /// An ISO 20022 purpose code, e.g. CHAR or GDDS.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub purpose: String,
+    /// Structured remittance information, usually an ISO 11649 creditor reference.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub reference: String,
+    /// Unstructured remittance information.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub remittance_text: String,
+    /// Beneficiary to originator information, shown to the payer.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub information: String,
+}
+
+impl Transfer {
+    /// A transfer for the builder, in UTF-8.
+    pub fn new(version: &str) -> Self {
+        Transfer {
+            version: version.to_owned(),
+            character_set: UTF_8.to_owned(),
+            identification: IDENTIFICATION.to_owned(),
+            ..Transfer::default()
+        }
+    }
+
+    /// The IBAN and BIC without spaces and in capitals, and an amount of at most two decimals
+    /// padded to two.
+    fn normalise(&mut self) {
+        self.iban = compact(&self.iban);
+        self.bic = compact(&self.bic);
+        if let Some(amount) = self.amount.as_deref().and_then(pad_amount) {
+            self.amount = Some(amount);
+        }
+    }
+
+    /// Everything EPC069-12 requires of the fields, one message per problem.
+    pub fn check(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !matches!(self.version.as_str(), "001" | "002") {
+            errors.push(format!("version '{}' is not 001 or 002", self.version));
+        }
+        if !matches!(
+            self.character_set.as_str(),
+            "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8"
+        ) {
+            errors.push(format!(
+                "character set '{}' is not from 1 (UTF-8) to 8",
+                self.character_set
+            ));
+        }
+        if self.identification != IDENTIFICATION {
+            errors.push(format!(
+                "identification '{}' is not {IDENTIFICATION}",
+                self.identification
+            ));
+        }
+
+        if self.bic.is_empty() {
+            if self.version == "001" {
+                errors.push("version 001 needs a BIC".to_owned());
+            }
+        } else if !is_bic(&self.bic) {
+            errors.push(format!(
+                "the BIC '{}' is not 8 or 11 characters of bank, country, location and branch",
+                self.bic
+            ));
+        }
+        if self.name.is_empty() {
+            errors.push("the name of the beneficiary is missing".to_owned());
+        }
+        if self.iban.len() < 15
+            || self.iban.len() > 34
+            || !self.iban.bytes().all(|b| b.is_ascii_alphanumeric())
+            || !self.iban[..2].bytes().all(|b| b.is_ascii_uppercase())
+        {
+            errors.push(format!(
+                "the IBAN '{}' is not a country code and 13 to 32 letters and digits",
+                self.iban
+            ));
+        } else if !checksum::iban_is_valid(&self.iban) {
+            errors.push(format!(
+                "the IBAN '{}' has the wrong check digits (mod 97)",
+                self.iban
+            ));
+        }
+
+        if let Some(amount) = &self.amount {
+            let (whole, cents) = amount.split_once('.').unwrap_or((amount, ""));
+            let digits = whole
+                .bytes()
+                .chain(cents.bytes())
+                .all(|b| b.is_ascii_digit());
+            let value = amount.parse::<f64>().unwrap_or_default();
+            if !digits || whole.is_empty() || cents.len() > 2 || !(0.01..1e9).contains(&value) {
+                errors.push(format!(
+                    "the amount '{amount}' is not from 0.01 to 999999999.99 euro with at most two decimals"
+                ));
+            }
+        }
+        if !self.purpose.is_empty()
+            && (self.purpose.len() != 4
+                || !self
+                    .purpose
+                    .bytes()
+                    .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit()))
+        {
+            errors.push(format!(
+                "the purpose '{}' is not a four character ISO 20022 purpose code",
+                self.purpose
+            ));
+        }
+        if !self.reference.is_empty() && !self.remittance_text.is_empty() {
+            errors
+                .push("only one of the reference and the remittance text may be given".to_owned());
+        }
+        if self.reference.starts_with("RF") {
+            errors.extend(checksum::creditor_reference_error(&self.reference));
+        }
+
+        for (field, value, max) in [
+            ("name", &self.name, 70),
+            ("reference", &self.reference, 35),
+            ("remittance text", &self.remittance_text, 140),
+            ("information", &self.information, 70),
+        ] {
+            let length = value.chars().count();
+            if length > max {
+                errors.push(format!(
+                    "the {field} is {length} characters, at most {max} are allowed"
+                ));
+            }
+            if let Some(c) = value.chars().find(|c| !self.is_in_character_set(*c)) {
+                errors.push(format!(
+                    "the {field} contains '{}', which is not allowed in character set {}",
+                    c.escape_default(),
+                    self.character_set
+                ));
+            }
+        }
+        errors
+    }
+
+    /// Control characters are never allowed, ISO 8859-1 only reaches U+00FF. The other ISO
+    /// 8859 parts are not checked character by character.
+    fn is_in_character_set(&self, c: char) -> bool {
+        !c.is_control() && (self.character_set != "2" || c <= '\u{ff}')
+    }
+
+    /// The payload, one field per line separated by line feeds, without trailing empty lines.
+    pub fn to_payload(&self) -> String {
+        let amount = self
+            .amount
+            .as_ref()
+            .map(|amount| format!("EUR{amount}"))
+            .unwrap_or_default();
+        let lines = [
+            SERVICE_TAG,
+            &self.version,
+            &self.character_set,
+            &self.identification,
+            &self.bic,
+            &self.name,
+            &self.iban,
+            &amount,
+            &self.purpose,
+            &self.reference,
+            &self.remittance_text,
+            &self.information,
+        ];
+        lines.join("\n").trim_end_matches('\n').to_owned()
+    }
+}
+
+/// Build the payload of `transfer`, failing with every problem found.
+pub fn build(mut transfer: Transfer) -> Result<String, CliError> {
+    transfer.normalise();
+    let payload = transfer.to_payload();
+    let mut errors = transfer.check();
+    errors.extend(size_error(&payload));
+    invalid(errors)?;
+    Ok(payload)
+}
+
+/// Check a payload given as is with --data.
+pub fn validate(payload: &str) -> Result<(), CliError> {
+    invalid(parse(payload).errors)
+}
+
+fn invalid(errors: Vec<String>) -> Result<(), CliError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::InvalidHint(format!(
+            "Invalid EPC QR code:\n  {}",
+            errors.join("\n  ")
+        )))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Epc {
+    #[serde(flatten)]
+    pub transfer: Transfer,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl Epc {
+    /// One line per field, for the text output.
+    pub fn to_text(&self) -> String {
+        let Ok(Value::Object(fields)) = serde_json::to_value(&self.transfer) else {
+            return String::new();
+        };
+        let mut text = "EPC:".to_owned();
+        for (name, value) in fields {
+            if let Some(value) = value.as_str().filter(|value| !value.is_empty()) {
+                text.push_str(&format!("\n  {name}: {value}"));
+            }
+        }
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        text.push_str(&format!("\n  valid: {}", self.valid));
+        text
+    }
+}
+
+/// Parse an EPC069-12 payload and report where it breaks the standard.
+pub fn parse(payload: &str) -> Epc {
+    let lines: Vec<&str> = payload
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect();
+    let mut epc = Epc {
+        transfer: Transfer::default(),
+        errors: Vec::new(),
+        valid: false,
+    };
+    if lines[0] != SERVICE_TAG {
+        epc.errors
+            .push(format!("the payload does not start with '{SERVICE_TAG}'"));
+        return epc;
+    }
+    if lines.len() < MIN_LINES || lines.len() > MAX_LINES {
+        epc.errors.push(format!(
+            "the payload has {} lines, from {MIN_LINES} to {MAX_LINES} are allowed",
+            lines.len()
+        ));
+        if lines.len() < MIN_LINES {
+            return epc;
+        }
+    }
+    let line = |i: usize| lines.get(i).copied().unwrap_or_default().to_owned();
+    let transfer = &mut epc.transfer;
+    transfer.version = line(1);
+    transfer.character_set = line(2);
+    transfer.identification = line(3);
+    transfer.bic = line(4);
+    transfer.name = line(5);
+    transfer.iban = line(6);
+    let amount = line(7);
+    if let Some(euro) = amount.strip_prefix("EUR") {
+        transfer.amount = Some(euro.to_owned());
+    } else if !amount.is_empty() {
+        epc.errors
+            .push(format!("the amount '{amount}' does not start with EUR"));
+    }
+    transfer.purpose = line(8);
+    transfer.reference = line(9);
+    transfer.remittance_text = line(10);
+    transfer.information = line(11);
+
+    epc.errors.extend(epc.transfer.check());
+    epc.errors.extend(size_error(payload));
+    epc.valid = epc.errors.is_empty();
+    epc
+}
+
+fn size_error(payload: &str) -> Option<String> {
+    (payload.len() > MAX_PAYLOAD_BYTES).then(|| {
+        format!(
+            "the payload is {} bytes, at most {MAX_PAYLOAD_BYTES} are allowed",
+            payload.len()
+        )
+    })
+}
+
+/// Four letters of bank, two of country, two letters or digits of location and an optional
+/// three of branch.
+fn is_bic(bic: &str) -> bool {
+    let bytes = bic.as_bytes();
+    matches!(bytes.len(), 8 | 11)
+        && bytes[..6].iter().all(u8::is_ascii_uppercase)
+        && bytes[6..]
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+}
+
+/// Digits with at most two decimals, written with exactly two. Anything else is left for
+/// `check` to report rather than rounded.
+fn pad_amount(amount: &str) -> Option<String> {
+    let amount = amount.trim();
+    let (whole, cents) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits = whole
+        .bytes()
+        .chain(cents.bytes())
+        .all(|b| b.is_ascii_digit());
+    (!whole.is_empty() && cents.len() <= 2 && digits).then(|| format!("{whole}.{cents:0<2}"))
+}
+
+fn compact(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer() -> Transfer {
+        Transfer {
+            bic: "bpot be b1".to_owned(),
+            name: "Red Cross of Belgium".to_owned(),
+            iban: "BE72 0000 0000 1616".to_owned(),
+            amount: Some("1".to_owned()),
+            purpose: "CHAR".to_owned(),
+            remittance_text: "Urgency fund".to_owned(),
+            ..Transfer::new("001")
+        }
+    }
+
+    #[test]
+    fn build_and_parse() {
+        let payload = build(transfer()).unwrap();
+        assert_eq!(
+            payload,
+            "BCD\n001\n1\nSCT\nBPOTBEB1\nRed Cross of Belgium\nBE72000000001616\nEUR1.00\nCHAR\n\nUrgency fund"
+        );
+        let epc = parse(&payload);
+        assert!(epc.valid, "{:?}", epc.errors);
+        assert_eq!(epc.transfer.amount.as_deref(), Some("1.00"));
+        assert!(validate(&format!("{payload}\r\n")).is_ok());
+    }
+
+    #[test]
+    fn amounts_are_padded_not_rounded() {
+        assert_eq!(pad_amount("12.3").as_deref(), Some("12.30"));
+        assert_eq!(pad_amount("1.005"), None);
+        assert_eq!(pad_amount("1e3"), None);
+    }
+
+    #[test]
+    fn invalid_transfers() {
+        let mut no_bic = transfer();
+        no_bic.bic.clear();
+        let mut wrong_iban = transfer();
+        wrong_iban.iban = "BE73000000001616".to_owned();
+        let mut both_references = transfer();
+        both_references.reference = "RF18539007547034".to_owned();
+        let mut too_much = transfer();
+        too_much.amount = Some("1000000000".to_owned());
+        let mut rounded = transfer();
+        rounded.amount = Some("1.005".to_owned());
+        let mut exponent = transfer();
+        exponent.amount = Some("1e3".to_owned());
+        for transfer in [
+            no_bic,
+            wrong_iban,
+            both_references,
+            too_much,
+            rounded,
+            exponent,
+        ] {
+            assert!(build(transfer).is_err());
+        }
+        let mut version_2 = transfer();
+        version_2.version = "002".to_owned();
+        version_2.bic.clear();
+        assert!(build(version_2).is_ok());
+    }
+
+    #[test]
+    fn invalid_payloads() {
+        assert!(!parse("SPC\n0200").valid);
+        assert!(!parse("BCD\n001\n1\nSCT").valid);
+        let amount = "BCD\n002\n1\nSCT\n\nName\nBE72000000001616\n1.00";
+        assert!(validate(amount).is_err());
+    }
+}
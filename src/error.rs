@@ -0,0 +1,199 @@
+use std::{fmt, path::Path, process::ExitCode};
+
+use rxing::Exceptions;
+
+/// Errors reported by the command line tool. Each variant maps to its own process exit code
+/// so that scripts can tell failures apart without parsing stderr:
+///
+/// | Code | Meaning                                                    |
+/// |------|------------------------------------------------------------|
+/// | 0    | Success                                                    |
+/// | 1    | No barcode was found                                       |
+/// | 2    | Invalid command line (reported by clap)                    |
+/// | 3    | A file could not be read or written                        |
+/// | 4    | The image or barcode format is not supported               |
+/// | 5    | A hint or option has an invalid value                      |
+/// | 6    | The data could not be encoded                              |
+/// | 7    | Some, but not all, files in a batch failed to decode       |
+/// | 8    | An encoded barcode did not decode to its input (--verify), |
+/// |      | or a certificate signature did not verify (--dcc, --shc)   |
+#[derive(Debug)]
+pub enum CliError {
+    NotFound(String),
+    Io(String),
+    UnsupportedFormat(String),
+    InvalidHint(String),
+    EncodeFailure(String),
+    PartialBatchFailure { decoded: usize, failed: usize },
+    VerifyFailure(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> ExitCode {
+        ExitCode::from(match self {
+            CliError::NotFound(_) => 1,
+            CliError::Io(_) => 3,
+            CliError::UnsupportedFormat(_) => 4,
+            CliError::InvalidHint(_) => 5,
+            CliError::EncodeFailure(_) => 6,
+            CliError::PartialBatchFailure { .. } => 7,
+            CliError::VerifyFailure(_) => 8,
+        })
+    }
+
+    /// Classify an error returned while reading and searching `file_name`.
+    pub fn from_decode_error(file_name: &str, multi: bool, error: Exceptions) -> Self {
+        match error {
+            // rxing reports files that are missing and files that are not images in the same way
+            Exceptions::IllegalArgumentException(_) | Exceptions::RuntimeException(_)
+                if file_name != crate::stdio::STDIO && !Path::new(file_name).is_file() =>
+            {
+                CliError::Io(format!("'{file_name}' does not exist or is not a file"))
+            }
+            Exceptions::IllegalArgumentException(_)
+            | Exceptions::RuntimeException(_)
+            | Exceptions::UnsupportedOperationException(_) => {
+                CliError::UnsupportedFormat(format!("Could not read image '{file_name}': {error}"))
+            }
+            _ if multi => CliError::NotFound(format!(
+                "Error while attempting to locate multiple barcodes in '{file_name}': {error}"
+            )),
+            _ => CliError::NotFound(format!(
+                "Error while attempting to locate barcode in '{file_name}': {error}"
+            )),
+        }
+    }
+
+    /// Classify an error returned by `MultiFormatWriter`.
+    pub fn from_encode_error(error: Exceptions) -> Self {
+        match error {
+            Exceptions::ParseException(_) => {
+                CliError::InvalidHint(format!("Couldn't encode: {error}"))
+            }
+            Exceptions::IllegalArgumentException(Some(ref message))
+                if message.starts_with("No encoder available") =>
+            {
+                CliError::UnsupportedFormat(format!("Couldn't encode: {error}"))
+            }
+            _ => CliError::EncodeFailure(format!("Couldn't encode: {error}")),
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::NotFound(message)
+            | CliError::Io(message)
+            | CliError::UnsupportedFormat(message)
+            | CliError::InvalidHint(message)
+            | CliError::EncodeFailure(message)
+            | CliError::VerifyFailure(message) => write!(f, "{message}"),
+            CliError::PartialBatchFailure { decoded, failed } => write!(
+                f,
+                "{failed} of {} inputs failed to decode",
+                decoded + failed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::Args;
+
+    fn message(text: &str) -> Option<String> {
+        Some(text.to_owned())
+    }
+
+    #[test]
+    fn exit_codes() {
+        let message = || "message".to_owned();
+        let errors = [
+            (CliError::NotFound(message()), 1),
+            (CliError::Io(message()), 3),
+            (CliError::UnsupportedFormat(message()), 4),
+            (CliError::InvalidHint(message()), 5),
+            (CliError::EncodeFailure(message()), 6),
+            (
+                CliError::PartialBatchFailure {
+                    decoded: 1,
+                    failed: 1,
+                },
+                7,
+            ),
+            (CliError::VerifyFailure(message()), 8),
+        ];
+        for (error, code) in errors {
+            assert_eq!(error.exit_code(), ExitCode::from(code), "{error:?}");
+        }
+        // clap reports an invalid command line itself
+        match Args::try_parse_from(["rxing-cli", "--no-such-flag"]) {
+            Err(usage) => assert_eq!(usage.exit_code(), 2),
+            Ok(_) => panic!("an unknown flag was accepted"),
+        }
+    }
+
+    #[test]
+    fn decode_errors() {
+        let missing = CliError::from_decode_error(
+            "no-such-file.png",
+            false,
+            Exceptions::IllegalArgumentException(None),
+        );
+        assert!(matches!(missing, CliError::Io(_)), "{missing:?}");
+
+        let not_an_image = CliError::from_decode_error(
+            "Cargo.toml",
+            false,
+            Exceptions::RuntimeException(message("unknown format")),
+        );
+        assert!(
+            matches!(not_an_image, CliError::UnsupportedFormat(_)),
+            "{not_an_image:?}"
+        );
+        let stdin = CliError::from_decode_error(
+            crate::stdio::STDIO,
+            false,
+            Exceptions::IllegalArgumentException(None),
+        );
+        assert!(matches!(stdin, CliError::UnsupportedFormat(_)), "{stdin:?}");
+
+        let single =
+            CliError::from_decode_error("Cargo.toml", false, Exceptions::NotFoundException(None));
+        assert!(single
+            .to_string()
+            .contains("locate barcode in 'Cargo.toml'"));
+        let multi =
+            CliError::from_decode_error("Cargo.toml", true, Exceptions::NotFoundException(None));
+        assert!(multi.to_string().contains("locate multiple barcodes"));
+        assert!(matches!(multi, CliError::NotFound(_)));
+    }
+
+    #[test]
+    fn encode_errors() {
+        let invalid = CliError::from_encode_error(Exceptions::ParseException(message("hint")));
+        assert!(matches!(invalid, CliError::InvalidHint(_)), "{invalid:?}");
+
+        let unsupported = CliError::from_encode_error(Exceptions::IllegalArgumentException(
+            message("No encoder available for format UPC_EAN_EXTENSION"),
+        ));
+        assert!(
+            matches!(unsupported, CliError::UnsupportedFormat(_)),
+            "{unsupported:?}"
+        );
+
+        let failure =
+            CliError::from_encode_error(Exceptions::IllegalArgumentException(message("too long")));
+        assert!(matches!(failure, CliError::EncodeFailure(_)), "{failure:?}");
+        assert_eq!(
+            failure.to_string(),
+            "Couldn't encode: IllegalArgumentException - too long"
+        );
+    }
+}
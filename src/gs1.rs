@@ -0,0 +1,634 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use Content::*;
+
+/// FNC1 in its role as field separator is transmitted as ASCII group separator.
+pub const GROUP_SEPARATOR: char = '\u{1d}';
+
+/// The character the rxing Code 128 writer encodes as FNC1.
+pub const CODE_128_FNC1: char = '\u{f1}';
+
+/// Symbology identifiers announcing GS1 data: GS1-128, GS1 DataBar, GS1 DataMatrix, GS1 QR
+/// Code and GS1 composite components.
+const GS1_SYMBOLOGY_IDENTIFIERS: &[&str] = &["]C1", "]e0", "]e1", "]e2", "]d2", "]Q3", "]J1"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Content {
+    /// Digits only.
+    Numeric,
+    /// Characters of GS1 character set 82.
+    Alphanumeric,
+    /// Digits ending in a GS1 mod 10 check digit (GTIN, SSCC, GLN...).
+    CheckDigit,
+    /// YYMMDD, where a day of 00 means the last day of the month.
+    Date,
+    /// Digits with the number of decimals given by the last digit of the AI.
+    Decimal,
+    /// A key of this many digits ending in a check digit, followed by characters of set 82.
+    CheckDigitThenText(usize),
+    /// This many digits (e.g. an ISO country or currency code) followed by characters of
+    /// set 82, or further digits for decimal amounts.
+    DigitsThenText(usize),
+}
+
+/// Definition of an Application Identifier.
+#[derive(Debug)]
+pub struct AiSpec {
+    /// The AI, or its leading digits when the last digit is variable (e.g. "310" for 3100-3105).
+    pub code: &'static str,
+    /// Number of digits of the complete AI.
+    pub ai_length: usize,
+    pub title: &'static str,
+    /// Length of the data if it is fixed. Only the AIs of `PREDEFINED_LENGTH` may leave out
+    /// the separator before the next element, whatever their length.
+    pub fixed: Option<usize>,
+    pub max: usize,
+    pub content: Content,
+}
+
+const fn fixed(
+    code: &'static str,
+    ai_length: usize,
+    title: &'static str,
+    length: usize,
+    content: Content,
+) -> AiSpec {
+    AiSpec {
+        code,
+        ai_length,
+        title,
+        fixed: Some(length),
+        max: length,
+        content,
+    }
+}
+
+const fn variable(
+    code: &'static str,
+    ai_length: usize,
+    title: &'static str,
+    max: usize,
+    content: Content,
+) -> AiSpec {
+    AiSpec {
+        code,
+        ai_length,
+        title,
+        fixed: None,
+        max,
+        content,
+    }
+}
+
+/// The Application Identifiers of the GS1 General Specifications that are in common use.
+pub const AIS: &[AiSpec] = &[
+    fixed("00", 2, "SSCC", 18, CheckDigit),
+    fixed("01", 2, "GTIN", 14, CheckDigit),
+    fixed("02", 2, "CONTENT", 14, CheckDigit),
+    variable("10", 2, "BATCH/LOT", 20, Alphanumeric),
+    fixed("11", 2, "PROD DATE", 6, Date),
+    fixed("12", 2, "DUE DATE", 6, Date),
+    fixed("13", 2, "PACK DATE", 6, Date),
+    fixed("15", 2, "BEST BEFORE or BEST BY", 6, Date),
+    fixed("16", 2, "SELL BY", 6, Date),
+    fixed("17", 2, "USE BY or EXPIRY", 6, Date),
+    fixed("20", 2, "VARIANT", 2, Numeric),
+    variable("21", 2, "SERIAL", 20, Alphanumeric),
+    variable("22", 2, "CPV", 20, Alphanumeric),
+    variable("235", 3, "TPX", 28, Alphanumeric),
+    variable("240", 3, "ADDITIONAL ID", 30, Alphanumeric),
+    variable("241", 3, "CUST. PART No.", 30, Alphanumeric),
+    variable("242", 3, "MTO VARIANT", 6, Numeric),
+    variable("243", 3, "PCN", 20, Alphanumeric),
+    variable("250", 3, "SECONDARY SERIAL", 30, Alphanumeric),
+    variable("251", 3, "REF. TO SOURCE", 30, Alphanumeric),
+    variable("253", 3, "GDTI", 30, CheckDigitThenText(13)),
+    variable("254", 3, "GLN EXTENSION COMPONENT", 20, Alphanumeric),
+    variable("255", 3, "GCN", 25, CheckDigitThenText(13)),
+    variable("30", 2, "VAR. COUNT", 8, Numeric),
+    fixed("310", 4, "NET WEIGHT (kg)", 6, Decimal),
+    fixed("311", 4, "LENGTH (m)", 6, Decimal),
+    fixed("312", 4, "WIDTH (m)", 6, Decimal),
+    fixed("313", 4, "HEIGHT (m)", 6, Decimal),
+    fixed("314", 4, "AREA (m2)", 6, Decimal),
+    fixed("315", 4, "NET VOLUME (l)", 6, Decimal),
+    fixed("316", 4, "NET VOLUME (m3)", 6, Decimal),
+    fixed("320", 4, "NET WEIGHT (lb)", 6, Decimal),
+    fixed("330", 4, "GROSS WEIGHT (kg)", 6, Decimal),
+    fixed("331", 4, "LENGTH (m), log", 6, Decimal),
+    fixed("332", 4, "WIDTH (m), log", 6, Decimal),
+    fixed("333", 4, "HEIGHT (m), log", 6, Decimal),
+    fixed("334", 4, "AREA (m2), log", 6, Decimal),
+    fixed("335", 4, "VOLUME (l), log", 6, Decimal),
+    fixed("336", 4, "VOLUME (m3), log", 6, Decimal),
+    fixed("340", 4, "GROSS WEIGHT (lb)", 6, Decimal),
+    variable("37", 2, "COUNT", 8, Numeric),
+    variable("390", 4, "AMOUNT", 15, Decimal),
+    variable("391", 4, "AMOUNT", 18, DigitsThenText(3)),
+    variable("392", 4, "PRICE", 15, Decimal),
+    variable("393", 4, "PRICE", 18, DigitsThenText(3)),
+    fixed("394", 4, "PRCNT OFF", 4, Decimal),
+    variable("400", 3, "ORDER NUMBER", 30, Alphanumeric),
+    variable("401", 3, "GINC", 30, Alphanumeric),
+    fixed("402", 3, "GSIN", 17, CheckDigit),
+    variable("403", 3, "ROUTE", 30, Alphanumeric),
+    fixed("410", 3, "SHIP TO LOC", 13, CheckDigit),
+    fixed("411", 3, "BILL TO", 13, CheckDigit),
+    fixed("412", 3, "PURCHASE FROM", 13, CheckDigit),
+    fixed("413", 3, "SHIP FOR LOC", 13, CheckDigit),
+    fixed("414", 3, "LOC No.", 13, CheckDigit),
+    fixed("415", 3, "PAY TO", 13, CheckDigit),
+    fixed("416", 3, "PROD/SERV LOC", 13, CheckDigit),
+    fixed("417", 3, "PARTY", 13, CheckDigit),
+    variable("420", 3, "SHIP TO POST", 20, Alphanumeric),
+    variable("421", 3, "SHIP TO POST", 12, DigitsThenText(3)),
+    fixed("422", 3, "ORIGIN", 3, Numeric),
+    variable("423", 3, "COUNTRY - INITIAL PROCESS", 15, Numeric),
+    fixed("424", 3, "COUNTRY - PROCESS", 3, Numeric),
+    variable("425", 3, "COUNTRY - DISASSEMBLY", 15, Numeric),
+    fixed("426", 3, "COUNTRY - FULL PROCESS", 3, Numeric),
+    fixed("7003", 4, "EXPIRY TIME", 10, Numeric),
+    variable("8003", 4, "GRAI", 30, CheckDigitThenText(14)),
+    variable("8004", 4, "GIAI", 30, Alphanumeric),
+    fixed("8005", 4, "PRICE PER UNIT", 6, Numeric),
+    fixed("8006", 4, "ITIP", 18, Numeric),
+    variable("8007", 4, "IBAN", 34, Alphanumeric),
+    variable("8008", 4, "PROD TIME", 12, Numeric),
+    variable("8010", 4, "CPID", 30, Alphanumeric),
+    variable("8011", 4, "CPID SERIAL", 12, Numeric),
+    variable("8013", 4, "GMN", 25, Alphanumeric),
+    fixed("8017", 4, "GSRN - PROVIDER", 18, CheckDigit),
+    fixed("8018", 4, "GSRN - RECIPIENT", 18, CheckDigit),
+    variable("8019", 4, "SRIN", 10, Numeric),
+    variable("8020", 4, "REF No.", 25, Alphanumeric),
+    variable("90", 2, "INTERNAL", 30, Alphanumeric),
+    variable("91", 2, "INTERNAL", 90, Alphanumeric),
+    variable("92", 2, "INTERNAL", 90, Alphanumeric),
+    variable("93", 2, "INTERNAL", 90, Alphanumeric),
+    variable("94", 2, "INTERNAL", 90, Alphanumeric),
+    variable("95", 2, "INTERNAL", 90, Alphanumeric),
+    variable("96", 2, "INTERNAL", 90, Alphanumeric),
+    variable("97", 2, "INTERNAL", 90, Alphanumeric),
+    variable("98", 2, "INTERNAL", 90, Alphanumeric),
+    variable("99", 2, "INTERNAL", 90, Alphanumeric),
+];
+
+/// The leading two digits of the AIs whose element length, AI included, is predefined, and
+/// that length. These are the only elements that need no separator when followed by another,
+/// readers split other fixed length elements on the separator like variable length ones.
+const PREDEFINED_LENGTH: &[(&str, usize)] = &[
+    ("00", 20),
+    ("01", 16),
+    ("02", 16),
+    ("03", 16),
+    ("04", 18),
+    ("11", 8),
+    ("12", 8),
+    ("13", 8),
+    ("14", 8),
+    ("15", 8),
+    ("16", 8),
+    ("17", 8),
+    ("18", 8),
+    ("19", 8),
+    ("20", 4),
+    ("31", 10),
+    ("32", 10),
+    ("33", 10),
+    ("34", 10),
+    ("35", 10),
+    ("36", 10),
+    ("41", 16),
+];
+
+/// Whether the element of `ai` has a predefined length and so needs no separator after it.
+pub fn has_predefined_length(ai: &str) -> bool {
+    PREDEFINED_LENGTH
+        .iter()
+        .any(|(prefix, _)| ai.starts_with(prefix))
+}
+
+/// The definition of the AI that `text` starts with, if it is known.
+pub fn lookup(text: &str) -> Option<&'static AiSpec> {
+    AIS.iter()
+        .filter(|spec| {
+            text.len() >= spec.ai_length
+                && text.starts_with(spec.code)
+                && text.as_bytes()[..spec.ai_length]
+                    .iter()
+                    .all(u8::is_ascii_digit)
+        })
+        .max_by_key(|spec| spec.code.len())
+}
+
+/// GS1 mod 10 check digit of `digits`, which must not include the check digit itself.
+pub fn check_digit(digits: &str) -> u32 {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .enumerate()
+        .map(|(i, b)| (b - b'0') as u32 * if i % 2 == 0 { 3 } else { 1 })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+/// GS1 character set 82, the characters allowed in alphanumeric AIs.
+fn is_cset82(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!\"%&'()*+,-./:;<=>?_".contains(c)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Element {
+    pub ai: String,
+    pub title: &'static str,
+    pub value: String,
+    /// Dates as YYYY-MM-DD.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// Decimal AIs with the implied decimal point placed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decimal: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ElementString {
+    /// Human readable interpretation, e.g. `(01)09501101530003(17)260101`.
+    pub hri: String,
+    pub elements: Vec<Element>,
+    /// Problems with the structure, after which the rest of the data could not be split.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl ElementString {
+    pub fn new(elements: Vec<Element>, errors: Vec<String>) -> Self {
+        let valid = errors.is_empty() && elements.iter().all(|e| e.errors.is_empty());
+        ElementString {
+            hri: elements
+                .iter()
+                .map(|e| format!("({}){}", e.ai, e.value))
+                .collect(),
+            elements,
+            errors,
+            valid,
+        }
+    }
+
+    /// The HRI followed by one line per element, for the text output.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("GS1: {}", self.hri);
+        for element in &self.elements {
+            text.push_str(&format!(
+                "\n  ({}) {}: {}",
+                element.ai, element.title, element.value
+            ));
+            if let Some(readable) = element.date.as_ref().or(element.decimal.as_ref()) {
+                text.push_str(&format!(" [{readable}]"));
+            }
+            for error in &element.errors {
+                text.push_str(&format!("\n    error: {error}"));
+            }
+        }
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        text
+    }
+}
+
+/// `data` without a leading GS1 symbology identifier, which some readers prepend.
+pub fn strip_symbology_identifier(data: &str) -> &str {
+    GS1_SYMBOLOGY_IDENTIFIERS
+        .iter()
+        .find_map(|id| data.strip_prefix(id))
+        .unwrap_or(data)
+}
+
+/// Split a decoded GS1 element string (AIs without brackets, variable length values ended by
+/// a group separator) and validate every element.
+pub fn parse_element_string(data: &str) -> ElementString {
+    // a leading FNC1 is not part of the data either
+    let mut rest = strip_symbology_identifier(data).trim_start_matches(GROUP_SEPARATOR);
+    let mut elements = Vec::new();
+    let mut errors = Vec::new();
+
+    while !rest.is_empty() {
+        let Some(spec) = lookup(rest) else {
+            errors.push(format!("unknown application identifier at '{rest}'"));
+            break;
+        };
+        let ai = &rest[..spec.ai_length];
+        rest = &rest[spec.ai_length..];
+
+        let (value, remainder) = match spec.fixed {
+            Some(length) => {
+                let end = rest
+                    .char_indices()
+                    .nth(length)
+                    .map_or(rest.len(), |(i, _)| i);
+                (&rest[..end], &rest[end..])
+            }
+            None => rest.split_once(GROUP_SEPARATOR).unwrap_or((rest, "")),
+        };
+        // fixed length values may still be followed by a (redundant) separator
+        rest = remainder.trim_start_matches(GROUP_SEPARATOR);
+        elements.push(validate(spec, ai, value));
+    }
+
+    ElementString::new(elements, errors)
+}
+
+/// Validate the `value` of one element, `ai` is the complete AI for `spec`.
+pub fn validate(spec: &AiSpec, ai: &str, value: &str) -> Element {
+    let mut element = Element {
+        ai: ai.to_owned(),
+        title: spec.title,
+        value: value.to_owned(),
+        date: None,
+        decimal: None,
+        errors: Vec::new(),
+    };
+    let errors = &mut element.errors;
+    let length = value.chars().count();
+
+    match spec.fixed {
+        Some(fixed) if length != fixed => {
+            errors.push(format!("must be {fixed} characters long, got {length}"))
+        }
+        None if length > spec.max => errors.push(format!(
+            "must be at most {} characters long, got {length}",
+            spec.max
+        )),
+        _ if length == 0 => errors.push("must not be empty".to_owned()),
+        _ => {}
+    }
+
+    let digits_end = match spec.content {
+        Numeric | CheckDigit | Date | Decimal => length,
+        CheckDigitThenText(n) | DigitsThenText(n) => n.min(length),
+        Alphanumeric => 0,
+    };
+    let (digits, text) = value.split_at(
+        value
+            .char_indices()
+            .nth(digits_end)
+            .map_or(value.len(), |(i, _)| i),
+    );
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        errors.push(format!("'{digits}' must only contain digits"));
+        return element;
+    }
+    let text_is_numeric = matches!(spec.content, DigitsThenText(_)) && spec.code.starts_with("39");
+    if let Some(c) = text.chars().find(|c| {
+        if text_is_numeric {
+            !c.is_ascii_digit()
+        } else {
+            !is_cset82(*c)
+        }
+    }) {
+        errors.push(format!("'{c}' is not allowed in ({ai})"));
+    }
+
+    match spec.content {
+        CheckDigit | CheckDigitThenText(_) if digits.len() >= 2 => {
+            let (key, check) = digits.split_at(digits.len() - 1);
+            let expected = check_digit(key);
+            if check != expected.to_string() {
+                errors.push(format!("check digit is {check}, expected {expected}"));
+            }
+        }
+        Date if digits.len() == 6 => match parse_date(digits) {
+            Some(date) => element.date = Some(date.to_string()),
+            None => errors.push(format!("'{digits}' is not a valid YYMMDD date")),
+        },
+        // a value with characters outside set 82 is already reported, and can not be split
+        Decimal | DigitsThenText(_) if ai.len() == 4 && value.is_ascii() => {
+            let amount = if let DigitsThenText(n) = spec.content {
+                &value[n.min(value.len())..]
+            } else {
+                value
+            };
+            let decimals = (ai.as_bytes()[3] - b'0') as usize;
+            if decimals > amount.len() {
+                errors.push(format!(
+                    "({ai}) places the decimal point {decimals} digits from the right of a {} digit value",
+                    amount.len()
+                ));
+            } else if !amount.is_empty() {
+                let (whole, fraction) = amount.split_at(amount.len() - decimals);
+                let whole = whole.trim_start_matches('0');
+                let whole = if whole.is_empty() { "0" } else { whole };
+                element.decimal = Some(if fraction.is_empty() {
+                    whole.to_owned()
+                } else {
+                    format!("{whole}.{fraction}")
+                });
+            }
+        }
+        _ => {}
+    }
+    element
+}
+
+/// A GS1 YYMMDD date. The century is chosen so that the year falls between 49 years in the
+/// past and 50 years in the future, and a day of 00 stands for the end of the month.
+pub fn parse_date(yymmdd: &str) -> Option<NaiveDate> {
+    use chrono::Datelike;
+
+    let yy: i32 = yymmdd.get(0..2)?.parse().ok()?;
+    let month: u32 = yymmdd.get(2..4)?.parse().ok()?;
+    let day: u32 = yymmdd.get(4..6)?.parse().ok()?;
+
+    let current_year = chrono::Utc::now().year();
+    let mut year = current_year / 100 * 100 + yy;
+    if year - current_year >= 51 {
+        year -= 100;
+    } else if current_year - year > 49 {
+        year += 100;
+    }
+
+    if day == 0 {
+        let first_of_next = if month == 12 {
+            NaiveDate::from_ymd_opt(year + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(year, month + 1, 1)
+        };
+        return first_of_next?
+            .pred_opt()
+            .filter(|_| (1..=12).contains(&month));
+    }
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Parse GS1 data in the bracketed form printed under barcodes, e.g.
+/// `(01)09506000134352(17)260101(10)ABC`, and validate every element.
+///
+/// A `(` starts the next AI only when it is followed by digits and `)`, so values may contain
+/// other brackets. With `compute_check_digit` the check digit is appended to keys that are
+/// one digit short.
+pub fn parse_hri(hri: &str, compute_check_digit: bool) -> Result<ElementString, String> {
+    let mut rest = hri
+        .strip_prefix('(')
+        .ok_or_else(|| format!("'{hri}' does not start with an AI in brackets, e.g. (01)"))?;
+    let mut elements = Vec::new();
+
+    while !rest.is_empty() {
+        let (ai, after_ai) = rest
+            .split_once(')')
+            .ok_or_else(|| format!("the bracket before '{rest}' is not closed"))?;
+        let spec = lookup(ai)
+            .filter(|spec| spec.ai_length == ai.len())
+            .ok_or_else(|| format!("({ai}) is not a known application identifier"))?;
+        let end = next_ai(after_ai).unwrap_or(after_ai.len());
+        let mut value = after_ai[..end].to_owned();
+        rest = after_ai[end..].strip_prefix('(').unwrap_or_default();
+
+        if compute_check_digit
+            && spec.content == CheckDigit
+            && spec.fixed == Some(value.len() + 1)
+            && value.bytes().all(|b| b.is_ascii_digit())
+        {
+            value.push_str(&check_digit(&value).to_string());
+        }
+        elements.push(validate(spec, ai, &value));
+    }
+
+    Ok(ElementString::new(elements, Vec::new()))
+}
+
+/// Index of the `(` that opens the next AI in `text`.
+fn next_ai(text: &str) -> Option<usize> {
+    text.match_indices('(').map(|(i, _)| i).find(|i| {
+        let ai = &text[i + 1..];
+        ai.find(')')
+            .is_some_and(|close| close >= 2 && ai[..close].bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+impl ElementString {
+    /// The data to encode: the AIs and values without brackets, with `separator` after every
+    /// value that is followed by another element, unless its AI has a predefined length.
+    pub fn encode(&self, separator: char) -> String {
+        let mut data = String::new();
+        for (i, element) in self.elements.iter().enumerate() {
+            data.push_str(&element.ai);
+            data.push_str(&element.value);
+            if !has_predefined_length(&element.ai) && i + 1 < self.elements.len() {
+                data.push(separator);
+            }
+        }
+        data
+    }
+
+    /// Every problem found, one per line, prefixed by the AI it belongs to.
+    pub fn error_lines(&self) -> Vec<String> {
+        self.elements
+            .iter()
+            .flat_map(|element| {
+                element
+                    .errors
+                    .iter()
+                    .map(move |error| format!("({}) {error}", element.ai))
+            })
+            .chain(self.errors.iter().cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_ascii_amount_is_an_error() {
+        let element_string = parse_hri("(3911)978é", false).unwrap();
+        assert!(!element_string.valid);
+        assert_eq!(
+            element_string.error_lines(),
+            ["(3911) 'é' is not allowed in (3911)"]
+        );
+
+        let element_string = parse_element_string("3911978é");
+        assert!(!element_string.valid);
+    }
+
+    #[test]
+    fn separator_only_after_variable_or_not_predefined_length() {
+        let element_string = parse_hri("(01)09506000134352(17)260101(10)ABC(21)1", false).unwrap();
+        assert_eq!(
+            element_string.encode(GROUP_SEPARATOR),
+            "01095060001343521726010110ABC\u{1d}211"
+        );
+
+        // (422) is three digits long, but that length is not predefined
+        let element_string = parse_hri("(422)756(10)ABC", false).unwrap();
+        assert_eq!(element_string.encode(GROUP_SEPARATOR), "422756\u{1d}10ABC");
+        let element_string = parse_hri("(8005)000123(3103)000750", false).unwrap();
+        assert_eq!(
+            element_string.encode(GROUP_SEPARATOR),
+            "8005000123\u{1d}3103000750"
+        );
+    }
+
+    #[test]
+    fn mod_10_check_digit() {
+        assert_eq!(check_digit("0950600013435"), 2);
+        assert_eq!(check_digit("37610425002123456"), 9);
+    }
+
+    #[test]
+    fn parse_bracketed() {
+        let element_string =
+            parse_hri("(01)09506000134352(17)260100(10)ABC(3103)000750", false).unwrap();
+        assert!(element_string.valid);
+        assert_eq!(
+            element_string.elements[1].date.as_deref(),
+            Some("2026-01-31")
+        );
+        assert_eq!(element_string.elements[3].decimal.as_deref(), Some("0.750"));
+
+        let element_string = parse_hri("(01)09506000134353", false).unwrap();
+        assert_eq!(
+            element_string.error_lines(),
+            ["(01) check digit is 3, expected 2"]
+        );
+        let element_string = parse_hri("(17)261301", false).unwrap();
+        assert!(!element_string.valid);
+        assert!(parse_hri("(23)1", false).is_err());
+        assert!(parse_hri("01)09506000134352", false).is_err());
+    }
+
+    #[test]
+    fn complete_check_digit() {
+        let element_string = parse_hri("(01)0950600013435", true).unwrap();
+        assert_eq!(element_string.hri, "(01)09506000134352");
+        assert!(element_string.valid);
+    }
+
+    #[test]
+    fn brackets_in_values() {
+        let element_string = parse_hri("(10)A(B)C(21)1", false).unwrap();
+        assert_eq!(element_string.hri, "(10)A(B)C(21)1");
+    }
+
+    #[test]
+    fn parse_decoded_element_string() {
+        let element_string =
+            parse_element_string("]C1010950600013435217260101\u{1d}10ABC\u{1d}21XYZ");
+        assert_eq!(
+            element_string.hri,
+            "(01)09506000134352(17)260101(10)ABC(21)XYZ"
+        );
+        assert!(element_string.valid);
+
+        let element_string = parse_element_string("010950600013435205123");
+        assert_eq!(
+            element_string.errors,
+            ["unknown application identifier at '05123'"]
+        );
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::Path;
+
+/// Extensions picked up when walking a directory. Files named explicitly on the command line
+/// are always decoded, whatever their extension.
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "tif", "tiff", "webp", "pbm", "pgm", "ppm", "pnm", "tga",
+    "ico", "svg",
+];
+
+/// The files to decode, in the order they should be processed, plus any inputs that could
+/// not be expanded (as `(input, reason)`).
+#[derive(Default)]
+pub struct Inputs {
+    pub files: Vec<String>,
+    pub errors: Vec<(String, String)>,
+    /// True when more than a single plain file was requested, in which case results are
+    /// tagged with their source file and a summary is printed.
+    pub is_batch: bool,
+}
+
+/// Expand plain paths, shell-style glob patterns and directories into a list of files.
+/// Directories contribute the images they contain, and with `recursive` the images of all of
+/// their subdirectories as well.
+pub fn expand_inputs(inputs: &[String], recursive: bool) -> Inputs {
+    let mut expanded = Inputs {
+        is_batch: inputs.len() > 1,
+        ..Default::default()
+    };
+
+    for input in inputs {
+        let path = Path::new(input);
+        // an existing file is never a pattern, even with glob characters in its name
+        if path.is_file() {
+            expanded.files.push(input.to_owned());
+        } else if path.is_dir() {
+            expanded.is_batch = true;
+            walk_directory(path, recursive, &mut expanded);
+        } else if is_glob(input) {
+            expanded.is_batch = true;
+            expand_glob(input, recursive, &mut expanded);
+        } else {
+            expanded.files.push(input.to_owned());
+        }
+    }
+
+    expanded
+}
+
+fn is_glob(input: &str) -> bool {
+    input.contains(['*', '?', '['])
+}
+
+fn expand_glob(pattern: &str, recursive: bool, expanded: &mut Inputs) {
+    let paths = match glob::glob(pattern) {
+        Ok(paths) => paths,
+        Err(pattern_err) => {
+            expanded.errors.push((
+                pattern.to_owned(),
+                format!("invalid pattern: {pattern_err}"),
+            ));
+            return;
+        }
+    };
+
+    let mut matched = false;
+    for entry in paths {
+        matched = true;
+        match entry {
+            Ok(path) if path.is_dir() => {
+                if recursive {
+                    walk_directory(&path, recursive, expanded)
+                }
+            }
+            Ok(path) => expanded.files.push(path.to_string_lossy().to_string()),
+            Err(glob_err) => expanded.errors.push((
+                glob_err.path().to_string_lossy().to_string(),
+                glob_err.error().to_string(),
+            )),
+        }
+    }
+
+    if !matched {
+        expanded
+            .errors
+            .push((pattern.to_owned(), "no files match pattern".to_owned()));
+    }
+}
+
+fn walk_directory(directory: &Path, recursive: bool, expanded: &mut Inputs) {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(io_err) => {
+            expanded
+                .errors
+                .push((directory.to_string_lossy().to_string(), io_err.to_string()));
+            return;
+        }
+    };
+
+    // Sort so that the processing order does not depend on the file system.
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            if recursive {
+                walk_directory(&path, recursive, expanded);
+            }
+        } else if has_image_extension(&path) {
+            expanded.files.push(path.to_string_lossy().to_string());
+        }
+    }
+}
+
+fn has_image_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_with_glob_characters_is_not_a_pattern() {
+        let directory =
+            std::env::temp_dir().join(format!("rxing-cli-inputs-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let file = directory.join("scan[1].png");
+        std::fs::write(&file, b"").unwrap();
+        let file = file.to_string_lossy().into_owned();
+        let missing = directory.join("none[1].png").to_string_lossy().into_owned();
+
+        let expanded = expand_inputs(&[file.clone(), missing.clone()], false);
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(expanded.files, [file]);
+        assert_eq!(
+            expanded.errors,
+            [(missing, "no files match pattern".to_owned())]
+        );
+    }
+}
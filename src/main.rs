@@ -1,19 +1,64 @@
 use std::{
     collections::{HashMap, HashSet},
+    fmt::Write,
     path::PathBuf,
+    process::ExitCode,
 };
 
-use clap::{ArgGroup, Parser, Subcommand};
+use clap::{ArgGroup, CommandFactory, Parser, Subcommand};
+use colour::{Colour, Palette};
+use encode_cli::{CommonEncodeArgs, ContentArgs, EncodeFormat, LightningArgs, OtpAuthArgs};
+use encode_options::EncodeOptions;
+use error::CliError;
+use output::{JsonError, JsonResult, JsonSummary, OutputFormat};
 use rxing::{BarcodeFormat, MultiFormatWriter, Writer};
+use sizing::Sizing;
+use stdio::ImageFormat;
+use structured_append::Layout;
+
+mod aamva;
+mod bcbp;
+mod checksum;
+mod client_payload;
+mod colour;
+mod datamatrix;
+mod dcc;
+mod digital_link;
+mod encode_cli;
+mod encode_options;
+mod epc;
+mod error;
+mod gs1;
+mod inputs;
+mod otpauth;
+mod output;
+mod parsed;
+mod payment_uri;
+mod pool;
+mod shc;
+mod sizing;
+mod stdio;
+mod structured_append;
+mod swiss_qr_bill;
+mod units;
+mod uri;
+mod verify;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_precedence_over_arg = true)]
 struct Args {
-    file_name: String,
+    /// Image(s) to decode, or the file to write when encoding. When decoding, any number of
+    /// files, directories and shell-style glob patterns (e.g. 'scans/*.png') may be given.
+    /// Use '-' to decode an image read from stdin, or to write the encoded image to stdout.
+    #[arg(required = true, verbatim_doc_comment)]
+    file_names: Vec<String>,
     #[command(subcommand)]
     command: Commands,
 }
 
+// parsed once from the command line, so the size of the variants does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     #[command(group(
@@ -30,6 +75,18 @@ enum Commands {
         #[arg(short, long)]
         decode_multi: bool,
 
+        /// Descend into subdirectories of any directory (or directory matched by a glob) given as input.
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Number of files to decode in parallel, 0 uses one worker per CPU.
+        #[arg(short, long, default_value_t = 1)]
+        jobs: usize,
+
+        /// When decoding in parallel, print results as they finish instead of in input order.
+        #[arg(long)]
+        unordered: bool,
+
         /// Can be specified multiple times with different barcode formats, only listed formats are searched for.
         #[arg(short, long, value_enum)]
         barcode_types: Option<Vec<BarcodeFormat>>,
@@ -38,7 +95,9 @@ enum Commands {
         #[arg(long)]
         detailed_results: bool,
 
-        /// Print parsed results (exclusive with --detailed-results and --raw-bytes)
+        /// Print parsed results, the type of content (vCard, WiFi, calendar event, geo location...)
+        /// and its fields (exclusive with --detailed-results and --raw-bytes). With --output json
+        /// they are added to each result as a "parsed" object.
         #[arg(long)]
         parsed_results: bool,
 
@@ -46,6 +105,12 @@ enum Commands {
         #[arg(long)]
         raw_bytes: bool,
 
+        /// Output format for results. `json` ignores the other display options, apart from
+        /// --parsed-results, and writes a versioned JSON document per result (NDJSON with
+        /// --decode-multi).
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text, verbatim_doc_comment)]
+        output: OutputFormat,
+
         /// Unspecified, application-specific hint.
         #[arg(long)]
         other: Option<String>,
@@ -66,175 +131,135 @@ enum Commands {
         #[arg(long)]
         assume_code_39_check_digit: Option<bool>,
 
-        /// Assume the barcode is being processed as a GS1 barcode, and modify behavior as needed.
-        /// For example this affects FNC1 handling for Code 128 (aka GS1-128).
+        /// Split GS1 data into its application identifiers, e.g. (01) GTIN and (17) expiry, and
+        /// check their lengths, characters, check digits and dates. Implies --assume-gs1.
         #[arg(long, verbatim_doc_comment)]
-        assume_gs1: Option<bool>,
+        gs1: bool,
 
-        /// If true, return the start and end digits in a Codabar barcode instead of stripping them. They
-        /// are alpha, whereas the rest are numeric. By default, they are stripped, but this causes them
-        /// to not be.
+        /// Parse the text as the PDF417 of a US or Canadian driver licence or ID card (AAMVA
+        /// DL/ID standard): the header is validated and the subfiles are split into named fields
+        /// with dates as YYYY-MM-DD. With --output json they are added as an "aamva" object.
         #[arg(long, verbatim_doc_comment)]
-        return_codabar_start_end: Option<bool>,
+        aamva: bool,
 
-        /// Allowed extension lengths for EAN or UPC barcodes. Other formats will ignore this.
-        /// Maps to an {@code int[]} of the allowed extension lengths, for example [2], [5], or [2, 5].
-        /// If it is optional to have an extension, do not set this hint. If this is set,
-        /// and a UPC or EAN barcode is found but an extension is not, then no result will be returned
-        /// at all.
+        /// With --aamva, replace names, addresses, the date of birth, the licence number and
+        /// other personal data by [REDACTED], including in the text and raw bytes of the result.
+        #[arg(long, requires = "aamva", verbatim_doc_comment)]
+        redact_pii: bool,
+
+        /// Parse the text as an IATA bar coded boarding pass (BCBP): the passenger, every flight
+        /// leg with its date, and the security data, with a report of fields of the wrong size.
+        /// With --output json they are added as a "bcbp" object.
         #[arg(long, verbatim_doc_comment)]
-        allowed_ean_extensions: Option<Vec<u32>>,
+        bcbp: bool,
 
-        /// If true, also tries to decode as inverted image. All configured decoders are simply called a
-        /// second time with an inverted image.
+        /// Decode an EU Digital COVID Certificate (HC1: Base45, zlib, COSE_Sign1 and CBOR) and
+        /// show its header (kid, alg), claims and certificate. With --dcc-trust-list or --dcc-key
+        /// the signature is checked, and a signature that does not verify exits with code 8.
+        /// With --output json they are added as a "dcc" object.
         #[arg(long, verbatim_doc_comment)]
-        also_inverted: Option<bool>,
-    },
-    #[command(group(
-        ArgGroup::new("code_set_rules")
-        .required(false)
-        .args(["code_128_compact", "force_code_set"]),
-    ))]
-    #[command(group(
-        ArgGroup::new("data_source")
-        .required(true)
-        .args(["data", "data_file"]),
-    ))]
-    #[command(group(
-        ArgGroup::new("data_matrix_encoding")
-        .required(false)
-        .args(["data_matrix_compact","force_c40"]),
-    ))]
-    Encode {
-        barcode_type: BarcodeFormat,
-        #[arg(long)]
-        width: u32,
-        #[arg(long)]
-        height: u32,
+        dcc: bool,
 
-        /// String input for the encoder.
-        #[arg(short, long)]
-        data: Option<String>,
+        /// A JSON trust list of document signer keys for --dcc: an object of base64 key IDs to
+        /// PEM or base64 DER certificates or public keys, or an array of objects with "kid" and
+        /// "rawData".
+        #[arg(long, requires = "dcc", verbatim_doc_comment)]
+        dcc_trust_list: Option<PathBuf>,
 
-        /// A file containing the text to be encoded.
-        #[arg(long)]
-        data_file: Option<PathBuf>,
+        /// A PEM certificate or public key (P-256 or RSA) to check --dcc signatures with,
+        /// whatever their key ID. May be given more than once.
+        #[arg(long, requires = "dcc", verbatim_doc_comment)]
+        dcc_key: Vec<PathBuf>,
 
-        /// Specifies what degree of error correction to use, for example in QR Codes.
-        /// Type depends on the encoder. For example for QR codes it's (L,M,Q,H).
-        /// For Aztec it is of type u32, representing the minimal percentage of error correction words.
-        /// For PDF417 it is of type u8, valid values being 0 to 8.
-        /// Note: an Aztec symbol should have a minimum of 25% EC words.
+        /// Decode a SMART Health Card (shc:/ and numeric digits) into its JWS header, claims and
+        /// FHIR bundle. Cards chunked over several QR codes are put together from the results of
+        /// --decode-multi. With --shc-jwks the signature is checked, and a signature that does
+        /// not verify exits with code 8. With --output json they are added as a "shc" object.
         #[arg(long, verbatim_doc_comment)]
-        error_correction: Option<String>,
+        shc: bool,
 
-        /// Specifies what character encoding to use where applicable.
-        #[arg(long)]
-        character_set: Option<String>,
+        /// A JSON Web Key Set of issuer keys to check --shc signatures with, e.g. a saved copy
+        /// of the issuer's /.well-known/jwks.json.
+        #[arg(long, requires = "shc", verbatim_doc_comment)]
+        shc_jwks: Option<PathBuf>,
 
-        /// Specifies whether to use compact mode for Data Matrix.
-        /// The compact encoding mode also supports the encoding of characters that are not in the ISO-8859-1
-        /// character set via ECIs.
-        /// Please note that in that case, the most compact character encoding is chosen for characters in
-        /// the input that are not in the ISO-8859-1 character set. Based on experience, some scanners do not
-        /// support encodings like cp-1256 (Arabic). In such cases the encoding can be forced to UTF-8 by
-        /// means of the #CHARACTER_SET encoding hint.
-        /// Compact encoding also provides GS1-FNC1 support when #GS1_FORMAT is selected. In this case
-        /// group-separator character (ASCII 29 decimal) can be used to encode the positions of FNC1 codewords
-        /// for the purpose of delimiting AIs.
+        /// Parse the text as a Swiss QR-bill (SPC payload) and check it: the IBAN check digits
+        /// (mod 97), the QR or creditor reference check digits, field lengths and characters.
+        /// With --output json the bill is added as a "swiss_qr_bill" object.
         #[arg(long, verbatim_doc_comment)]
-        data_matrix_compact: Option<bool>,
+        swiss_qr_bill: bool,
 
-        /// Specifies margin, in pixels, to use when generating the barcode.
-        /// The meaning can vary
-        /// by format; for example it controls margin before and after the barcode horizontally for
-        /// most 1D formats.
+        /// Parse the text as an EPC QR code (GiroCode, service tag BCD) for a SEPA credit
+        /// transfer and report where it breaks EPC069-12: field lengths, characters, the IBAN
+        /// and BIC, and the amount. With --output json it is added as an "epc" object.
         #[arg(long, verbatim_doc_comment)]
-        margin: Option<String>,
-
-        /**
-         Specifies whether to use compact mode for PDF417.
-        */
-        #[arg(long)]
-        pdf_417_compact: Option<bool>,
-
-        /**
-         Specifies what compaction mode to use for PDF417
-         AUTO = 0,
-         TEXT = 1,
-         BYTE = 2,
-         NUMERIC = 3
-        */
-        #[arg(long)]
-        pdf_417_compaction: Option<String>,
+        epc: bool,
 
-        /// Specifies whether to automatically insert ECIs when encoding PDF417.
-        /// Please note that in that case, the most compact character encoding is chosen for characters in
-        /// the input that are not in the ISO-8859-1 character set. Based on experience, some scanners do not
-        /// support encodings like cp-1256 (Arabic). In such cases the encoding can be forced to UTF-8 by
-        /// means of the #CHARACTER_SET encoding hint.
+        /// Parse bitcoin: (BIP 21), lightning: and upi://pay URIs: the amount, label, message,
+        /// payee VPA and so on. Bitcoin addresses and Lightning invoices are checked against
+        /// their base58 or bech32 checksums. With --output json they are added as a
+        /// "payment_uri" object.
         #[arg(long, verbatim_doc_comment)]
-        pdf_417_auto_eci: Option<bool>,
+        payment_uri: bool,
 
-        /// Specifies the required number of layers for an Aztec code.
-        /// A negative number (-1, -2, -3, -4) specifies a compact Aztec code.
-        /// 0 indicates to use the minimum number of layers (the default).
-        /// A positive number (1, 2, .. 32) specifies a normal (non-compact) Aztec code.
+        /// Parse otpauth:// URIs of two factor authentication keys: the type, issuer, account,
+        /// secret, algorithm, digits and period or counter, and check them against the Key Uri
+        /// Format. With --output json they are added as an "otpauth" object.
         #[arg(long, verbatim_doc_comment)]
-        aztec_layers: Option<i32>,
+        otpauth: bool,
 
-        /**
-         Specifies the exact version of QR code to be encoded.
-        */
-        #[arg(long)]
-        qr_version: Option<String>,
+        /// With --otpauth, add the code the key shows now, or the code of its counter for HOTP.
+        #[arg(long, requires = "otpauth")]
+        otp_code: bool,
 
-        /// Specifies the QR code mask pattern to be used. Allowed values are
-        /// 0..8. By default the code will automatically select
-        /// the optimal mask pattern.
-        #[arg(long, verbatim_doc_comment)]
-        qr_mask_pattern: Option<String>,
+        /// With --otp-code, the time to compute the code for instead of now, in seconds since
+        /// the Unix epoch.
+        #[arg(long, requires = "otp_code", verbatim_doc_comment)]
+        otp_time: Option<u64>,
 
-        /// Specifies whether to use compact mode for QR code.
-        /// Please note that when compaction is performed, the most compact character encoding is chosen
-        /// for characters in the input that are not in the ISO-8859-1 character set. Based on experience,
-        /// some scanners do not support encodings like cp-1256 (Arabic). In such cases the encoding can
-        /// be forced to UTF-8 by means of the #CHARACTER_SET encoding hint.
+        /// Assume the barcode is being processed as a GS1 barcode, and modify behavior as needed.
+        /// For example this affects FNC1 handling for Code 128 (aka GS1-128).
         #[arg(long, verbatim_doc_comment)]
-        qr_compact: Option<bool>,
-
-        /**
-         Specifies whether the data should be encoded to the GS1 standard/
-        */
-        #[arg(long)]
-        gs1_format: Option<bool>,
+        assume_gs1: Option<bool>,
 
-        /// Forces which encoding will be used. Currently only used for Code-128 code sets.
-        /// Valid values are "A", "B", "C".
+        /// If true, return the start and end digits in a Codabar barcode instead of stripping them. They
+        /// are alpha, whereas the rest are numeric. By default, they are stripped, but this causes them
+        /// to not be.
         #[arg(long, verbatim_doc_comment)]
-        force_code_set: Option<String>,
+        return_codabar_start_end: Option<bool>,
 
-        /**
-         Forces C40 encoding for data-matrix. This
-        */
-        #[arg(long)]
-        force_c40: Option<bool>,
+        /// Allowed extension lengths for EAN or UPC barcodes. Other formats will ignore this.
+        /// Maps to an {@code int[]} of the allowed extension lengths, for example [2], [5], or [2, 5].
+        /// If it is optional to have an extension, do not set this hint. If this is set,
+        /// and a UPC or EAN barcode is found but an extension is not, then no result will be returned
+        /// at all.
+        #[arg(long, verbatim_doc_comment)]
+        allowed_ean_extensions: Option<Vec<u32>>,
 
-        /**
-         Specifies whether to use compact mode for Code-128 code.
-         This can yield slightly smaller bar codes.
-        */
-        #[arg(long)]
-        code_128_compact: Option<bool>,
+        /// If true, also tries to decode as inverted image. All configured decoders are simply called a
+        /// second time with an inverted image.
+        #[arg(long, verbatim_doc_comment)]
+        also_inverted: Option<bool>,
+    },
+    /// Encode data as a barcode, run `encode help <FORMAT>` for the options of each format.
+    Encode {
+        #[command(subcommand)]
+        barcode_type: EncodeFormat,
     },
 }
 
-fn main() {
-    let cli = Args::parse();
-    match &cli.command {
+fn main() -> ExitCode {
+    let cli = Args::parse_from(encode_cli::rewrite_legacy_args(
+        &Args::command(),
+        std::env::args_os().collect(),
+    ));
+    let result = match &cli.command {
         Commands::Decode {
             try_harder,
             decode_multi,
+            recursive,
+            jobs,
+            unordered,
             barcode_types,
             other,
             pure_barcode,
@@ -242,81 +267,87 @@ fn main() {
             allowed_lengths,
             assume_code_39_check_digit,
             assume_gs1,
+            gs1,
+            aamva,
+            redact_pii,
+            bcbp,
+            dcc,
+            dcc_trust_list,
+            dcc_key,
+            shc,
+            shc_jwks,
+            swiss_qr_bill,
+            epc,
+            payment_uri,
+            otpauth,
+            otp_code,
+            otp_time,
             return_codabar_start_end,
             allowed_ean_extensions,
             also_inverted,
             detailed_results,
             parsed_results,
             raw_bytes,
-        } => decode_command(
-            &cli.file_name,
-            try_harder,
-            decode_multi,
-            barcode_types,
-            other,
-            pure_barcode,
-            character_set,
-            allowed_lengths,
-            assume_code_39_check_digit,
-            assume_gs1,
-            return_codabar_start_end,
-            allowed_ean_extensions,
-            also_inverted,
-            detailed_results,
-            parsed_results,
-            raw_bytes,
-        ),
-        Commands::Encode {
-            barcode_type,
-            width,
-            height,
-            data,
-            data_file,
-            error_correction,
-            character_set,
-            data_matrix_compact,
-            margin,
-            pdf_417_compact,
-            pdf_417_compaction,
-            pdf_417_auto_eci,
-            aztec_layers,
-            qr_version,
-            qr_mask_pattern,
-            qr_compact,
-            gs1_format,
-            force_code_set,
-            force_c40,
-            code_128_compact,
-        } => encode_command(
-            &cli.file_name,
-            barcode_type,
-            width,
-            height,
-            data,
-            data_file,
-            error_correction,
-            character_set,
-            data_matrix_compact,
-            margin,
-            pdf_417_compact,
-            pdf_417_compaction,
-            pdf_417_auto_eci,
-            aztec_layers,
-            qr_version,
-            qr_mask_pattern,
-            qr_compact,
-            gs1_format,
-            force_code_set,
-            force_c40,
-            code_128_compact,
-        ),
+            output,
+        } => payload_keys(dcc_trust_list, dcc_key, shc_jwks).and_then(|(trust, jwks)| {
+            decode_command(
+                &cli.file_names,
+                try_harder,
+                decode_multi,
+                recursive,
+                jobs,
+                unordered,
+                barcode_types,
+                other,
+                pure_barcode,
+                character_set,
+                allowed_lengths,
+                assume_code_39_check_digit,
+                assume_gs1,
+                Payloads {
+                    gs1: *gs1,
+                    aamva: *aamva,
+                    redact_pii: *redact_pii,
+                    bcbp: *bcbp,
+                    dcc: dcc.then_some(&trust),
+                    shc: shc.then_some(&jwks),
+                    swiss_qr_bill: *swiss_qr_bill,
+                    epc: *epc,
+                    payment_uri: *payment_uri,
+                    otpauth: *otpauth,
+                    otp_time: otp_code.then(|| otp_time.unwrap_or_else(unix_time)),
+                },
+                return_codabar_start_end,
+                allowed_ean_extensions,
+                also_inverted,
+                detailed_results,
+                parsed_results,
+                raw_bytes,
+                output,
+            )
+        }),
+        Commands::Encode { barcode_type } => {
+            encode_command(single_file_name(&cli.file_names), barcode_type)
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            err.exit_code()
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn decode_command(
-    file_name: &str,
+    file_names: &[String],
     try_harder: &bool,
     decode_multi: &bool,
+    recursive: &bool,
+    jobs: &usize,
+    unordered: &bool,
     barcode_types: &Option<Vec<BarcodeFormat>>,
     other: &Option<String>,
     pure_barcode: &Option<bool>,
@@ -324,87 +355,256 @@ fn decode_command(
     allowed_lengths: &Option<Vec<u32>>,
     assume_code_39_check_digit: &Option<bool>,
     assume_gs1: &Option<bool>,
+    payloads: Payloads,
     return_codabar_start_end: &Option<bool>,
     allowed_ean_extensions: &Option<Vec<u32>>,
     also_inverted: &Option<bool>,
     detailed_result: &bool,
     parsed_bytes: &bool,
     raw_bytes: &bool,
-) {
-    let mut hints: rxing::DecodingHintDictionary = HashMap::new();
-    if let Some(other) = other {
-        hints.insert(
-            rxing::DecodeHintType::OTHER,
-            rxing::DecodeHintValue::Other(other.to_owned()),
-        );
-    }
-    if let Some(pure_barcode) = pure_barcode {
-        hints.insert(
-            rxing::DecodeHintType::PURE_BARCODE,
-            rxing::DecodeHintValue::PureBarcode(*pure_barcode),
-        );
-    }
-    if let Some(character_set) = character_set {
-        hints.insert(
-            rxing::DecodeHintType::CHARACTER_SET,
-            rxing::DecodeHintValue::CharacterSet(character_set.to_owned()),
-        );
-    }
-    if let Some(allowed_lengths) = allowed_lengths {
-        hints.insert(
-            rxing::DecodeHintType::ALLOWED_LENGTHS,
-            rxing::DecodeHintValue::AllowedLengths(allowed_lengths.to_vec()),
-        );
-    }
-    if let Some(assume_code_39_check_digit) = assume_code_39_check_digit {
-        hints.insert(
-            rxing::DecodeHintType::ASSUME_CODE_39_CHECK_DIGIT,
-            rxing::DecodeHintValue::AssumeCode39CheckDigit(*assume_code_39_check_digit),
-        );
-    }
-    if let Some(assume_gs1) = assume_gs1 {
-        hints.insert(
-            rxing::DecodeHintType::ASSUME_GS1,
-            rxing::DecodeHintValue::AssumeGs1(*assume_gs1),
-        );
-    }
-    if let Some(return_codabar_start_end) = return_codabar_start_end {
-        hints.insert(
-            rxing::DecodeHintType::RETURN_CODABAR_START_END,
-            rxing::DecodeHintValue::ReturnCodabarStartEnd(*return_codabar_start_end),
-        );
-    }
-    if let Some(allowed_ean_extensions) = allowed_ean_extensions {
-        hints.insert(
-            rxing::DecodeHintType::ALLOWED_EAN_EXTENSIONS,
-            rxing::DecodeHintValue::AllowedEanExtensions(allowed_ean_extensions.to_vec()),
-        );
-    }
-    if let Some(also_inverted) = also_inverted {
-        hints.insert(
-            rxing::DecodeHintType::ALSO_INVERTED,
-            rxing::DecodeHintValue::AlsoInverted(*also_inverted),
-        );
-    }
-
-    // println!(
-    //     "Decode '{}' with: try_harder: {}, decode_multi: {}, barcode_types: {:?}",
-    //     file_name, try_harder, decode_multi, barcode_types
-    // );
-
-    if !try_harder {
-        hints.insert(
-            rxing::DecodeHintType::TRY_HARDER,
-            rxing::DecodeHintValue::TryHarder(false),
-        );
-    }
-    if let Some(barcode_type) = barcode_types {
-        hints.insert(
-            rxing::DecodeHintType::POSSIBLE_FORMATS,
-            rxing::DecodeHintValue::PossibleFormats(HashSet::from_iter(
-                barcode_type.iter().copied(),
-            )),
-        );
+    output: &OutputFormat,
+) -> Result<(), CliError> {
+    let build_hints = || {
+        let mut hints: rxing::DecodingHintDictionary = HashMap::new();
+        if let Some(other) = other {
+            hints.insert(
+                rxing::DecodeHintType::OTHER,
+                rxing::DecodeHintValue::Other(other.to_owned()),
+            );
+        }
+        if let Some(pure_barcode) = pure_barcode {
+            hints.insert(
+                rxing::DecodeHintType::PURE_BARCODE,
+                rxing::DecodeHintValue::PureBarcode(*pure_barcode),
+            );
+        }
+        if let Some(character_set) = character_set {
+            hints.insert(
+                rxing::DecodeHintType::CHARACTER_SET,
+                rxing::DecodeHintValue::CharacterSet(character_set.to_owned()),
+            );
+        }
+        if let Some(allowed_lengths) = allowed_lengths {
+            hints.insert(
+                rxing::DecodeHintType::ALLOWED_LENGTHS,
+                rxing::DecodeHintValue::AllowedLengths(allowed_lengths.to_vec()),
+            );
+        }
+        if let Some(assume_code_39_check_digit) = assume_code_39_check_digit {
+            hints.insert(
+                rxing::DecodeHintType::ASSUME_CODE_39_CHECK_DIGIT,
+                rxing::DecodeHintValue::AssumeCode39CheckDigit(*assume_code_39_check_digit),
+            );
+        }
+        if let Some(assume_gs1) = assume_gs1.or(payloads.gs1.then_some(true)) {
+            hints.insert(
+                rxing::DecodeHintType::ASSUME_GS1,
+                rxing::DecodeHintValue::AssumeGs1(assume_gs1),
+            );
+        }
+        if let Some(return_codabar_start_end) = return_codabar_start_end {
+            hints.insert(
+                rxing::DecodeHintType::RETURN_CODABAR_START_END,
+                rxing::DecodeHintValue::ReturnCodabarStartEnd(*return_codabar_start_end),
+            );
+        }
+        if let Some(allowed_ean_extensions) = allowed_ean_extensions {
+            hints.insert(
+                rxing::DecodeHintType::ALLOWED_EAN_EXTENSIONS,
+                rxing::DecodeHintValue::AllowedEanExtensions(allowed_ean_extensions.to_vec()),
+            );
+        }
+        if let Some(also_inverted) = also_inverted {
+            hints.insert(
+                rxing::DecodeHintType::ALSO_INVERTED,
+                rxing::DecodeHintValue::AlsoInverted(*also_inverted),
+            );
+        }
+
+        // println!(
+        //     "Decode '{}' with: try_harder: {}, decode_multi: {}, barcode_types: {:?}",
+        //     file_name, try_harder, decode_multi, barcode_types
+        // );
+
+        if !try_harder {
+            hints.insert(
+                rxing::DecodeHintType::TRY_HARDER,
+                rxing::DecodeHintValue::TryHarder(false),
+            );
+        }
+        if let Some(barcode_type) = barcode_types {
+            hints.insert(
+                rxing::DecodeHintType::POSSIBLE_FORMATS,
+                rxing::DecodeHintValue::PossibleFormats(HashSet::from_iter(
+                    barcode_type.iter().copied(),
+                )),
+            );
+        }
+        hints
+    };
+
+    let inputs = inputs::expand_inputs(file_names, *recursive);
+    let mut decoded = 0;
+    let mut failed = 0;
+
+    let mut last_error = None;
+
+    for (input, reason) in &inputs.errors {
+        failed += 1;
+        let read_err = CliError::Io(format!("Error reading '{input}': {reason}"));
+        if *output == OutputFormat::Json {
+            println!(
+                "{}",
+                output::to_json_line(&JsonError::from_message(input, "io", reason))
+            );
+        }
+        if inputs.is_batch {
+            eprintln!("{read_err}");
+        }
+        last_error = Some(read_err);
+    }
+
+    let decode = |file_name: &String| {
+        decode_file(
+            file_name,
+            &mut build_hints(),
+            *decode_multi,
+            *detailed_result,
+            *parsed_bytes,
+            *raw_bytes,
+            payloads,
+            *output,
+            inputs.is_batch,
+        )
+    };
+    let tally = |outcome: DecodeOutcome| {
+        print!("{}", outcome.output);
+        match outcome.error {
+            None => decoded += 1,
+            Some(decode_err) => {
+                failed += 1;
+                // a single input's error is reported once, on exit
+                if inputs.is_batch {
+                    eprintln!("{decode_err}");
+                }
+                last_error = Some(decode_err);
+            }
+        }
+    };
+    let jobs = if *jobs == 0 {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        *jobs
+    };
+    pool::run(&inputs.files, jobs, !*unordered, decode, tally);
+
+    if inputs.is_batch {
+        let found = inputs.files.len();
+        if *output == OutputFormat::Json {
+            println!(
+                "{}",
+                output::to_json_line(&JsonSummary::new(found, decoded, failed))
+            );
+        } else {
+            println!("Summary: {found} files found, {decoded} decoded, {failed} failed");
+        }
+    }
+
+    match last_error {
+        None => Ok(()),
+        Some(_) if inputs.is_batch && decoded > 0 => {
+            Err(CliError::PartialBatchFailure { decoded, failed })
+        }
+        Some(_) if inputs.is_batch => Err(CliError::NotFound(format!(
+            "No barcodes were decoded from {failed} inputs"
+        ))),
+        Some(single_err) => Err(single_err),
+    }
+}
+
+/// The payload formats the decoded text is interpreted as, on top of the plain result.
+#[derive(Clone, Copy)]
+struct Payloads<'a> {
+    gs1: bool,
+    aamva: bool,
+    redact_pii: bool,
+    bcbp: bool,
+    /// The keys to check certificate signatures with, with --dcc.
+    dcc: Option<&'a dcc::TrustStore>,
+    /// The issuer keys to check health card signatures with, with --shc.
+    shc: Option<&'a shc::KeySet>,
+    swiss_qr_bill: bool,
+    epc: bool,
+    payment_uri: bool,
+    otpauth: bool,
+    /// The time to add the code of an otpauth key for, with --otp-code.
+    otp_time: Option<u64>,
+}
+
+/// The keys given with --dcc-trust-list, --dcc-key and --shc-jwks, read once for all inputs.
+fn payload_keys(
+    trust_list: &Option<PathBuf>,
+    keys: &[PathBuf],
+    jwks: &Option<PathBuf>,
+) -> Result<(dcc::TrustStore, shc::KeySet), CliError> {
+    let mut trust = dcc::TrustStore::default();
+    if let Some(trust_list) = trust_list {
+        trust.add_trust_list(trust_list)?;
+    }
+    for key in keys {
+        trust.add_key(key)?;
+    }
+    let jwks = match jwks {
+        Some(jwks) => shc::KeySet::load(jwks)?,
+        None => shc::KeySet::default(),
+    };
+    Ok((trust, jwks))
+}
+
+/// The signed credentials found in one result, kept so that a signature that does not verify
+/// fails the decode.
+#[derive(Default)]
+struct Credentials {
+    dcc: Option<dcc::Dcc>,
+    shc: Option<shc::Shc>,
+}
+
+impl Credentials {
+    /// Why the signature of a credential did not verify, if it did not.
+    fn unverified(&self) -> Option<String> {
+        let dcc = self.dcc.as_ref().filter(|dcc| dcc.failed_verification());
+        let shc = self.shc.as_ref().filter(|shc| shc.failed_verification());
+        dcc.map(|dcc| (dcc.signature, &dcc.errors))
+            .or(shc.map(|shc| (shc.signature, &shc.errors)))
+            .map(|(signature, errors)| {
+                format!("did not verify ({signature}): {}", errors.join(", "))
+            })
+    }
+}
+
+/// The rendered output of decoding one file, and why it failed if it did.
+struct DecodeOutcome {
+    output: String,
+    error: Option<CliError>,
+}
+
+/// Decode a single file, rendering its results rather than printing them so that they can be
+/// written in order when decoding in parallel.
+#[allow(clippy::too_many_arguments)]
+fn decode_file(
+    file_name: &str,
+    hints: &mut rxing::DecodingHintDictionary,
+    decode_multi: bool,
+    detailed_result: bool,
+    parsed_bytes: bool,
+    raw_bytes: bool,
+    payloads: Payloads,
+    output: OutputFormat,
+    tag_file: bool,
+) -> DecodeOutcome {
+    let mut out = String::new();
+    if tag_file && output == OutputFormat::Text {
+        writeln!(out, "File: '{file_name}'").unwrap();
     }
 
     let path = PathBuf::from(file_name);
@@ -414,74 +614,196 @@ fn decode_command(
         String::default()
     };
 
-    if *decode_multi {
-        let results = if extension == "svg" {
-            rxing::helpers::detect_multiple_in_svg_with_hints(file_name, &mut hints)
-        } else {
-            rxing::helpers::detect_multiple_in_file_with_hints(file_name, &mut hints)
-        };
-        match results {
-            Ok(result_array) => {
-                println!("Found {} results", result_array.len());
-                for (i, result) in result_array.into_iter().enumerate() {
-                    println!(
-                        "Result {}:\n{}",
-                        i,
-                        print_result(&result, *detailed_result, *raw_bytes, *parsed_bytes)
-                    );
+    let stdin_data = if file_name == stdio::STDIO {
+        match stdio::read_stdin() {
+            Ok(data) => Some(data),
+            Err(read_err) => {
+                if output == OutputFormat::Json {
+                    writeln!(
+                        out,
+                        "{}",
+                        output::to_json_line(&JsonError::new(file_name, &read_err))
+                    )
+                    .unwrap();
                 }
-            }
-            Err(search_err) => {
-                println!(
-                    "Error while attempting to locate multiple barcodes in '{file_name}': {search_err}"
-                );
+                return DecodeOutcome {
+                    output: out,
+                    error: Some(CliError::Io(read_err.to_string())),
+                };
             }
         }
     } else {
-        let result = if extension == "svg" {
-            rxing::helpers::detect_in_svg_with_hints(file_name, None, &mut hints)
+        None
+    };
+
+    let results = if decode_multi {
+        if let Some(data) = &stdin_data {
+            stdio::detect_multiple_in_bytes_with_hints(data, hints)
+        } else if extension == "svg" {
+            rxing::helpers::detect_multiple_in_svg_with_hints(file_name, hints)
         } else {
-            rxing::helpers::detect_in_file_with_hints(file_name, None, &mut hints)
-        };
-        match result {
-            Ok(result) => {
-                println!(
-                    "Detection result: \n{}",
-                    print_result(&result, *detailed_result, *raw_bytes, *parsed_bytes)
-                );
+            rxing::helpers::detect_multiple_in_file_with_hints(file_name, hints)
+        }
+    } else {
+        if let Some(data) = &stdin_data {
+            stdio::detect_in_bytes_with_hints(data, hints)
+        } else if extension == "svg" {
+            rxing::helpers::detect_in_svg_with_hints(file_name, None, hints)
+        } else {
+            rxing::helpers::detect_in_file_with_hints(file_name, None, hints)
+        }
+        .map(|result| vec![result])
+    };
+
+    let results = match results {
+        Ok(results) => results,
+        Err(search_err) => {
+            if output == OutputFormat::Json {
+                writeln!(
+                    out,
+                    "{}",
+                    output::to_json_line(&JsonError::new(file_name, &search_err))
+                )
+                .unwrap();
+            }
+            return DecodeOutcome {
+                output: out,
+                error: Some(CliError::from_decode_error(
+                    file_name,
+                    decode_multi,
+                    search_err,
+                )),
+            };
+        }
+    };
+
+    let texts: Vec<&str> = results.iter().map(|result| result.getText()).collect();
+    let mut shcs = payloads
+        .shc
+        .map(|keys| shc::decode_all(&texts, keys))
+        .unwrap_or_default()
+        .into_iter();
+    let credentials: Vec<Credentials> = texts
+        .iter()
+        .map(|text| Credentials {
+            dcc: payloads.dcc.map(|trust| dcc::decode(text, trust)),
+            shc: shcs.next().flatten(),
+        })
+        .collect();
+
+    if output == OutputFormat::Json {
+        for (i, result) in results.iter().enumerate() {
+            let mut json = JsonResult::new(file_name, decode_multi.then_some(i), result);
+            if parsed_bytes {
+                json.parsed = Some(parsed::parsed_result(result));
+            }
+            json.gs1 = gs1_data(result.getText(), payloads.gs1, parsed_bytes);
+            if payloads.aamva {
+                json.aamva = Some(aamva::parse(result.getText(), payloads.redact_pii));
             }
-            Err(search_err) => {
-                println!(
-                    "Error while attempting to locate barcode in '{file_name}': {search_err}"
-                );
+            if payloads.bcbp {
+                json.bcbp = Some(bcbp::parse(result.getText(), today()));
             }
+            if payloads.swiss_qr_bill {
+                json.swiss_qr_bill = Some(swiss_qr_bill::parse(result.getText()));
+            }
+            if payloads.epc {
+                json.epc = Some(epc::parse(result.getText()));
+            }
+            if payloads.payment_uri {
+                json.payment_uri = payment_uri::parse(result.getText());
+            }
+            if payloads.otpauth {
+                json.otpauth = otpauth::parse(result.getText(), payloads.otp_time);
+            }
+            json.dcc = credentials[i].dcc.clone();
+            json.shc = credentials[i].shc.clone();
+            if payloads.redact_pii {
+                json.redact();
+            }
+            writeln!(out, "{}", output::to_json_line(&json)).unwrap();
+        }
+    } else if decode_multi {
+        writeln!(out, "Found {} results", results.len()).unwrap();
+        for (i, result) in results.iter().enumerate() {
+            writeln!(
+                out,
+                "Result {}:\n{}",
+                i,
+                print_decoded(
+                    result,
+                    detailed_result,
+                    raw_bytes,
+                    parsed_bytes,
+                    payloads,
+                    &credentials[i]
+                )
+            )
+            .unwrap();
         }
+    } else {
+        writeln!(
+            out,
+            "Detection result: \n{}",
+            print_decoded(
+                &results[0],
+                detailed_result,
+                raw_bytes,
+                parsed_bytes,
+                payloads,
+                &credentials[0]
+            )
+        )
+        .unwrap();
+    }
+
+    let error = if results.is_empty() {
+        Some(CliError::NotFound(format!(
+            "No barcodes found in '{file_name}'"
+        )))
+    } else {
+        credentials
+            .iter()
+            .find_map(Credentials::unverified)
+            .map(|reason| {
+                CliError::VerifyFailure(format!("The signature in '{file_name}' {reason}"))
+            })
+    };
+    DecodeOutcome { output: out, error }
+}
+
+/// `encode` writes a single image, so it only accepts a single file name.
+fn single_file_name(file_names: &[String]) -> &str {
+    if file_names.len() != 1 {
+        Args::command()
+            .error(
+                clap::error::ErrorKind::TooManyValues,
+                "encode writes exactly one output file",
+            )
+            .exit();
     }
+    &file_names[0]
 }
 
-fn encode_command(
-    file_name: &str,
-    barcode_type: &BarcodeFormat,
-    width: &u32,
-    height: &u32,
-    data: &Option<String>,
-    data_file: &Option<PathBuf>,
-    error_correction: &Option<String>,
-    character_set: &Option<String>,
-    data_matrix_compact: &Option<bool>,
-    margin: &Option<String>,
-    pdf_417_compact: &Option<bool>,
-    pdf_417_compaction: &Option<String>,
-    pdf_417_auto_eci: &Option<bool>,
-    aztec_layers: &Option<i32>,
-    qr_version: &Option<String>,
-    qr_mask_pattern: &Option<String>,
-    qr_compact: &Option<bool>,
-    gs1_format: &Option<bool>,
-    force_code_set: &Option<String>,
-    force_c40: &Option<bool>,
-    code_128_compact: &Option<bool>,
-) {
+fn encode_command(file_name: &str, barcode_type: &EncodeFormat) -> Result<(), CliError> {
+    let CommonEncodeArgs {
+        width,
+        height,
+        module_size,
+        x_dimension,
+        dpi,
+        quiet_zone,
+        margin_pixels,
+        foreground,
+        background,
+        transparent,
+        verify,
+        data,
+        data_file,
+        image_format,
+    } = barcode_type.common();
+    let format = barcode_type.barcode_format();
+
     // if data.is_none() && data_file.is_none() {
     //     println!("must provide either data string or data file");
     //     return;
@@ -491,151 +813,457 @@ fn encode_command(
     //     return;
     // }
 
-    let input_data = if let Some(path_from) = data_file {
+    let input_data = if let Some(payload) = built_payload(barcode_type)? {
+        payload
+    } else if let Some(path_from) = data_file {
         if path_from.exists() {
             let Ok(fl) = std::fs::File::open(path_from) else {
-                println!("file cannot be opened");
-                return;
+                return Err(CliError::Io(format!(
+                    "{} cannot be opened",
+                    path_from.to_string_lossy()
+                )));
             };
-            std::io::read_to_string(fl).expect("file should read")
+            std::io::read_to_string(fl).map_err(|e| {
+                CliError::Io(format!(
+                    "{} cannot be read: {e}",
+                    path_from.to_string_lossy()
+                ))
+            })?
         } else {
-            println!("{} does not exist", path_from.to_string_lossy());
-            return;
+            return Err(CliError::Io(format!(
+                "{} does not exist",
+                path_from.to_string_lossy()
+            )));
         }
     } else if let Some(ds) = data {
         ds.to_owned()
     } else {
-        println!("Unknown error getting data");
-        return;
+        return Err(CliError::InvalidHint(
+            "Unknown error getting data".to_owned(),
+        ));
     };
 
-    let mut hints: rxing::EncodingHintDictionary = HashMap::new();
-
-    if let Some(ec) = error_correction {
-        hints.insert(
-            rxing::EncodeHintType::ERROR_CORRECTION,
-            rxing::EncodeHintValue::ErrorCorrection(ec.to_owned()),
-        );
+    // a ready made payload is checked like a built one
+    match barcode_type {
+        EncodeFormat::SwissQrBill(_) => swiss_qr_bill::validate(&input_data)?,
+        EncodeFormat::Epc(_) => epc::validate(&input_data)?,
+        EncodeFormat::Bitcoin(_) | EncodeFormat::Lightning(_) | EncodeFormat::Upi(_) => {
+            payment_uri::validate(&input_data)?
+        }
+        EncodeFormat::OtpAuth(_) => otpauth::validate(&input_data)?,
+        _ => {}
     }
 
-    if let Some(character_set) = character_set {
-        hints.insert(
-            rxing::EncodeHintType::CHARACTER_SET,
-            rxing::EncodeHintValue::CharacterSet(character_set.to_owned()),
-        );
+    if file_name == stdio::STDIO && image_format.is_none() {
+        return Err(CliError::InvalidHint(
+            "--image-format is required when writing to stdout".to_owned(),
+        ));
     }
-
-    if let Some(data_matrix_compact) = data_matrix_compact {
-        hints.insert(
-            rxing::EncodeHintType::DATA_MATRIX_COMPACT,
-            rxing::EncodeHintValue::DataMatrixCompact(*data_matrix_compact),
-        );
+    let structured_append = barcode_type.structured_append();
+    if file_name == stdio::STDIO
+        && structured_append.is_some_and(|(_, layout)| layout == Layout::Files)
+    {
+        return Err(CliError::InvalidHint(
+            "--structured-append writes one file per symbol, use --structured-append-layout row or column to write to stdout".to_owned(),
+        ));
     }
 
-    if let Some(margin) = margin {
-        hints.insert(
-            rxing::EncodeHintType::MARGIN,
-            rxing::EncodeHintValue::Margin(margin.to_owned()),
-        );
+    let palette = Palette {
+        foreground: *foreground,
+        background: if *transparent {
+            Colour {
+                a: 0,
+                ..*background
+            }
+        } else {
+            *background
+        },
+    };
+    for warning in palette.check_contrast()? {
+        eprintln!("{warning}");
     }
 
-    if let Some(pdf_417_compact) = pdf_417_compact {
-        hints.insert(
-            rxing::EncodeHintType::PDF417_COMPACT,
-            rxing::EncodeHintValue::Pdf417Compact(pdf_417_compact.to_string()),
-        );
-    }
+    // stdout carries the image itself when writing to '-'
+    let status = |message: String| {
+        if file_name == stdio::STDIO {
+            eprintln!("{message}");
+        } else {
+            println!("{message}");
+        }
+    };
 
-    if let Some(pdf_417_compaction) = pdf_417_compaction {
-        hints.insert(
-            rxing::EncodeHintType::PDF417_COMPACTION,
-            rxing::EncodeHintValue::Pdf417Compaction(pdf_417_compaction.to_owned()),
-        );
+    // a generated secret has to be stored by the server as well
+    if let EncodeFormat::OtpAuth(OtpAuthArgs {
+        account: Some(_),
+        secret: None,
+        ..
+    }) = barcode_type
+    {
+        if let Some(otp) = otpauth::parse(&input_data, None) {
+            status(format!("Secret: {}", otp.key.secret));
+        }
     }
 
-    if let Some(pdf_417_auto_eci) = pdf_417_auto_eci {
-        hints.insert(
-            rxing::EncodeHintType::PDF417_AUTO_ECI,
-            rxing::EncodeHintValue::Pdf417AutoEci(pdf_417_auto_eci.to_string()),
-        );
-    }
+    // the text handed to the writer, the text a reader returns for it, and whether that is
+    // a GS1 element string
+    let (contents, input_data, is_gs1) = match barcode_type.gs1_input() {
+        Some(gs1_input) => {
+            let element_string = gs1::parse_hri(input_data.trim(), gs1_input.gs1_check_digit)
+                .map_err(|e| CliError::InvalidHint(format!("Invalid GS1 data, {e}")))?;
+            let errors = element_string.error_lines();
+            if !errors.is_empty() {
+                return Err(CliError::InvalidHint(format!(
+                    "Invalid GS1 data {}:\n  {}",
+                    element_string.hri,
+                    errors.join("\n  ")
+                )));
+            }
+            if let Some(link) = barcode_type.digital_link() {
+                let uri = digital_link::to_uri(
+                    &element_string,
+                    link.resolver
+                        .as_deref()
+                        .unwrap_or(digital_link::DEFAULT_RESOLVER),
+                    link.compress,
+                )
+                .map_err(|e| CliError::InvalidHint(format!("Invalid GS1 Digital Link, {e}")))?;
+                status(format!("Digital Link: {uri}"));
+                (uri.clone(), uri, false)
+            } else {
+                let data = element_string.encode(gs1::GROUP_SEPARATOR);
+                let contents = match format {
+                    BarcodeFormat::CODE_128 => format!(
+                        "{}{}",
+                        gs1::CODE_128_FNC1,
+                        element_string.encode(gs1::CODE_128_FNC1)
+                    ),
+                    _ => data.clone(),
+                };
+                (contents, data, true)
+            }
+        }
+        None => (input_data.clone(), input_data, false),
+    };
 
-    if let Some(aztec_layers) = aztec_layers {
-        hints.insert(
-            rxing::EncodeHintType::AZTEC_LAYERS,
-            rxing::EncodeHintValue::AztecLayers(*aztec_layers),
-        );
+    let (options, warnings) = EncodeOptions::parse(&format, &barcode_type.raw_options())?;
+    for warning in warnings {
+        eprintln!("{warning}");
     }
+    let hints = options.hints();
 
-    if let Some(qr_version) = qr_version {
-        hints.insert(
-            rxing::EncodeHintType::QR_VERSION,
-            rxing::EncodeHintValue::QrVersion(qr_version.to_owned()),
-        );
-    }
+    // println!("Encode: file_name: {}, barcode_type: {}, width: {:?}, height: {:?}, data: '{:?}', data_file: {:?}", file_name, barcode_type, width, height, data, data_file);
+
+    // encode at one pixel per module, the symbol is then scaled to whole pixels per module
+    let symbols = if let Some((count, _)) = structured_append {
+        structured_append::encode(&contents, count, &options)?
+    } else {
+        let matrix = if is_gs1 && format == BarcodeFormat::DATA_MATRIX {
+            if let EncodeFormat::DataMatrix(args) = barcode_type {
+                if args.character_set.is_some()
+                    || args.data_matrix_compact.is_some()
+                    || args.force_c40.is_some()
+                {
+                    eprintln!("Warning, --gs1 data is always encoded in ASCII encodation, the DATAMATRIX encoding flags are ignored");
+                }
+            }
+            datamatrix::encode_gs1(&contents)?
+        } else {
+            MultiFormatWriter
+                .encode_with_hints(&contents, &format, 0, 0, &hints)
+                .map_err(CliError::from_encode_error)?
+        };
+        vec![structured_append::Symbol {
+            matrix,
+            text: input_data,
+        }]
+    };
+    let module_size = match (x_dimension, dpi) {
+        (Some(x_dimension), Some(dpi)) => {
+            let (dots, warning) = units::x_dimension_dots(*x_dimension, *dpi);
+            if let Some(warning) = warning {
+                eprintln!("{warning}");
+            }
+            Some(dots)
+        }
+        _ => *module_size,
+    };
+    // a legacy --margin in pixels takes the place of the quiet zone, outside the bounds
+    let margin = margin_pixels.unwrap_or_default();
+    let bound = |length: &Option<units::Length>, flag: &str| {
+        length
+            .map(|length| length.to_pixels(flag, *dpi))
+            .transpose()
+            .map(|pixels| pixels.map(|pixels| pixels.saturating_sub(2 * margin)))
+    };
+    let sizing = Sizing {
+        module_size,
+        quiet_zone: margin_pixels.map_or(*quiet_zone, |_| Some(0)),
+        width: bound(width, "width")?,
+        height: bound(height, "height")?,
+    };
+    let quiet_zone = sizing
+        .quiet_zone
+        .unwrap_or_else(|| sizing::default_quiet_zone(&format));
 
-    if let Some(qr_mask_pattern) = qr_mask_pattern {
-        hints.insert(
-            rxing::EncodeHintType::QR_MASK_PATTERN,
-            rxing::EncodeHintValue::QrMaskPattern(qr_mask_pattern.to_owned()),
-        );
+    // the file name and image of every symbol, or of all symbols laid out together
+    let mut images = match structured_append {
+        Some((_, Layout::Files)) | None => symbols
+            .iter()
+            .enumerate()
+            .map(|(position, symbol)| {
+                let name = if symbols.len() > 1 {
+                    structured_append::numbered_file_name(file_name, position + 1, symbols.len())
+                } else {
+                    file_name.to_owned()
+                };
+                Ok((name, sizing.layout(&format, &symbol.matrix)?))
+            })
+            .collect::<Result<Vec<_>, CliError>>()?,
+        Some((_, layout)) => {
+            let combined = structured_append::combine(&symbols, layout, quiet_zone)?;
+            vec![(file_name.to_owned(), sizing.layout(&format, &combined)?)]
+        }
+    };
+    if let EncodeFormat::SwissQrBill(_) = barcode_type {
+        let symbol = &symbols[0].matrix;
+        if symbol.getWidth() > swiss_qr_bill::MAX_MODULES {
+            return Err(CliError::InvalidHint(format!(
+                "The Swiss QR-bill needs a {} module symbol, the largest allowed is version 25 of {} modules",
+                symbol.getWidth(),
+                swiss_qr_bill::MAX_MODULES
+            )));
+        }
+        let result = &mut images[0].1;
+        let module_pixels = result.getWidth() / (symbol.getWidth() + 2 * quiet_zone);
+        swiss_qr_bill::draw_swiss_cross(result, symbol.getWidth() * module_pixels);
+    }
+    if margin > 0 {
+        for (_, image) in &mut images {
+            *image = sizing::add_margin(&format, image, margin)?;
+        }
     }
 
-    if let Some(qr_compact) = qr_compact {
-        println!("Warning, QRCompact can generate unreadable barcodes");
-        hints.insert(
-            rxing::EncodeHintType::QR_COMPACT,
-            rxing::EncodeHintValue::QrCompact(qr_compact.to_string()),
-        );
+    let written_format = image_format.or_else(|| ImageFormat::from_path(file_name));
+    if dpi.is_some() && !written_format.is_some_and(ImageFormat::stores_resolution) {
+        eprintln!("Warning, the resolution is only stored in PNG, JPEG and SVG files");
+    }
+    if !palette.is_default() && !written_format.is_some_and(ImageFormat::supports_colour) {
+        eprintln!("Warning, colours are ignored for this image format");
+    } else if palette.has_transparency()
+        && !written_format.is_some_and(ImageFormat::supports_transparency)
+    {
+        eprintln!("Warning, this image format has no transparency, it is rendered opaque");
+    }
+    if *verify {
+        if images.len() == symbols.len() {
+            for ((_, image), symbol) in images.iter().zip(&symbols) {
+                verify::verify(
+                    &format,
+                    image,
+                    written_format,
+                    &palette,
+                    &symbol.text,
+                    options.character_set.as_deref(),
+                    is_gs1,
+                )?;
+            }
+        } else {
+            // symbols laid out together are read one at a time, at the same module size
+            let (_, combined) = &images[0];
+            let across = combined.getWidth().min(combined.getHeight()) - 2 * margin;
+            let single = Sizing {
+                module_size: Some(across / (symbols[0].matrix.getWidth() + 2 * quiet_zone)),
+                quiet_zone: Some(quiet_zone),
+                width: None,
+                height: None,
+            };
+            for symbol in &symbols {
+                let image = single.layout(&format, &symbol.matrix)?;
+                verify::verify(
+                    &format,
+                    &image,
+                    written_format,
+                    &palette,
+                    &symbol.text,
+                    options.character_set.as_deref(),
+                    is_gs1,
+                )?;
+            }
+        }
+        if symbols.len() > 1 {
+            status(format!(
+                "Verified, each of the {} symbols decodes to its part of the input",
+                symbols.len()
+            ));
+        } else {
+            status("Verified, the barcode decodes to the input".to_owned());
+        }
     }
+    status("Encode successful, saving...".to_owned());
+    for (name, image) in &images {
+        stdio::save(name, image, *image_format, *dpi, &palette)
+            .map_err(|error| CliError::Io(format!("Could not save '{name}': {error}")))?;
+        status(format!("Saved to '{name}'"));
+    }
+    Ok(())
+}
 
-    if let Some(gs1_format) = gs1_format {
-        hints.insert(
-            rxing::EncodeHintType::GS1_FORMAT,
-            rxing::EncodeHintValue::Gs1Format(*gs1_format),
-        );
+/// The payload of the formats that build it from their own flags or file instead of --data,
+/// or `None` to read --data or --data-file.
+fn built_payload(barcode_type: &EncodeFormat) -> Result<Option<String>, CliError> {
+    match barcode_type {
+        EncodeFormat::QrCode(args) => content_payload(&args.content),
+        EncodeFormat::SwissQrBill(args) => {
+            let bill = if let Some(bill_file) = &args.bill_file {
+                let name = bill_file.to_string_lossy();
+                let json = std::fs::read_to_string(bill_file)
+                    .map_err(|e| CliError::Io(format!("{name} cannot be read: {e}")))?;
+                serde_json::from_str(&json).map_err(|e| {
+                    CliError::InvalidHint(format!("{name} is not a Swiss QR-bill JSON file: {e}"))
+                })?
+            } else if args.iban.is_some() {
+                args.bill()
+            } else {
+                return Ok(None);
+            };
+            swiss_qr_bill::build(bill).map(Some)
+        }
+        EncodeFormat::Epc(args) if args.iban.is_some() => epc::build(args.transfer()).map(Some),
+        EncodeFormat::Bitcoin(args) if args.address.is_some() => {
+            payment_uri::build_bitcoin(args.request()).map(Some)
+        }
+        EncodeFormat::Lightning(LightningArgs {
+            invoice: Some(invoice),
+            ..
+        }) => payment_uri::build_lightning(invoice).map(Some),
+        EncodeFormat::Upi(args) if args.vpa.is_some() => {
+            payment_uri::build_upi(args.payment()).map(Some)
+        }
+        EncodeFormat::OtpAuth(args) if args.account.is_some() => {
+            let secret = match &args.secret {
+                Some(secret) => secret.clone(),
+                None => otpauth::generate_secret(&args.algorithm)?,
+            };
+            otpauth::build(args.key(secret)).map(Some)
+        }
+        _ => Ok(None),
     }
+}
 
-    if let Some(force_code_set) = force_code_set {
-        hints.insert(
-            rxing::EncodeHintType::FORCE_CODE_SET,
-            rxing::EncodeHintValue::ForceCodeSet(force_code_set.to_owned()),
-        );
+/// The payload of the WiFi, contact, SMS, email, location or event flags of `encode qrcode`.
+fn content_payload(args: &ContentArgs) -> Result<Option<String>, CliError> {
+    if let Some(wifi) = args.wifi() {
+        return wifi.payload().map(Some);
+    }
+    if let Some(path) = args.vcard_file.as_ref().or(args.mecard_file.as_ref()) {
+        let name = path.to_string_lossy();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| CliError::Io(format!("{name} cannot be read: {e}")))?;
+        let contact: client_payload::Contact = serde_json::from_str(&json).map_err(|e| {
+            CliError::InvalidHint(format!("{name} is not a contact JSON file: {e}"))
+        })?;
+        return if args.vcard_file.is_some() {
+            contact.vcard()
+        } else {
+            contact.mecard()
+        }
+        .map(Some);
+    }
+    if let Some(number) = &args.sms_number {
+        return client_payload::sms(number, args.sms_body.as_deref()).map(Some);
+    }
+    if let Some(email) = args.email() {
+        return email.payload().map(Some);
     }
+    if let (Some(latitude), Some(longitude)) = (args.geo_latitude, args.geo_longitude) {
+        return client_payload::geo(
+            latitude,
+            longitude,
+            args.geo_altitude,
+            args.geo_query.as_deref(),
+        )
+        .map(Some);
+    }
+    args.event().map(|event| event.payload()).transpose()
+}
 
-    if let Some(force_c40) = force_c40 {
-        hints.insert(
-            rxing::EncodeHintType::FORCE_C40,
-            rxing::EncodeHintValue::ForceC40(*force_c40),
-        );
+/// The GS1 data of a decoded `text`. Digital Link URIs are expanded with --gs1 and with
+/// --parsed-results, other text is split as an element string with --gs1 only.
+fn gs1_data(text: &str, gs1: bool, parsed: bool) -> Option<gs1::ElementString> {
+    if gs1 || parsed {
+        if let Some(expanded) = digital_link::parse(text) {
+            return Some(expanded);
+        }
     }
+    gs1.then(|| gs1::parse_element_string(text))
+}
 
-    if let Some(code_128_compact) = code_128_compact {
-        hints.insert(
-            rxing::EncodeHintType::CODE128_COMPACT,
-            rxing::EncodeHintValue::Code128Compact(*code_128_compact),
-        );
+/// The text output of one result, followed by the payloads found in it.
+fn print_decoded(
+    result: &rxing::RXingResult,
+    detailed: bool,
+    raw: bool,
+    parsed: bool,
+    payloads: Payloads,
+    credentials: &Credentials,
+) -> String {
+    let mut text = if payloads.redact_pii {
+        format!("({}) {}", result.getBarcodeFormat(), aamva::REDACTED)
+    } else {
+        print_result(result, detailed, raw, parsed)
+    };
+    if let Some(gs1_data) = gs1_data(result.getText(), payloads.gs1, parsed) {
+        text.push_str(&format!("\n{}", gs1_data.to_text()));
+    }
+    if payloads.aamva {
+        let aamva = aamva::parse(result.getText(), payloads.redact_pii);
+        text.push_str(&format!("\n{}", aamva.to_text()));
+    }
+    if payloads.bcbp {
+        let bcbp = bcbp::parse(result.getText(), today());
+        text.push_str(&format!("\n{}", bcbp.to_text()));
+    }
+    if payloads.swiss_qr_bill {
+        let bill = swiss_qr_bill::parse(result.getText());
+        text.push_str(&format!("\n{}", bill.to_text()));
+    }
+    if payloads.epc {
+        let epc = epc::parse(result.getText());
+        text.push_str(&format!("\n{}", epc.to_text()));
+    }
+    if let Some(payment) = payloads
+        .payment_uri
+        .then(|| payment_uri::parse(result.getText()))
+        .flatten()
+    {
+        text.push_str(&format!("\n{}", payment.to_text()));
+    }
+    if let Some(otp) = payloads
+        .otpauth
+        .then(|| otpauth::parse(result.getText(), payloads.otp_time))
+        .flatten()
+    {
+        text.push_str(&format!("\n{}", otp.to_text()));
     }
+    if let Some(dcc) = &credentials.dcc {
+        text.push_str(&format!("\n{}", dcc.to_text()));
+    }
+    if let Some(shc) = &credentials.shc {
+        text.push_str(&format!("\n{}", shc.to_text()));
+    }
+    text
+}
 
-    // println!("Encode: file_name: {}, barcode_type: {}, width: {:?}, height: {:?}, data: '{:?}', data_file: {:?}", file_name, barcode_type, width, height, data, data_file);
+/// Seconds since the Unix epoch, which TOTP codes count in.
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
 
-    let writer = MultiFormatWriter::default();
-    match writer.encode_with_hints(
-        &input_data,
-        barcode_type,
-        *width as i32,
-        *height as i32,
-        &hints,
-    ) {
-        Ok(result) => {
-            println!("Encode successful, saving...");
-            match rxing::helpers::save_file(file_name, &result) {
-                Ok(_) => println!("Saved to '{file_name}'"),
-                Err(error) => println!("Could not save '{file_name}': {error}"),
-            }
-        }
-        Err(encode_error) => println!("Couldn't encode: {encode_error}"),
-    }
+/// The local date, which dates without a year are placed near.
+fn today() -> chrono::NaiveDate {
+    chrono::Local::now().date_naive()
 }
 
 fn print_result(result: &rxing::RXingResult, detailed: bool, raw: bool, parsed: bool) -> String {
@@ -648,8 +1276,7 @@ fn print_result(result: &rxing::RXingResult, detailed: bool, raw: bool, parsed:
             .iter()
             .fold(String::from(""), |acc, b| acc + " " + &b.to_string())
     } else if parsed {
-        let parsed_data = rxing::client::result::parseRXingResult(result);
-        parsed_data.to_string()
+        parsed::to_text(&parsed::parsed_result(result))
     } else {
         format!("({}) {}", result.getBarcodeFormat(), result_data)
     }
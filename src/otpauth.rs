@@ -0,0 +1,428 @@
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use crate::{error::CliError, uri};
+
+pub const SCHEME: &str = "otpauth://";
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+/// RFC 4226 R6: the shared secret must be at least 128 bits.
+const MIN_SECRET_BYTES: usize = 16;
+const DEFAULT_ALGORITHM: &str = "SHA1";
+const DEFAULT_DIGITS: u32 = 6;
+const DEFAULT_PERIOD: u64 = 30;
+
+/// An `otpauth://` key as Google's Key Uri Format describes it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Key {
+    /// totp or hotp.
+    #[serde(rename = "type")]
+    pub otp_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+    /// The account the secret is for, usually a user name or email address.
+    pub account: String,
+    /// The shared secret in Base32, without padding.
+    pub secret: String,
+    /// SHA1, SHA256 or SHA512.
+    pub algorithm: String,
+    pub digits: u32,
+    /// The time step of a TOTP code in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<u64>,
+    /// The moving factor of an HOTP code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counter: Option<u64>,
+}
+
+impl Key {
+    /// Everything the Key Uri Format and RFC 4226 require, one message per problem.
+    pub fn check(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !matches!(self.otp_type.as_str(), "totp" | "hotp") {
+            errors.push(format!("the type '{}' is not totp or hotp", self.otp_type));
+        }
+        if self.account.is_empty() {
+            errors.push("the account name is missing".to_owned());
+        }
+        for (field, value) in [
+            ("issuer", self.issuer.as_deref().unwrap_or_default()),
+            ("account name", &self.account),
+        ] {
+            if value.contains(':') {
+                errors.push(format!("the {field} '{value}' contains a colon"));
+            }
+        }
+        match base32_decode(&self.secret) {
+            None if self.secret.is_empty() => errors.push("the secret is missing".to_owned()),
+            None => errors.push(format!("the secret '{}' is not Base32", self.secret)),
+            Some(secret) if secret.len() < MIN_SECRET_BYTES => errors.push(format!(
+                "the secret is {} bits, at least {} are required",
+                secret.len() * 8,
+                MIN_SECRET_BYTES * 8
+            )),
+            Some(_) => {}
+        }
+        if !matches!(self.algorithm.as_str(), "SHA1" | "SHA256" | "SHA512") {
+            errors.push(format!(
+                "the algorithm '{}' is not SHA1, SHA256 or SHA512",
+                self.algorithm
+            ));
+        }
+        if !(6..=8).contains(&self.digits) {
+            errors.push(format!("{} digits are not from 6 to 8", self.digits));
+        }
+        match (self.otp_type.as_str(), self.period, self.counter) {
+            ("totp", Some(0), _) => errors.push("the period is 0 seconds".to_owned()),
+            ("hotp", _, None) => errors.push("an hotp key needs a counter".to_owned()),
+            _ => {}
+        }
+        errors
+    }
+
+    /// The URI, with the issuer both as the label prefix and as a parameter as recommended.
+    pub fn to_uri(&self) -> String {
+        // account names are often email addresses
+        let label = match &self.issuer {
+            Some(issuer) => format!(
+                "{}:{}",
+                uri::path_encode(issuer),
+                uri::path_encode(&self.account)
+            ),
+            None => uri::path_encode(&self.account),
+        };
+        let (digits, period, counter) = (
+            self.digits.to_string(),
+            self.period.map(|period| period.to_string()),
+            self.counter.map(|counter| counter.to_string()),
+        );
+        let query = uri::query(&[
+            ("secret", Some(&self.secret)),
+            ("issuer", self.issuer.as_deref()),
+            ("algorithm", Some(&self.algorithm)),
+            ("digits", Some(&digits)),
+            ("period", period.as_deref()),
+            ("counter", counter.as_deref()),
+        ]);
+        format!("{SCHEME}{}/{label}?{query}", self.otp_type)
+    }
+
+    /// The code for `counter`, RFC 4226 dynamic truncation of the HMAC.
+    fn code(&self, counter: u64) -> Option<String> {
+        let secret = base32_decode(&self.secret)?;
+        let message = counter.to_be_bytes();
+        let hash = match self.algorithm.as_str() {
+            "SHA1" => mac::<Hmac<Sha1>>(&secret, &message),
+            "SHA256" => mac::<Hmac<Sha256>>(&secret, &message),
+            "SHA512" => mac::<Hmac<Sha512>>(&secret, &message),
+            _ => return None,
+        };
+        let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+        let binary = u32::from_be_bytes(hash[offset..offset + 4].try_into().ok()?) & 0x7fff_ffff;
+        let code = u64::from(binary) % 10u64.pow(self.digits);
+        Some(format!("{code:0width$}", width = self.digits as usize))
+    }
+}
+
+fn mac<M: Mac + hmac::digest::KeyInit>(secret: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = <M as Mac>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// A key for the builder, with the defaults of the Key Uri Format.
+pub fn new_key(otp_type: &str, account: &str) -> Key {
+    Key {
+        otp_type: otp_type.to_owned(),
+        issuer: None,
+        account: account.to_owned(),
+        secret: String::new(),
+        algorithm: DEFAULT_ALGORITHM.to_owned(),
+        digits: DEFAULT_DIGITS,
+        period: (otp_type == "totp").then_some(DEFAULT_PERIOD),
+        counter: (otp_type == "hotp").then_some(0),
+    }
+}
+
+/// A random secret as long as the output of `algorithm`, as RFC 4226 recommends.
+pub fn generate_secret(algorithm: &str) -> Result<String, CliError> {
+    let mut secret = vec![
+        0;
+        match algorithm {
+            "SHA256" => 32,
+            "SHA512" => 64,
+            _ => 20,
+        }
+    ];
+    getrandom::getrandom(&mut secret)
+        .map_err(|e| CliError::Io(format!("no random secret could be generated: {e}")))?;
+    Ok(base32_encode(&secret))
+}
+
+/// Build the URI of `key`, failing with every problem found. The secret is written in
+/// capitals without spaces or padding.
+pub fn build(mut key: Key) -> Result<String, CliError> {
+    if let Some(secret) = base32_decode(&key.secret) {
+        key.secret = base32_encode(&secret);
+    }
+    invalid(key.check())?;
+    Ok(key.to_uri())
+}
+
+/// Check a URI given as is with --data.
+pub fn validate(text: &str) -> Result<(), CliError> {
+    match parse(text, None) {
+        Some(otp) => invalid(otp.errors),
+        None => invalid(vec![format!("it does not start with {SCHEME}")]),
+    }
+}
+
+fn invalid(errors: Vec<String>) -> Result<(), CliError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::InvalidHint(format!(
+            "Invalid otpauth URI:\n  {}",
+            errors.join("\n  ")
+        )))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OtpAuth {
+    #[serde(flatten)]
+    pub key: Key,
+    /// Parameters the Key Uri Format does not define, e.g. image.
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub parameters: Map<String, Value>,
+    /// The current code with --otp-code.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    /// The seconds until a TOTP code changes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_valid_for: Option<u64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl OtpAuth {
+    /// One line per field, for the text output.
+    pub fn to_text(&self) -> String {
+        let Ok(Value::Object(fields)) = serde_json::to_value(self) else {
+            return String::new();
+        };
+        let mut text = "otpauth:".to_owned();
+        for (name, value) in fields {
+            match value {
+                Value::String(s) => text.push_str(&format!("\n  {name}: {s}")),
+                Value::Array(errors) => {
+                    for error in errors.iter().filter_map(Value::as_str) {
+                        text.push_str(&format!("\n  error: {error}"));
+                    }
+                }
+                Value::Object(parameters) => {
+                    for (name, value) in parameters {
+                        let value = value.as_str().unwrap_or_default();
+                        text.push_str(&format!("\n  {name}: {value}"));
+                    }
+                }
+                other => text.push_str(&format!("\n  {name}: {other}")),
+            }
+        }
+        text
+    }
+}
+
+/// Parse an `otpauth://` URI and check it. With `now`, in seconds since the Unix epoch, the
+/// code a valid key shows at that time is added, for an HOTP key the code of its counter.
+/// Other text is not a key.
+pub fn parse(text: &str, now: Option<u64>) -> Option<OtpAuth> {
+    let text = text.trim();
+    let rest = text
+        .get(..SCHEME.len())
+        .filter(|scheme| scheme.eq_ignore_ascii_case(SCHEME))
+        .map(|_| &text[SCHEME.len()..])?;
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (otp_type, label) = path.split_once('/').unwrap_or((path, ""));
+    let label = uri::percent_decode(label);
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.to_owned()), account.trim_start()),
+        None => (None, label.as_str()),
+    };
+
+    let mut key = Key {
+        otp_type: otp_type.to_ascii_lowercase(),
+        issuer: label_issuer.clone(),
+        account: account.to_owned(),
+        secret: String::new(),
+        algorithm: DEFAULT_ALGORITHM.to_owned(),
+        digits: DEFAULT_DIGITS,
+        period: None,
+        counter: None,
+    };
+    let (mut parameters, mut errors) = (Map::new(), Vec::new());
+    let number = |name: &str, value: &str, errors: &mut Vec<String>| {
+        let number = value.parse::<u64>().ok();
+        if number.is_none() {
+            errors.push(format!("the {name} '{value}' is not a number"));
+        }
+        number
+    };
+    for (name, value) in uri::query_pairs(query) {
+        match name.as_str() {
+            "secret" => key.secret = value,
+            "issuer" => {
+                if let Some(prefix) = label_issuer.as_ref().filter(|prefix| **prefix != value) {
+                    errors.push(format!(
+                        "the issuer '{value}' does not match the label prefix '{prefix}'"
+                    ));
+                }
+                key.issuer = Some(value);
+            }
+            "algorithm" => key.algorithm = value.to_ascii_uppercase(),
+            "digits" => {
+                if let Some(digits) = number("digits", &value, &mut errors) {
+                    key.digits = u32::try_from(digits).unwrap_or(u32::MAX);
+                }
+            }
+            "period" => key.period = number("period", &value, &mut errors),
+            "counter" => key.counter = number("counter", &value, &mut errors),
+            _ => {
+                parameters.insert(name, value.into());
+            }
+        }
+    }
+    if key.otp_type == "totp" {
+        key.period = Some(key.period.unwrap_or(DEFAULT_PERIOD));
+        key.counter = None;
+    }
+
+    errors.extend(key.check());
+    let mut otp = OtpAuth {
+        parameters,
+        code: None,
+        code_valid_for: None,
+        valid: errors.is_empty(),
+        errors,
+        key,
+    };
+    if let Some(now) = now.filter(|_| otp.valid) {
+        let key = &otp.key;
+        match (key.period, key.counter) {
+            (Some(period), _) => {
+                otp.code = key.code(now / period);
+                otp.code_valid_for = Some(period - now % period);
+            }
+            (None, Some(counter)) => otp.code = key.code(counter),
+            (None, None) => {}
+        }
+    }
+    Some(otp)
+}
+
+/// RFC 4648 Base32 without padding.
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            text.push(BASE32_ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+    if bits > 0 {
+        text.push(BASE32_ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+    text
+}
+
+/// RFC 4648 Base32 in either case, ignoring spaces and padding as authenticator apps do.
+pub fn base32_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in text
+        .bytes()
+        .filter(|c| !c.is_ascii_whitespace() && *c != b'=')
+    {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_uppercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    (!bytes.is_empty()).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The RFC 4226 and RFC 6238 test secret, "12345678901234567890".
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    #[test]
+    fn base32() {
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+        assert_eq!(base32_encode(b"12345678901234567890"), SECRET);
+        assert_eq!(base32_decode("mzxw 6ytb oi======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("MZXW1"), None);
+        assert_eq!(base32_decode(""), None);
+    }
+
+    #[test]
+    fn totp_code() {
+        let text = format!(
+            "otpauth://totp/ACME%20Co:john@example.com?secret={SECRET}&issuer=ACME%20Co&digits=8"
+        );
+        let otp = parse(&text, Some(59)).unwrap();
+        assert!(otp.valid, "{:?}", otp.errors);
+        assert_eq!(otp.key.issuer.as_deref(), Some("ACME Co"));
+        assert_eq!(otp.key.account, "john@example.com");
+        assert_eq!(otp.code.as_deref(), Some("94287082"));
+        assert_eq!(otp.code_valid_for, Some(1));
+        let otp = parse(&text, Some(1_111_111_109)).unwrap();
+        assert_eq!(otp.code.as_deref(), Some("07081804"));
+    }
+
+    #[test]
+    fn hotp_code() {
+        let text = format!("otpauth://hotp/alice?secret={SECRET}&counter=1");
+        let otp = parse(&text, Some(0)).unwrap();
+        assert_eq!(otp.code.as_deref(), Some("287082"));
+    }
+
+    #[test]
+    fn build_round_trip() {
+        let mut key = new_key("totp", "john@example.com");
+        key.secret = SECRET.to_owned();
+        key.issuer = Some("ACME Co".to_owned());
+        let text = build(key).unwrap();
+        assert!(parse(&text, None).unwrap().valid, "{text}");
+    }
+
+    #[test]
+    fn invalid_keys() {
+        for text in [
+            "otpauth://totp/alice?secret=GEZDGNBV",
+            "otpauth://totp/alice?secret=not%20base32!",
+            "otpauth://totp/alice",
+            "otpauth://hotp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+            "otpauth://totp/ACME:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&issuer=Other",
+            "otpauth://totp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&digits=9",
+            "otpauth://totp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&algorithm=MD5",
+            "otpauth://motp/alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+        ] {
+            assert!(validate(text).is_err(), "{text}");
+        }
+        assert!(parse("https://example.com", None).is_none());
+    }
+}
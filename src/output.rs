@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use rxing::{
+    Exceptions, RXingResult, RXingResultMetadataType, RXingResultMetadataValue, ResultPoint,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    aamva::{self, Aamva},
+    bcbp::Bcbp,
+    dcc::Dcc,
+    epc::Epc,
+    gs1::ElementString,
+    otpauth::OtpAuth,
+    payment_uri::PaymentUri,
+    shc::Shc,
+    swiss_qr_bill::SwissQrBill,
+};
+
+/// Version of the JSON document layout. Bump this whenever a field is renamed, removed or
+/// changes type; adding new fields does not require a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// One JSON document per result. With --decode-multi every result is written on its own
+    /// line (NDJSON).
+    Json,
+}
+
+#[derive(Serialize)]
+pub struct JsonPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Serialize)]
+pub struct JsonResult {
+    pub schema_version: u32,
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<usize>,
+    pub format: String,
+    pub text: String,
+    pub raw_bytes_hex: String,
+    pub raw_bytes_base64: String,
+    pub num_bits: usize,
+    pub points: Vec<JsonPoint>,
+    pub timestamp: u128,
+    pub metadata: BTreeMap<String, Value>,
+    /// The result parsed as a vCard, WiFi network, calendar event etc. with --parsed-results.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<Value>,
+    /// The GS1 application identifiers of the result with --gs1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gs1: Option<ElementString>,
+    /// The fields of a driver licence or ID card with --aamva.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aamva: Option<Aamva>,
+    /// The passenger and flight legs of a boarding pass with --bcbp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bcbp: Option<Bcbp>,
+    /// The fields of a Swiss QR-bill with --swiss-qr-bill.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swiss_qr_bill: Option<SwissQrBill>,
+    /// The fields of a SEPA credit transfer with --epc.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epc: Option<Epc>,
+    /// The fields of a bitcoin:, lightning: or upi://pay URI with --payment-uri.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_uri: Option<PaymentUri>,
+    /// The fields of an otpauth:// key with --otpauth.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otpauth: Option<OtpAuth>,
+    /// The header, claims and certificate of an EU Digital COVID Certificate with --dcc.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dcc: Option<Dcc>,
+    /// The header, claims and FHIR bundle of a SMART Health Card with --shc.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shc: Option<Shc>,
+}
+
+impl JsonResult {
+    pub fn new(file_name: &str, index: Option<usize>, result: &RXingResult) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            file: file_name.to_owned(),
+            index,
+            format: format!("{:?}", result.getBarcodeFormat()),
+            text: result.getText().to_owned(),
+            raw_bytes_hex: to_hex(result.getRawBytes()),
+            raw_bytes_base64: base64::engine::general_purpose::STANDARD
+                .encode(result.getRawBytes()),
+            num_bits: result.getNumBits(),
+            points: result
+                .getRXingResultPoints()
+                .iter()
+                .map(|p| JsonPoint {
+                    x: p.getX(),
+                    y: p.getY(),
+                })
+                .collect(),
+            timestamp: result.getTimestamp(),
+            metadata: result
+                .getRXingResultMetadata()
+                .iter()
+                .map(|(k, v)| (metadata_key(k), metadata_value(v)))
+                .collect(),
+            parsed: None,
+            gs1: None,
+            aamva: None,
+            bcbp: None,
+            swiss_qr_bill: None,
+            epc: None,
+            payment_uri: None,
+            otpauth: None,
+            dcc: None,
+            shc: None,
+        }
+    }
+
+    /// Remove everything that carries the decoded data as is, for --redact-pii.
+    pub fn redact(&mut self) {
+        self.text = aamva::REDACTED.to_owned();
+        self.raw_bytes_hex.clear();
+        self.raw_bytes_base64.clear();
+        self.metadata.remove("BYTE_SEGMENTS");
+        self.parsed = None;
+    }
+}
+
+#[derive(Serialize)]
+pub struct JsonErrorDetail {
+    pub kind: String,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct JsonError {
+    pub schema_version: u32,
+    pub file: String,
+    pub error: JsonErrorDetail,
+}
+
+impl JsonError {
+    pub fn new(file_name: &str, error: &Exceptions) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            file: file_name.to_owned(),
+            error: JsonErrorDetail {
+                kind: exception_kind(error).to_owned(),
+                message: error.to_string(),
+            },
+        }
+    }
+
+    pub fn from_message(file_name: &str, kind: &str, message: &str) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            file: file_name.to_owned(),
+            error: JsonErrorDetail {
+                kind: kind.to_owned(),
+                message: message.to_owned(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct JsonSummaryDetail {
+    pub found: usize,
+    pub decoded: usize,
+    pub failed: usize,
+}
+
+/// Written once at the end of a batch run.
+#[derive(Serialize)]
+pub struct JsonSummary {
+    pub schema_version: u32,
+    pub summary: JsonSummaryDetail,
+}
+
+impl JsonSummary {
+    pub fn new(found: usize, decoded: usize, failed: usize) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            summary: JsonSummaryDetail {
+                found,
+                decoded,
+                failed,
+            },
+        }
+    }
+}
+
+/// Serialize a value as a single line of JSON.
+pub fn to_json_line<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).expect("output types always serialize")
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn metadata_key(key: &RXingResultMetadataType) -> String {
+    format!("{key:?}")
+}
+
+#[allow(deprecated)]
+fn metadata_value(value: &RXingResultMetadataValue) -> Value {
+    match value {
+        RXingResultMetadataValue::OTHER(v) => json!(v),
+        RXingResultMetadataValue::Orientation(v) => json!(v),
+        RXingResultMetadataValue::ByteSegments(segments) => {
+            json!(segments.iter().map(|s| to_hex(s)).collect::<Vec<_>>())
+        }
+        RXingResultMetadataValue::ErrorCorrectionLevel(v) => json!(v),
+        RXingResultMetadataValue::IssueNumber(v) => json!(v),
+        RXingResultMetadataValue::SuggestedPrice(v) => json!(v),
+        RXingResultMetadataValue::PossibleCountry(v) => json!(v),
+        RXingResultMetadataValue::UpcEanExtension(v) => json!(v),
+        RXingResultMetadataValue::Pdf417ExtraMetadata(md) => json!({
+            "segment_index": md.getSegmentIndex(),
+            "file_id": md.getFileId(),
+            "optional_data": md.getOptionalData(),
+            "last_segment": md.isLastSegment(),
+            "segment_count": md.getSegmentCount(),
+            "sender": md.getSender(),
+            "addressee": md.getAddressee(),
+            "file_name": md.getFileName(),
+            "file_size": md.getFileSize(),
+            "checksum": md.getChecksum(),
+            "timestamp": md.getTimestamp(),
+        }),
+        RXingResultMetadataValue::StructuredAppendSequence(v) => json!(v),
+        RXingResultMetadataValue::StructuredAppendParity(v) => json!(v),
+        RXingResultMetadataValue::SymbologyIdentifier(v) => json!(v),
+        RXingResultMetadataValue::IsMirrored(v) => json!(v),
+        RXingResultMetadataValue::ContentType(v) => json!(v),
+    }
+}
+
+fn exception_kind(error: &Exceptions) -> &'static str {
+    match error {
+        Exceptions::IllegalArgumentException(_) => "illegal_argument",
+        Exceptions::UnsupportedOperationException(_) => "unsupported_operation",
+        Exceptions::IllegalStateException(_) => "illegal_state",
+        Exceptions::ArithmeticException(_) => "arithmetic",
+        Exceptions::NotFoundException(_) => "not_found",
+        Exceptions::FormatException(_) => "format",
+        Exceptions::ChecksumException(_) => "checksum",
+        Exceptions::ReaderException(_) => "reader",
+        Exceptions::WriterException(_) => "writer",
+        Exceptions::ReedSolomonException(_) => "reed_solomon",
+        Exceptions::IndexOutOfBoundsException(_) => "index_out_of_bounds",
+        Exceptions::RuntimeException(_) => "runtime",
+        Exceptions::ParseException(_) => "parse",
+        Exceptions::ReaderDecodeException() => "reader_decode",
+    }
+}
@@ -0,0 +1,279 @@
+use std::panic::AssertUnwindSafe;
+
+use chrono::{DateTime, NaiveDate};
+use rxing::{
+    client::result::{
+        AddressBookParsedRXingResult, CalendarParsedRXingResult, ParsedClientResult,
+        ParsedRXingResult,
+    },
+    RXingResult,
+};
+use serde_json::{json, Map, Value};
+
+/// Parse the text of `result` as one of the client result types (vCard, WiFi, calendar event,
+/// geo location...) and describe it with its typed fields. The `type` key names the result
+/// type, fields that are not set are left out.
+pub fn parsed_result(result: &RXingResult) -> Value {
+    // the WIFI: and MECARD: field matcher of rxing 0.3 mixes byte and character offsets and
+    // panics on some text that is not ASCII, which is then reported as plain text
+    let parsed = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        rxing::client::result::parseRXingResult(result)
+    }));
+    let Ok(parsed) = parsed else {
+        return json!({ "type": "text", "text": result.getText() });
+    };
+    let kind = format!("{:?}", parsed.getType()).to_lowercase();
+
+    let fields = match &parsed {
+        ParsedClientResult::TextResult(text) => json!({
+            "text": text.getText(),
+            "language": text.getLanguage(),
+        }),
+        ParsedClientResult::TelResult(tel) => json!({
+            "number": tel.getNumber(),
+            "uri": tel.getTelURI(),
+            "title": tel.getTitle(),
+        }),
+        ParsedClientResult::ISBNResult(isbn) => json!({ "isbn": isbn.getISBN() }),
+        ParsedClientResult::WiFiResult(wifi) => json!({
+            "ssid": wifi.getSsid(),
+            "auth": wifi.getNetworkEncryption(),
+            "password": wifi.getPassword(),
+            "hidden": wifi.isHidden(),
+            "identity": wifi.getIdentity(),
+            "anonymous_identity": wifi.getAnonymousIdentity(),
+            "eap_method": wifi.getEapMethod(),
+        }),
+        ParsedClientResult::GeoResult(geo) => json!({
+            "latitude": geo.getLatitude(),
+            "longitude": geo.getLongitude(),
+            "altitude": (geo.getAltitude() != 0.0).then(|| geo.getAltitude()),
+            "query": geo.getQuery(),
+            "uri": geo.getGeoURI(),
+        }),
+        ParsedClientResult::SMSResult(sms) => json!({
+            "numbers": sms.getNumbers(),
+            "vias": sms.getVias().iter().filter(|via| !via.is_empty()).collect::<Vec<_>>(),
+            "subject": sms.getSubject(),
+            "body": sms.getBody(),
+            "uri": sms.getSMSURI(),
+        }),
+        ParsedClientResult::ProductResult(product) => json!({
+            "product_id": product.getProductID(),
+            "normalized_product_id": product.getNormalizedProductID(),
+        }),
+        ParsedClientResult::URIResult(uri) => json!({
+            "uri": uri.getURI(),
+            "title": uri.getTitle(),
+        }),
+        ParsedClientResult::EmailResult(email) => json!({
+            "to": email.getTos(),
+            "cc": email.getCCs(),
+            "bcc": email.getBCCs(),
+            "subject": email.getSubject(),
+            "body": email.getBody(),
+        }),
+        ParsedClientResult::VINResult(vin) => json!({
+            "vin": vin.getVIN(),
+            "world_manufacturer_id": vin.getWorldManufacturerID(),
+            "vehicle_descriptor_section": vin.getVehicleDescriptorSection(),
+            "vehicle_identifier_section": vin.getVehicleIdentifierSection(),
+            "country_code": vin.getCountryCode(),
+            "vehicle_attributes": vin.getVehicleAttributes(),
+            "model_year": vin.getModelYear(),
+            "plant_code": vin.getPlantCode().to_string(),
+            "sequential_number": vin.getSequentialNumber(),
+        }),
+        ParsedClientResult::AddressBookResult(address_book) => address_book_fields(address_book),
+        ParsedClientResult::CalendarEventResult(event) => calendar_fields(event),
+        ParsedClientResult::ExpandedProductResult(product) => json!({
+            "product_id": product.getProductID(),
+            "sscc": product.getSscc(),
+            "lot_number": product.getLotNumber(),
+            "production_date": product.getProductionDate(),
+            "packaging_date": product.getPackagingDate(),
+            "best_before_date": product.getBestBeforeDate(),
+            "expiration_date": product.getExpirationDate(),
+            "weight": product.getWeight(),
+            "weight_type": product.getWeightType(),
+            "weight_increment": product.getWeightIncrement(),
+            "price": product.getPrice(),
+            "price_increment": product.getPriceIncrement(),
+            "price_currency": product.getPriceCurrency(),
+            "other_ais": product.getUncommonAIs(),
+        }),
+        ParsedClientResult::Other(_) => json!({ "text": result.getText() }),
+    };
+
+    let mut object = Map::new();
+    object.insert("type".to_owned(), Value::String(kind));
+    if let Value::Object(fields) = fields {
+        object.extend(fields.into_iter().filter(|(_, value)| !is_empty(value)));
+    }
+    Value::Object(object)
+}
+
+fn address_book_fields(address_book: &AddressBookParsedRXingResult) -> Value {
+    // the types line up with their values, but are often missing
+    let typed = |values: &[String], types: &[String], key: &str| {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                json!({
+                    key: value,
+                    "type": types.get(i).filter(|t| !t.is_empty()),
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    json!({
+        "names": address_book.getNames(),
+        "nicknames": address_book.getNicknames(),
+        "pronunciation": address_book.getPronunciation(),
+        "phones": typed(address_book.getPhoneNumbers(), address_book.getPhoneTypes(), "number"),
+        "emails": typed(address_book.getEmails(), address_book.getEmailTypes(), "address"),
+        "addresses": typed(address_book.getAddresses(), address_book.getAddressTypes(), "address"),
+        "instant_messenger": address_book.getInstantMessenger(),
+        "note": address_book.getNote(),
+        "title": address_book.getTitle(),
+        "org": address_book.getOrg(),
+        "urls": address_book.getURLs(),
+        "birthday": address_book.getBirthday(),
+        "geo": address_book.getGeo(),
+    })
+}
+
+fn calendar_fields(event: &CalendarParsedRXingResult) -> Value {
+    let has_location = !event.getLatitude().is_nan() && !event.getLongitude().is_nan();
+    json!({
+        "summary": event.getSummary(),
+        "start": format_timestamp(event.getStartTimestamp(), event.isStartAllDay()),
+        // an end before the start means the event has no duration
+        "end": (event.getEndTimestamp() >= event.getStartTimestamp())
+            .then(|| format_timestamp(event.getEndTimestamp(), event.isEndAllDay())),
+        "location": event.getLocation(),
+        "organizer": event.getOrganizer(),
+        "attendees": event.getAttendees(),
+        "description": event.getDescription(),
+        "latitude": has_location.then(|| event.getLatitude()),
+        "longitude": has_location.then(|| event.getLongitude()),
+    })
+}
+
+/// RFC 3339 in UTC, or only the date for all day events.
+fn format_timestamp(seconds: i64, all_day: bool) -> Option<String> {
+    let time = DateTime::from_timestamp(seconds, 0)?;
+    Some(if all_day {
+        NaiveDate::from(time.naive_utc()).to_string()
+    } else {
+        time.to_rfc3339()
+    })
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(s) => s.is_empty(),
+        Value::Array(a) => a.is_empty(),
+        Value::Object(o) => o.is_empty(),
+        _ => false,
+    }
+}
+
+/// One `key: value` line per field, for the text output.
+pub fn to_text(parsed: &Value) -> String {
+    let Value::Object(fields) = parsed else {
+        return parsed.to_string();
+    };
+    let mut text = String::new();
+    for (key, value) in fields {
+        let value = match value {
+            Value::String(s) => s.escape_default().to_string(),
+            other => other.to_string(),
+        };
+        text.push_str(&format!("{key}: {value}\n"));
+    }
+    text.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use rxing::BarcodeFormat;
+
+    use super::*;
+
+    fn parse(text: &str) -> Value {
+        parsed_result(&RXingResult::new(
+            text,
+            Vec::new(),
+            Vec::new(),
+            BarcodeFormat::QR_CODE,
+        ))
+    }
+
+    #[test]
+    fn wifi() {
+        assert_eq!(
+            parse("WIFI:T:WPA;S:home;P:secret;;"),
+            json!({
+                "type": "wifi",
+                "ssid": "home",
+                "auth": "WPA",
+                "password": "secret",
+                "hidden": false,
+            })
+        );
+    }
+
+    #[test]
+    fn unset_fields_are_left_out() {
+        let geo = parse("geo:46.5,6.6");
+        assert_eq!(geo["type"], "geo");
+        assert_eq!(geo["latitude"], 46.5);
+        assert!(geo.get("altitude").is_none());
+        assert!(geo.get("query").is_none());
+
+        let url = parse("https://example.com");
+        assert_eq!(url, json!({ "type": "uri", "uri": "https://example.com" }));
+    }
+
+    #[test]
+    fn non_ascii_that_rxing_can_not_parse_is_text() {
+        let text = "WIFI:S:café;P:ünïcode;;";
+        assert_eq!(parse(text), json!({ "type": "text", "text": text }));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(
+            format_timestamp(1_700_000_000, false).as_deref(),
+            Some("2023-11-14T22:13:20+00:00")
+        );
+        assert_eq!(
+            format_timestamp(1_700_000_000, true).as_deref(),
+            Some("2023-11-14")
+        );
+        assert_eq!(format_timestamp(i64::MAX, false), None);
+    }
+
+    #[test]
+    fn calendar_event() {
+        let event = parse(
+            "BEGIN:VEVENT\r\nSUMMARY:Launch\r\nDTSTART:20231114T221320Z\r\nDTEND:20231114T231320Z\r\nEND:VEVENT",
+        );
+        assert_eq!(event["type"], "calendar");
+        assert_eq!(event["summary"], "Launch");
+        assert_eq!(event["start"], "2023-11-14T22:13:20+00:00");
+        assert_eq!(event["end"], "2023-11-14T23:13:20+00:00");
+        assert!(event.get("latitude").is_none());
+    }
+
+    #[test]
+    fn text_lines() {
+        let parsed = json!({ "type": "text", "text": "a\nb", "count": 2 });
+        assert_eq!(to_text(&parsed), "type: text\ntext: a\\nb\ncount: 2");
+        assert_eq!(to_text(&json!("plain")), "\"plain\"");
+    }
+}
@@ -0,0 +1,624 @@
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    checksum::{self, Bech32Variant, BECH32_CHARSET},
+    error::CliError,
+    uri,
+};
+
+/// The largest amount of bitcoin there will ever be.
+const MAX_BITCOIN: f64 = 21_000_000.0;
+/// Millisatoshi in one bitcoin, the unit of a BOLT 11 amount without a multiplier.
+const MSAT_PER_BITCOIN: u64 = 100_000_000_000;
+/// A BOLT 11 timestamp is 35 bits, seven words.
+const TIMESTAMP_WORDS: usize = 7;
+/// A BOLT 11 signature is 520 bits: 64 bytes and the recovery id.
+const SIGNATURE_WORDS: usize = 104;
+/// The expiry of an invoice without an x field, in seconds.
+const DEFAULT_EXPIRY: u64 = 3600;
+
+/// A BIP 21 payment request.
+#[derive(Debug, Default)]
+pub struct Bitcoin {
+    pub address: String,
+    /// The amount in bitcoin, e.g. 0.0015.
+    pub amount: Option<String>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    /// A BOLT 11 invoice wallets with Lightning support pay instead.
+    pub lightning: Option<String>,
+}
+
+/// A UPI payment intent, `upi://pay`.
+#[derive(Debug, Default)]
+pub struct Upi {
+    /// The payee's virtual payment address, `handle@psp`.
+    pub vpa: String,
+    pub name: Option<String>,
+    /// The amount in rupees, e.g. 250.00.
+    pub amount: Option<String>,
+    pub currency: String,
+    pub note: Option<String>,
+    pub transaction_ref: Option<String>,
+    pub merchant_code: Option<String>,
+}
+
+/// Build a `bitcoin:` URI, failing with every problem found.
+pub fn build_bitcoin(request: Bitcoin) -> Result<String, CliError> {
+    let query = uri::query(&[
+        ("amount", request.amount.as_deref()),
+        ("label", request.label.as_deref()),
+        ("message", request.message.as_deref()),
+        ("lightning", request.lightning.as_deref()),
+    ]);
+    let mut text = format!("bitcoin:{}", request.address.trim());
+    if !query.is_empty() {
+        text.push_str(&format!("?{query}"));
+    }
+    checked(text)
+}
+
+/// Build a `lightning:` URI of a BOLT 11 invoice or an LNURL, failing if it is malformed.
+pub fn build_lightning(invoice: &str) -> Result<String, CliError> {
+    checked(format!("lightning:{}", invoice.trim()))
+}
+
+/// Build a `upi://pay` URI, failing with every problem found.
+pub fn build_upi(mut payment: Upi) -> Result<String, CliError> {
+    // UPI apps expect rupees with paise
+    if let Some(amount) = &payment.amount {
+        if let Ok(value) = amount.trim().parse::<f64>() {
+            if is_decimal(amount.trim(), 2) {
+                payment.amount = Some(format!("{value:.2}"));
+            }
+        }
+    }
+    let query = uri::query(&[
+        ("pa", Some(payment.vpa.trim())),
+        ("pn", payment.name.as_deref()),
+        ("am", payment.amount.as_deref()),
+        ("cu", Some(&payment.currency)),
+        ("tn", payment.note.as_deref()),
+        ("tr", payment.transaction_ref.as_deref()),
+        ("mc", payment.merchant_code.as_deref()),
+    ]);
+    checked(format!("upi://pay?{query}"))
+}
+
+fn checked(text: String) -> Result<String, CliError> {
+    validate(&text)?;
+    Ok(text)
+}
+
+/// Check a URI given as is with --data.
+pub fn validate(text: &str) -> Result<(), CliError> {
+    let errors = match parse(text) {
+        Some(payment) => payment.errors,
+        None => vec!["it is not a bitcoin:, lightning: or upi://pay URI".to_owned()],
+    };
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::InvalidHint(format!(
+            "Invalid payment URI:\n  {}",
+            errors.join("\n  ")
+        )))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentUri {
+    /// bitcoin, lightning or upi.
+    pub scheme: &'static str,
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl PaymentUri {
+    /// One line per field, for the text output.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Payment URI ({}):", self.scheme);
+        push_fields(&mut text, &self.fields, "  ");
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        text.push_str(&format!("\n  valid: {}", self.valid));
+        text
+    }
+}
+
+fn push_fields(text: &mut String, fields: &Map<String, Value>, indent: &str) {
+    for (name, value) in fields {
+        match value {
+            Value::Object(nested) => {
+                text.push_str(&format!("\n{indent}{name}:"));
+                push_fields(text, nested, &format!("{indent}  "));
+            }
+            Value::String(s) => text.push_str(&format!("\n{indent}{name}: {s}")),
+            other => text.push_str(&format!("\n{indent}{name}: {other}")),
+        }
+    }
+}
+
+/// Parse a BIP 21, Lightning or UPI payment URI and check what can be checked offline. Other
+/// text is not a payment URI.
+pub fn parse(text: &str) -> Option<PaymentUri> {
+    let (scheme, rest) = text.trim().split_once(':')?;
+    let mut payment = PaymentUri {
+        scheme: "",
+        fields: Map::new(),
+        errors: Vec::new(),
+        valid: false,
+    };
+    match scheme.to_ascii_lowercase().as_str() {
+        "bitcoin" => {
+            payment.scheme = "bitcoin";
+            parse_bitcoin(&mut payment, rest);
+        }
+        "lightning" => {
+            payment.scheme = "lightning";
+            let rest = rest.strip_prefix("//").unwrap_or(rest);
+            parse_lightning(&mut payment.fields, &mut payment.errors, rest);
+        }
+        "upi" => {
+            payment.scheme = "upi";
+            parse_upi(&mut payment, rest);
+        }
+        _ => return None,
+    }
+    payment.valid = payment.errors.is_empty();
+    Some(payment)
+}
+
+fn parse_bitcoin(payment: &mut PaymentUri, rest: &str) {
+    let (address, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (fields, errors) = (&mut payment.fields, &mut payment.errors);
+    let pairs = uri::query_pairs(query);
+    if !address.is_empty() {
+        fields.insert("address".to_owned(), address.into());
+        match checksum::bitcoin_address(address) {
+            Ok((network, kind)) => {
+                fields.insert("network".to_owned(), network.into());
+                fields.insert("address_type".to_owned(), kind.into());
+            }
+            Err(error) => errors.push(format!("the address '{address}' is invalid: {error}")),
+        }
+    } else if !pairs.iter().any(|(name, _)| name == "lightning") {
+        errors.push("the address is missing".to_owned());
+    }
+
+    let mut parameters = Map::new();
+    for (name, value) in pairs {
+        match name.as_str() {
+            "amount" => {
+                let amount = value.parse::<f64>().unwrap_or_default();
+                if !is_decimal(&value, 8) || amount <= 0.0 || amount > MAX_BITCOIN {
+                    errors.push(format!(
+                        "the amount '{value}' is not from 0.00000001 to 21000000 bitcoin with at most eight decimals"
+                    ));
+                }
+                fields.insert(name, value.into());
+            }
+            "label" | "message" => {
+                fields.insert(name, value.into());
+            }
+            "lightning" => {
+                let mut invoice = Map::new();
+                let mut invoice_errors = Vec::new();
+                parse_lightning(&mut invoice, &mut invoice_errors, &value);
+                errors.extend(
+                    invoice_errors
+                        .into_iter()
+                        .map(|error| format!("lightning: {error}")),
+                );
+                fields.insert("lightning".to_owned(), invoice.into());
+            }
+            _ => {
+                // BIP 21: a wallet must refuse a request with a required parameter it does not know
+                if name.starts_with("req-") {
+                    errors.push(format!("the required parameter '{name}' is not understood"));
+                }
+                parameters.insert(name, value.into());
+            }
+        }
+    }
+    if !parameters.is_empty() {
+        fields.insert("parameters".to_owned(), parameters.into());
+    }
+}
+
+fn parse_lightning(fields: &mut Map<String, Value>, errors: &mut Vec<String>, text: &str) {
+    fields.insert("invoice".to_owned(), text.into());
+    let (hrp, words) = match checksum::bech32_decode(text) {
+        Ok((hrp, words, Bech32Variant::Bech32)) => (hrp, words),
+        Ok(_) => {
+            errors.push("it has a bech32m checksum, BOLT 11 and LNURL use bech32".to_owned());
+            return;
+        }
+        Err(error) => {
+            errors.push(format!("it is not bech32: {error}"));
+            return;
+        }
+    };
+    if hrp == "lnurl" {
+        match checksum::five_to_eight_bits(&words).and_then(|bytes| String::from_utf8(bytes).ok()) {
+            Some(url) => {
+                fields.insert("type".to_owned(), "lnurl".into());
+                fields.insert("url".to_owned(), url.into());
+            }
+            None => errors.push("the LNURL does not hold a URL".to_owned()),
+        }
+        return;
+    }
+    let Some(amount) = hrp.strip_prefix("ln") else {
+        errors.push(format!(
+            "the prefix '{hrp}' is neither a BOLT 11 invoice (ln...) nor an LNURL"
+        ));
+        return;
+    };
+    fields.insert("type".to_owned(), "bolt11".into());
+    parse_bolt11(fields, errors, amount, &words);
+}
+
+/// The fields of a BOLT 11 invoice. The signature is not checked, the payee is only known
+/// when the invoice names it.
+fn parse_bolt11(
+    fields: &mut Map<String, Value>,
+    errors: &mut Vec<String>,
+    currency_and_amount: &str,
+    words: &[u8],
+) {
+    let currency = ["bcrt", "bc", "tbs", "tb", "sb"]
+        .into_iter()
+        .find(|currency| currency_and_amount.starts_with(currency));
+    let Some(currency) = currency else {
+        errors.push(format!(
+            "'{currency_and_amount}' does not start with a currency of BOLT 11"
+        ));
+        return;
+    };
+    let network = match currency {
+        "bc" => "mainnet",
+        "tb" => "testnet",
+        "tbs" => "signet",
+        "bcrt" => "regtest",
+        _ => "simnet",
+    };
+    fields.insert("network".to_owned(), network.into());
+    let amount = &currency_and_amount[currency.len()..];
+    if !amount.is_empty() {
+        match amount_msat(amount) {
+            Some(msat) => {
+                fields.insert("amount_msat".to_owned(), msat.into());
+            }
+            None => errors.push(format!(
+                "the amount '{amount}' is not a number followed by an optional m, u, n or p"
+            )),
+        }
+    }
+
+    if words.len() < TIMESTAMP_WORDS + SIGNATURE_WORDS {
+        errors.push("the invoice is too short for a timestamp and a signature".to_owned());
+        return;
+    }
+    let (data, signature) = words.split_at(words.len() - SIGNATURE_WORDS);
+    let timestamp = number(&data[..TIMESTAMP_WORDS]);
+    if let Some(time) = DateTime::from_timestamp(timestamp as i64, 0) {
+        fields.insert("timestamp".to_owned(), time.to_rfc3339().into());
+    }
+
+    let mut expiry = DEFAULT_EXPIRY;
+    let mut rest = &data[TIMESTAMP_WORDS..];
+    while !rest.is_empty() {
+        if rest.len() < 3 {
+            errors.push("a tagged field is cut short".to_owned());
+            break;
+        }
+        let tag = BECH32_CHARSET[usize::from(rest[0])] as char;
+        let length = usize::from(rest[1]) * 32 + usize::from(rest[2]);
+        let Some(value) = rest.get(3..3 + length) else {
+            errors.push(format!("the tagged field '{tag}' is cut short"));
+            break;
+        };
+        rest = &rest[3 + length..];
+
+        // fields of an unexpected length must be skipped, they may come from a later version
+        let bytes = |name: &str, expected: usize, fields: &mut Map<String, Value>| {
+            if value.len() == expected {
+                if let Some(bytes) = checksum::five_to_eight_bits(value) {
+                    fields.insert(name.to_owned(), hex(&bytes).into());
+                }
+            }
+        };
+        match tag {
+            'p' => bytes("payment_hash", 52, fields),
+            's' => bytes("payment_secret", 52, fields),
+            'h' => bytes("description_hash", 52, fields),
+            'n' => bytes("payee", 53, fields),
+            'd' => match checksum::five_to_eight_bits(value)
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+            {
+                Some(description) => {
+                    fields.insert("description".to_owned(), description.into());
+                }
+                None => errors.push("the description is not UTF-8".to_owned()),
+            },
+            'x' => expiry = number(value),
+            'c' => {
+                fields.insert("min_final_cltv_expiry".to_owned(), number(value).into());
+            }
+            _ => {}
+        }
+    }
+    fields.insert("expiry".to_owned(), expiry.into());
+    if let Some(time) = DateTime::from_timestamp(timestamp.saturating_add(expiry) as i64, 0) {
+        fields.insert("expires".to_owned(), time.to_rfc3339().into());
+    }
+    if let Some(signature) = checksum::five_to_eight_bits(signature) {
+        fields.insert("signature".to_owned(), hex(&signature).into());
+    }
+
+    if !fields.contains_key("payment_hash") {
+        errors.push("the payment hash (p) is missing".to_owned());
+    }
+    if !fields.contains_key("description") && !fields.contains_key("description_hash") {
+        errors.push("neither a description (d) nor its hash (h) is given".to_owned());
+    }
+}
+
+/// The amount of a BOLT 11 prefix in millisatoshi: a number of bitcoin, or of milli, micro,
+/// nano or pico bitcoin.
+// is_multiple_of needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn amount_msat(amount: &str) -> Option<u64> {
+    let (digits, multiplier) = match amount.char_indices().last()? {
+        (i, 'm' | 'u' | 'n' | 'p') => amount.split_at(i),
+        _ => (amount, ""),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) || digits.starts_with('0') {
+        return None;
+    }
+    let value: u64 = digits.parse().ok()?;
+    match multiplier {
+        "" => value.checked_mul(MSAT_PER_BITCOIN),
+        "m" => value.checked_mul(MSAT_PER_BITCOIN / 1_000),
+        "u" => value.checked_mul(MSAT_PER_BITCOIN / 1_000_000),
+        "n" => value.checked_mul(MSAT_PER_BITCOIN / 1_000_000_000),
+        // a pico bitcoin is a tenth of a millisatoshi
+        _ => (value % 10 == 0).then_some(value / 10),
+    }
+}
+
+/// A big endian number of 5 bit words.
+fn number(words: &[u8]) -> u64 {
+    words
+        .iter()
+        .fold(0, |number, word| (number << 5) | u64::from(*word))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The UPI parameters and the names they are given in the output.
+const UPI_FIELDS: &[(&str, &str)] = &[
+    ("pa", "payee_vpa"),
+    ("pn", "payee_name"),
+    ("am", "amount"),
+    ("mam", "minimum_amount"),
+    ("cu", "currency"),
+    ("tn", "note"),
+    ("tr", "transaction_ref"),
+    ("tid", "transaction_id"),
+    ("mc", "merchant_code"),
+    ("url", "url"),
+    ("mode", "mode"),
+    ("purpose", "purpose"),
+    ("orgid", "org_id"),
+    ("sign", "signature"),
+];
+
+fn parse_upi(payment: &mut PaymentUri, rest: &str) {
+    let (fields, errors) = (&mut payment.fields, &mut payment.errors);
+    let rest = rest.strip_prefix("//").unwrap_or(rest);
+    let (action, query) = rest.split_once('?').unwrap_or((rest, ""));
+    if !action.eq_ignore_ascii_case("pay") {
+        errors.push(format!("the action '{action}' is not pay"));
+    }
+
+    let mut parameters = Map::new();
+    for (name, value) in uri::query_pairs(query) {
+        match UPI_FIELDS.iter().find(|(parameter, _)| *parameter == name) {
+            Some((_, field)) => {
+                fields.insert((*field).to_owned(), value.into());
+            }
+            None => {
+                parameters.insert(name, value.into());
+            }
+        }
+    }
+
+    match fields.get("payee_vpa").and_then(Value::as_str) {
+        Some(vpa) if !is_vpa(vpa) => errors.push(format!(
+            "the payee address '{vpa}' is not a handle and a PSP, e.g. name@bank"
+        )),
+        Some(_) => {}
+        None => errors.push("the payee address (pa) is missing".to_owned()),
+    }
+    if !fields.contains_key("payee_name") {
+        errors.push("the payee name (pn) is missing".to_owned());
+    }
+    for field in ["amount", "minimum_amount"] {
+        if let Some(amount) = fields.get(field).and_then(Value::as_str) {
+            if !is_decimal(amount, 2) || amount.parse::<f64>().unwrap_or_default() <= 0.0 {
+                errors.push(format!(
+                    "the {} '{amount}' is not a positive number of rupees with at most two decimals",
+                    field.replace('_', " ")
+                ));
+            }
+        }
+    }
+    if let Some(currency) = fields.get("currency").and_then(Value::as_str) {
+        if currency != "INR" {
+            errors.push(format!("the currency '{currency}' is not INR"));
+        }
+    }
+    if let Some(code) = fields.get("merchant_code").and_then(Value::as_str) {
+        if code.len() != 4 || !code.bytes().all(|b| b.is_ascii_digit()) {
+            errors.push(format!(
+                "the merchant code '{code}' is not a four digit merchant category code"
+            ));
+        }
+    }
+    if !parameters.is_empty() {
+        fields.insert("parameters".to_owned(), parameters.into());
+    }
+}
+
+/// A handle of letters, digits, dots, hyphens and underscores, an @, and the name of the
+/// payment service provider.
+fn is_vpa(vpa: &str) -> bool {
+    let Some((handle, psp)) = vpa.split_once('@') else {
+        return false;
+    };
+    (2..=256).contains(&handle.len())
+        && handle
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_'))
+        && (2..=64).contains(&psp.len())
+        && psp.bytes().all(|b| b.is_ascii_alphanumeric())
+        && psp.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+/// Digits with an optional decimal point and at most `decimals` digits after it.
+fn is_decimal(value: &str, decimals: usize) -> bool {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    !whole.is_empty()
+        && fraction.len() <= decimals
+        && whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 5 bit tagged field of an invoice.
+    fn tagged(tag: char, value: &[u8]) -> Vec<u8> {
+        let tag = BECH32_CHARSET.iter().position(|c| *c == tag as u8).unwrap() as u8;
+        let length = value.len() as u8;
+        [tag, length / 32, length % 32]
+            .into_iter()
+            .chain(value.iter().copied())
+            .collect()
+    }
+
+    fn eight_to_five_bits(bytes: &[u8]) -> Vec<u8> {
+        let mut words = Vec::new();
+        let (mut accumulator, mut bits) = (0u32, 0);
+        for byte in bytes {
+            accumulator = (accumulator << 8) | u32::from(*byte);
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                words.push(((accumulator >> bits) & 31) as u8);
+            }
+        }
+        if bits > 0 {
+            words.push(((accumulator << (5 - bits)) & 31) as u8);
+        }
+        words
+    }
+
+    /// An invoice of 2500 micro bitcoin at 2017-06-01T10:57:38Z with a payment hash, a
+    /// description and an expiry of a minute, and a zero signature.
+    fn invoice() -> String {
+        let mut words = vec![1, 12, 18, 31, 28, 25, 2];
+        words.extend(tagged('p', &eight_to_five_bits(&[1; 32])));
+        words.extend(tagged('d', &eight_to_five_bits(b"1 cup coffee")));
+        words.extend(tagged('x', &[1, 28]));
+        words.extend([0; SIGNATURE_WORDS]);
+        checksum::bech32_encode("lnbc2500u", &words)
+    }
+
+    #[test]
+    fn bitcoin() {
+        let text = "bitcoin:1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa?amount=20.3&label=Luke-Jr&x=1";
+        let payment = parse(text).unwrap();
+        assert!(payment.valid, "{:?}", payment.errors);
+        assert_eq!(payment.fields["network"], "mainnet");
+        assert_eq!(payment.fields["amount"], "20.3");
+        assert_eq!(payment.fields["parameters"]["x"], "1");
+
+        for text in [
+            "bitcoin:1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb",
+            "bitcoin:1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa?amount=0.000000001",
+            "bitcoin:1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa?req-unknown=1",
+            "bitcoin:?label=nothing",
+        ] {
+            assert!(validate(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn lightning() {
+        let payment = parse(&format!("lightning:{}", invoice())).unwrap();
+        assert!(payment.valid, "{:?}", payment.errors);
+        assert_eq!(payment.fields["amount_msat"], 250_000_000);
+        assert_eq!(payment.fields["timestamp"], "2017-06-01T10:57:38+00:00");
+        assert_eq!(payment.fields["description"], "1 cup coffee");
+        assert_eq!(payment.fields["payment_hash"], "01".repeat(32));
+        assert_eq!(payment.fields["expiry"], 60);
+
+        let mut broken = invoice();
+        broken.pop();
+        assert!(validate(&format!("lightning:{broken}")).is_err());
+        let no_hash = checksum::bech32_encode("lnbc", &[0; TIMESTAMP_WORDS + SIGNATURE_WORDS]);
+        assert!(validate(&format!("lightning:{no_hash}")).is_err());
+    }
+
+    #[test]
+    fn amounts_in_millisatoshi() {
+        assert_eq!(amount_msat("2500u"), Some(250_000_000));
+        assert_eq!(amount_msat("1"), Some(MSAT_PER_BITCOIN));
+        assert_eq!(amount_msat("10p"), Some(1));
+        assert_eq!(amount_msat("11p"), None);
+        assert_eq!(amount_msat("025m"), None);
+        assert_eq!(amount_msat("m"), None);
+    }
+
+    #[test]
+    fn upi() {
+        let text = build_upi(Upi {
+            vpa: "shop@okbank".to_owned(),
+            name: Some("Corner Shop".to_owned()),
+            amount: Some("250".to_owned()),
+            currency: "INR".to_owned(),
+            ..Upi::default()
+        })
+        .unwrap();
+        assert_eq!(
+            text,
+            "upi://pay?pa=shop@okbank&pn=Corner%20Shop&am=250.00&cu=INR"
+        );
+        assert!(parse(&text).unwrap().valid);
+
+        for text in [
+            "upi://pay?pa=shop&pn=Shop",
+            "upi://pay?pa=shop@okbank",
+            "upi://pay?pa=shop@okbank&pn=Shop&am=-1",
+            "upi://pay?pa=shop@okbank&pn=Shop&cu=USD",
+            "upi://collect?pa=shop@okbank&pn=Shop",
+        ] {
+            assert!(validate(text).is_err(), "{text}");
+        }
+        assert!(parse("mailto:someone@example.com").is_none());
+    }
+}
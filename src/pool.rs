@@ -0,0 +1,118 @@
+use std::{
+    collections::BTreeMap,
+    sync::{atomic::AtomicUsize, atomic::Ordering, mpsc},
+    thread,
+};
+
+/// Run `work` over every item on a pool of `jobs` threads. Results are handed to `on_result`
+/// on the calling thread, either in the order of `items` (`ordered`) or as soon as they finish.
+pub fn run<T, R, W, F>(items: &[T], jobs: usize, ordered: bool, work: W, mut on_result: F)
+where
+    T: Sync,
+    R: Send,
+    W: Fn(&T) -> R + Sync,
+    F: FnMut(R),
+{
+    if jobs <= 1 || items.len() <= 1 {
+        items.iter().map(&work).for_each(on_result);
+        return;
+    }
+
+    let next_item = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            let sender = sender.clone();
+            let (next_item, work) = (&next_item, &work);
+            scope.spawn(move || loop {
+                let index = next_item.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                if sender.send((index, work(item))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        let mut pending = BTreeMap::new();
+        let mut next_to_emit = 0;
+        for (index, result) in receiver {
+            if !ordered {
+                on_result(result);
+                continue;
+            }
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next_to_emit) {
+                on_result(result);
+                next_to_emit += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    /// Item 0 waits for `done`, so it finishes after item 1.
+    fn wait_for(done: &AtomicBool, item: &u32) -> u32 {
+        while *item == 0 && !done.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        *item
+    }
+
+    #[test]
+    fn ordered_keeps_the_order_of_the_items() {
+        let done = AtomicBool::new(false);
+        let work = |item: &u32| {
+            let result = wait_for(&done, item);
+            done.store(true, Ordering::SeqCst);
+            result
+        };
+        let mut results = Vec::new();
+        run(&[0, 1], 2, true, work, |result| results.push(result));
+        assert_eq!(results, [0, 1]);
+    }
+
+    #[test]
+    fn unordered_hands_over_results_as_they_finish() {
+        let done = AtomicBool::new(false);
+        let mut results = Vec::new();
+        run(
+            &[0, 1],
+            2,
+            false,
+            |item| wait_for(&done, item),
+            |result| {
+                results.push(result);
+                done.store(true, Ordering::SeqCst);
+            },
+        );
+        assert_eq!(results, [1, 0]);
+    }
+
+    #[test]
+    fn errors_reach_the_caller_in_place() {
+        let mut results = Vec::new();
+        let items = ["1", "x", "3", "", "5"];
+        run(
+            &items,
+            3,
+            true,
+            |item| item.parse::<u32>(),
+            |result| results.push(result.ok()),
+        );
+        assert_eq!(results, [Some(1), None, Some(3), None, Some(5)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panic_in_a_worker_reaches_the_caller() {
+        run(&[1, 2, 3], 2, true, |item| assert_ne!(*item, 2), |_| ());
+    }
+}
@@ -0,0 +1,490 @@
+use std::{collections::BTreeMap, io::Read, path::Path};
+
+use base64::Engine;
+use chrono::DateTime;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::CliError;
+
+pub const PREFIX: &str = "shc:/";
+
+/// The numeric encoding stores every JWS character as its code minus this, in two digits.
+const NUMERIC_OFFSET: u32 = 45;
+
+/// Issuer keys to check signatures with, from a JSON Web Key Set. Only P-256 keys are used,
+/// the one algorithm SMART Health Cards are signed with.
+#[derive(Default)]
+pub struct KeySet {
+    keys: Vec<(Option<String>, VerifyingKey)>,
+}
+
+impl KeySet {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Load the keys of a JWKS file, such as an issuer's `/.well-known/jwks.json`.
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let name = path.to_string_lossy();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| CliError::Io(format!("{name} cannot be read: {e}")))?;
+        let json: Value = serde_json::from_str(&text)
+            .map_err(|e| CliError::InvalidHint(format!("{name} is not a JWKS file: {e}")))?;
+        let keys = json
+            .get("keys")
+            .and_then(Value::as_array)
+            .ok_or_else(|| CliError::InvalidHint(format!("{name} has no \"keys\" array")))?;
+
+        let mut key_set = KeySet::default();
+        for key in keys {
+            if key.get("kty").and_then(Value::as_str) != Some("EC")
+                || key.get("crv").and_then(Value::as_str) != Some("P-256")
+            {
+                continue;
+            }
+            let kid = key.get("kid").and_then(Value::as_str).map(str::to_owned);
+            let coordinate = |name: &str| {
+                key.get(name)
+                    .and_then(Value::as_str)
+                    .and_then(|c| base64url_decode(c).ok())
+                    .filter(|c| c.len() == 32)
+            };
+            let verifying_key = match (coordinate("x"), coordinate("y")) {
+                (Some(x), Some(y)) => {
+                    VerifyingKey::from_sec1_bytes(&[&[0x04][..], &x, &y].concat()).ok()
+                }
+                _ => None,
+            };
+            let verifying_key = verifying_key.ok_or_else(|| {
+                CliError::InvalidHint(format!(
+                    "{name}: the key '{}' is not a valid P-256 point",
+                    kid.as_deref().unwrap_or("without kid")
+                ))
+            })?;
+            key_set.keys.push((kid, verifying_key));
+        }
+        if key_set.is_empty() {
+            return Err(CliError::InvalidHint(format!(
+                "{name} contains no P-256 (ES256) keys"
+            )));
+        }
+        Ok(key_set)
+    }
+
+    /// The keys with ID `kid`, and those without an ID.
+    fn keys_for<'a>(&'a self, kid: Option<&'a str>) -> impl Iterator<Item = &'a VerifyingKey> {
+        self.keys
+            .iter()
+            .filter(move |(key_id, _)| key_id.is_none() || key_id.as_deref() == kid)
+            .map(|(_, key)| key)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Shc {
+    /// The number of QR codes a chunked card was put together from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub chunks: Option<usize>,
+    /// The JWS header: kid, alg and zip.
+    pub header: Map<String, Value>,
+    /// The issuer, validity and credential types, with times in RFC 3339.
+    pub claims: Map<String, Value>,
+    /// The FHIR bundle of patient, immunization, observation etc. resources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fhir_bundle: Option<Value>,
+    /// `valid`, `invalid`, `unknown key` or `not checked` when no keys were given.
+    pub signature: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl Shc {
+    fn new() -> Self {
+        Shc {
+            chunks: None,
+            header: Map::new(),
+            claims: Map::new(),
+            fhir_bundle: None,
+            signature: "not checked",
+            errors: Vec::new(),
+            valid: false,
+        }
+    }
+
+    /// Whether keys were given but none of them verifies the signature.
+    pub fn failed_verification(&self) -> bool {
+        matches!(self.signature, "invalid" | "unknown key")
+    }
+
+    /// The header, signature and claims, followed by the FHIR bundle as indented JSON.
+    pub fn to_text(&self) -> String {
+        let header = |name: &str| {
+            self.header
+                .get(name)
+                .and_then(Value::as_str)
+                .unwrap_or("none")
+                .to_owned()
+        };
+        let mut text = format!(
+            "SHC: kid {}, alg {}\n  signature: {}",
+            header("kid"),
+            header("alg"),
+            self.signature
+        );
+        if let Some(chunks) = self.chunks {
+            text.push_str(&format!("\n  chunks: {chunks}"));
+        }
+        for (name, value) in &self.claims {
+            let value = value.as_str().map_or(value.to_string(), str::to_owned);
+            text.push_str(&format!("\n  {name}: {value}"));
+        }
+        if let Some(bundle) = &self.fhir_bundle {
+            let json = serde_json::to_string_pretty(bundle).unwrap_or_default();
+            text.push_str("\n  fhir_bundle: ");
+            text.push_str(&json.replace('\n', "\n  "));
+        }
+        for error in &self.errors {
+            text.push_str(&format!("\n  error: {error}"));
+        }
+        text
+    }
+}
+
+/// The chunks of a card by their chunk number, with the position and digits of the QR code
+/// carrying each chunk.
+type Chunks<'a> = BTreeMap<usize, (usize, &'a str)>;
+
+/// Decode the SMART Health Cards in the texts of one image, lined up with them. The chunks of
+/// a card split over several QR codes are put together in the position of its first chunk,
+/// the positions of the other chunks are left empty. Cards with as many chunks are told apart
+/// by the order their chunks were read in.
+pub fn decode_all(texts: &[&str], keys: &KeySet) -> Vec<Option<Shc>> {
+    let mut cards: Vec<Option<Shc>> = texts.iter().map(|_| None).collect();
+    // the number of chunks and the chunks of every chunked card, in the order they were read
+    let mut chunked: Vec<(usize, Chunks)> = Vec::new();
+
+    for (position, text) in texts.iter().enumerate() {
+        match split_chunk(text) {
+            Ok(Some((chunk, total, digits))) => {
+                // each chunk joins the first card that has no chunk of its number yet
+                let card = chunked
+                    .iter()
+                    .position(|(card_total, chunks)| {
+                        *card_total == total && !chunks.contains_key(&chunk)
+                    })
+                    .unwrap_or_else(|| {
+                        chunked.push((total, Chunks::new()));
+                        chunked.len() - 1
+                    });
+                chunked[card].1.insert(chunk, (position, digits));
+            }
+            Ok(None) => {
+                let digits = text.get(PREFIX.len()..).unwrap_or_default();
+                cards[position] = Some(decode_numeric(digits, keys));
+            }
+            Err(error) => {
+                let mut shc = Shc::new();
+                shc.errors.push(error);
+                cards[position] = Some(shc);
+            }
+        }
+    }
+
+    for (total, chunks) in chunked {
+        let first = chunks
+            .values()
+            .map(|(position, _)| *position)
+            .min()
+            .unwrap_or_default();
+        let mut errors = Vec::new();
+        let mut digits = String::new();
+        for chunk in 1..=total {
+            match chunks.get(&chunk) {
+                Some((_, chunk_digits)) => digits.push_str(chunk_digits),
+                None => errors.push(format!("chunk {chunk} of {total} is missing")),
+            }
+        }
+        let mut shc = if errors.is_empty() {
+            decode_numeric(&digits, keys)
+        } else {
+            Shc::new()
+        };
+        shc.chunks = Some(total);
+        shc.errors.splice(0..0, errors);
+        shc.valid = shc.errors.is_empty() && !shc.failed_verification();
+        cards[first] = Some(shc);
+    }
+    cards
+}
+
+/// The chunk number, number of chunks and digits of a `shc:/<chunk>/<total>/<digits>` text,
+/// or `None` for a card in a single QR code.
+fn split_chunk(text: &str) -> Result<Option<(usize, usize, &str)>, String> {
+    let rest = match text.get(..PREFIX.len()) {
+        Some(prefix) if prefix.eq_ignore_ascii_case(PREFIX) => &text[PREFIX.len()..],
+        _ => return Err(format!("the text does not start with '{PREFIX}'")),
+    };
+    let mut parts = rest.splitn(3, '/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(chunk), Some(total), Some(digits)) => {
+            let chunk: usize = chunk
+                .parse()
+                .map_err(|_| format!("the chunk number '{chunk}' is not a number"))?;
+            let total: usize = total
+                .parse()
+                .map_err(|_| format!("the number of chunks '{total}' is not a number"))?;
+            if chunk == 0 || chunk > total {
+                return Err(format!("chunk {chunk} of {total} is out of range"));
+            }
+            Ok(Some((chunk, total, digits)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn decode_numeric(digits: &str, keys: &KeySet) -> Shc {
+    let mut shc = Shc::new();
+    if let Err(error) = decode_into(&mut shc, digits, keys) {
+        shc.errors.push(error);
+    }
+    shc.valid = shc.errors.is_empty() && !shc.failed_verification();
+    shc
+}
+
+fn decode_into(shc: &mut Shc, digits: &str, keys: &KeySet) -> Result<(), String> {
+    let jws = numeric_decode(digits)?;
+    let parts: Vec<&str> = jws.split('.').collect();
+    let [header, payload, signature] = parts.as_slice() else {
+        return Err(format!(
+            "the JWS has {} parts instead of a header, payload and signature",
+            parts.len()
+        ));
+    };
+
+    let header_json: Value = serde_json::from_slice(&base64url_decode(header)?)
+        .map_err(|e| format!("the JWS header is not JSON: {e}"))?;
+    let Value::Object(header_json) = header_json else {
+        return Err("the JWS header is not a JSON object".to_owned());
+    };
+    shc.header = header_json;
+
+    let mut payload_bytes = base64url_decode(payload)?;
+    if shc.header.get("zip").and_then(Value::as_str) == Some("DEF") {
+        let mut inflated = Vec::new();
+        flate2::read::DeflateDecoder::new(payload_bytes.as_slice())
+            .read_to_end(&mut inflated)
+            .map_err(|e| format!("the deflated payload is damaged: {e}"))?;
+        payload_bytes = inflated;
+    }
+    let payload_json: Value = serde_json::from_slice(&payload_bytes)
+        .map_err(|e| format!("the JWS payload is not JSON: {e}"))?;
+
+    if let Some(issuer) = payload_json.get("iss") {
+        shc.claims.insert("issuer".to_owned(), issuer.clone());
+    }
+    for (claim, name) in [("nbf", "not_before"), ("exp", "expires")] {
+        if let Some(time) = payload_json.get(claim) {
+            let time = time
+                .as_f64()
+                .and_then(|seconds| DateTime::from_timestamp(seconds as i64, 0))
+                .map_or(time.clone(), |time| time.to_rfc3339().into());
+            shc.claims.insert(name.to_owned(), time);
+        }
+    }
+    let credential = payload_json.get("vc");
+    if let Some(types) = credential.and_then(|vc| vc.get("type")) {
+        shc.claims.insert("types".to_owned(), types.clone());
+    }
+    shc.fhir_bundle = credential
+        .and_then(|vc| vc.pointer("/credentialSubject/fhirBundle"))
+        .cloned();
+    if shc.fhir_bundle.is_none() {
+        shc.errors
+            .push("the payload has no vc.credentialSubject.fhirBundle".to_owned());
+    }
+
+    if !keys.is_empty() {
+        let alg = shc.header.get("alg").and_then(Value::as_str);
+        if alg != Some("ES256") {
+            shc.signature = "invalid";
+            return Err(format!(
+                "the algorithm is {}, not ES256",
+                alg.unwrap_or("missing")
+            ));
+        }
+        let signature = Signature::from_slice(&base64url_decode(signature)?).map_err(|e| {
+            shc.signature = "invalid";
+            format!("the ES256 signature is malformed: {e}")
+        })?;
+        let kid = shc.header.get("kid").and_then(Value::as_str);
+        let mut candidates = keys.keys_for(kid).peekable();
+        if candidates.peek().is_none() {
+            shc.signature = "unknown key";
+            return Err(format!(
+                "no key with the ID {} is in the JWKS",
+                kid.unwrap_or("none")
+            ));
+        }
+        let signed = format!("{header}.{payload}");
+        shc.signature = if candidates.any(|key| key.verify(signed.as_bytes(), &signature).is_ok()) {
+            "valid"
+        } else {
+            shc.errors
+                .push("the ES256 signature does not match".to_owned());
+            "invalid"
+        };
+    }
+    Ok(())
+}
+
+/// Every two digits are a character of the JWS, offset by 45.
+// is_multiple_of needs Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+fn numeric_decode(digits: &str) -> Result<String, String> {
+    if digits.len() % 2 != 0 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("the numeric data is not an even number of digits".to_owned());
+    }
+    digits
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let value = u32::from(pair[0] - b'0') * 10 + u32::from(pair[1] - b'0');
+            char::from_u32(value + NUMERIC_OFFSET)
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                .ok_or(format!(
+                    "'{}{}' is not a numerically encoded JWS character",
+                    pair[0] as char, pair[1] as char
+                ))
+        })
+        .collect()
+}
+
+fn base64url_decode(text: &str) -> Result<Vec<u8>, String> {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(text.trim_end_matches('='))
+        .map_err(|e| format!("a JWS part is not base64url: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::{signature::Signer, SigningKey};
+
+    use super::*;
+
+    fn base64url(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    fn numeric(jws: &str) -> String {
+        jws.chars()
+            .map(|c| format!("{:02}", c as u32 - NUMERIC_OFFSET))
+            .collect()
+    }
+
+    fn signing_key(byte: u8) -> SigningKey {
+        SigningKey::from_bytes(&[byte; 32].into()).unwrap()
+    }
+
+    /// The numeric digits of a card with an empty FHIR bundle, signed by `key`.
+    fn card(key: &SigningKey) -> String {
+        let header = base64url(br#"{"alg":"ES256","kid":"key-1"}"#);
+        let payload = base64url(
+            br#"{"iss":"https://example.org","nbf":1622505600,"vc":{"type":["https://smarthealth.cards#health-card"],"credentialSubject":{"fhirBundle":{"resourceType":"Bundle"}}}}"#,
+        );
+        let signed = format!("{header}.{payload}");
+        let signature: Signature = key.sign(signed.as_bytes());
+        numeric(&format!("{signed}.{}", base64url(&signature.to_bytes())))
+    }
+
+    fn key_set(kid: Option<&str>, key: &SigningKey) -> KeySet {
+        KeySet {
+            keys: vec![(kid.map(str::to_owned), *key.verifying_key())],
+        }
+    }
+
+    #[test]
+    fn numeric_encoding() {
+        assert_eq!(numeric_decode("5676").unwrap(), "ey");
+        assert_eq!(numeric_decode("0001").unwrap(), "-.");
+        assert!(numeric_decode("567").is_err());
+        assert!(numeric_decode("56a6").is_err());
+        assert!(numeric_decode("99").is_err());
+    }
+
+    #[test]
+    fn chunk_numbers() {
+        assert_eq!(split_chunk("shc:/5676"), Ok(None));
+        assert_eq!(split_chunk("SHC:/2/3/5676"), Ok(Some((2, 3, "5676"))));
+        assert!(split_chunk("shc:/0/3/5676").is_err());
+        assert!(split_chunk("shc:/4/3/5676").is_err());
+        assert!(split_chunk("shc:/x/3/5676").is_err());
+        assert!(split_chunk("HC1:5676").is_err());
+    }
+
+    #[test]
+    fn decode_single_card() {
+        let key = signing_key(1);
+        let text = format!("{PREFIX}{}", card(&key));
+        let [Some(shc)] = &decode_all(&[&text], &KeySet::default())[..] else {
+            panic!("no card");
+        };
+        assert!(shc.valid, "{:?}", shc.errors);
+        assert_eq!(shc.signature, "not checked");
+        assert_eq!(shc.claims["issuer"], "https://example.org");
+        assert_eq!(shc.claims["not_before"], "2021-06-01T00:00:00+00:00");
+
+        let check = |keys: &KeySet| decode_all(&[&text], keys)[0].clone().unwrap();
+        assert_eq!(check(&key_set(Some("key-1"), &key)).signature, "valid");
+        assert_eq!(check(&key_set(None, &key)).signature, "valid");
+        let wrong_key = check(&key_set(Some("key-1"), &signing_key(2)));
+        assert_eq!(wrong_key.signature, "invalid");
+        assert!(!wrong_key.valid);
+        let unknown = check(&key_set(Some("key-2"), &key));
+        assert_eq!(unknown.signature, "unknown key");
+        assert!(!unknown.valid);
+    }
+
+    #[test]
+    fn decode_chunked_card() {
+        let digits = card(&signing_key(1));
+        let (first, second) = digits.split_at(digits.len() / 2);
+        let second = format!("shc:/2/2/{second}");
+        let first = format!("shc:/1/2/{first}");
+        let cards = decode_all(&[&second, &first], &KeySet::default());
+        let shc = cards[0].as_ref().unwrap();
+        assert!(shc.valid, "{:?}", shc.errors);
+        assert_eq!(shc.chunks, Some(2));
+        assert!(cards[1].is_none());
+
+        let cards = decode_all(&[&first], &KeySet::default());
+        assert!(!cards[0].as_ref().unwrap().valid);
+        // a chunk read twice starts another card
+        let cards = decode_all(&[&first, &first, &second], &KeySet::default());
+        assert!(cards[0].as_ref().unwrap().valid);
+        let again = cards[1].as_ref().unwrap();
+        assert_eq!(again.errors, ["chunk 2 of 2 is missing"]);
+    }
+
+    #[test]
+    fn cards_with_as_many_chunks() {
+        let chunks = |key: u8| {
+            let digits = card(&signing_key(key));
+            let (first, second) = digits.split_at(digits.len() / 2);
+            (format!("shc:/1/2/{first}"), format!("shc:/2/2/{second}"))
+        };
+        let (a1, a2) = chunks(1);
+        let (b1, b2) = chunks(2);
+        let cards = decode_all(&[&a1, &b1, &a2, &b2], &KeySet::default());
+        for (position, key) in [(0, 1), (1, 2)] {
+            let shc = cards[position].as_ref().unwrap();
+            assert!(shc.valid, "{:?}", shc.errors);
+            let keys = key_set(None, &signing_key(key));
+            let verified = decode_all(&[&a1, &b1, &a2, &b2], &keys);
+            assert_eq!(verified[position].as_ref().unwrap().signature, "valid");
+        }
+        assert!(cards[2].is_none() && cards[3].is_none());
+    }
+}
@@ -0,0 +1,267 @@
+use rxing::{common::BitMatrix, BarcodeFormat};
+
+use crate::{encode_options::format_name, error::CliError};
+
+/// Pixels per module when no size or bounds are given.
+pub const DEFAULT_MODULE_SIZE: u32 = 4;
+
+/// Bar height, in modules, of one dimensional barcodes drawn without a --height.
+pub const DEFAULT_BAR_HEIGHT: u32 = 50;
+
+/// How the symbol should be scaled into an image, from the `encode` command line.
+pub struct Sizing {
+    pub module_size: Option<u32>,
+    pub quiet_zone: Option<u32>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+pub fn is_one_dimensional(format: &BarcodeFormat) -> bool {
+    !matches!(
+        format,
+        BarcodeFormat::QR_CODE
+            | BarcodeFormat::PDF_417
+            | BarcodeFormat::AZTEC
+            | BarcodeFormat::DATA_MATRIX
+            | BarcodeFormat::MAXICODE
+    )
+}
+
+/// Quiet zone, in modules, that the specification of each format asks for.
+pub fn default_quiet_zone(format: &BarcodeFormat) -> u32 {
+    match format {
+        BarcodeFormat::QR_CODE => 4,
+        BarcodeFormat::PDF_417 => 2,
+        BarcodeFormat::AZTEC | BarcodeFormat::DATA_MATRIX | BarcodeFormat::MAXICODE => 1,
+        _ => 10,
+    }
+}
+
+/// Surround a laid out `image` with `margin` blank pixels, only on the sides of one
+/// dimensional barcodes. This is the border the legacy --margin gave, in pixels.
+pub fn add_margin(
+    format: &BarcodeFormat,
+    image: &BitMatrix,
+    margin: u32,
+) -> Result<BitMatrix, CliError> {
+    let top = if is_one_dimensional(format) {
+        0
+    } else {
+        margin
+    };
+    let mut framed = BitMatrix::new(image.getWidth() + 2 * margin, image.getHeight() + 2 * top)
+        .map_err(|e| CliError::EncodeFailure(format!("Couldn't add the margin: {e}")))?;
+    for y in 0..image.getHeight() {
+        for x in 0..image.getWidth() {
+            if image.get(x, y) {
+                framed.set(x + margin, y + top);
+            }
+        }
+    }
+    Ok(framed)
+}
+
+impl Sizing {
+    /// Scale `symbol`, encoded at one pixel per module and without any margin, to a whole
+    /// number of pixels per module and surround it with the quiet zone.
+    ///
+    /// One dimensional barcodes are encoded as a single row, they only get a quiet zone on
+    /// their sides and are stretched to the requested height.
+    pub fn layout(
+        &self,
+        format: &BarcodeFormat,
+        symbol: &BitMatrix,
+    ) -> Result<BitMatrix, CliError> {
+        let one_d = is_one_dimensional(format);
+        let quiet_zone = self
+            .quiet_zone
+            .unwrap_or_else(|| default_quiet_zone(format));
+        let full_width = symbol.getWidth() + 2 * quiet_zone;
+        let full_height = if one_d {
+            None
+        } else {
+            Some(symbol.getHeight() + 2 * quiet_zone)
+        };
+
+        let module_size = self.module_size(format, full_width, full_height)?;
+        let image_width = full_width * module_size;
+        let image_height = match full_height {
+            Some(full_height) => full_height * module_size,
+            None => self
+                .height
+                .unwrap_or(DEFAULT_BAR_HEIGHT * module_size)
+                .max(1),
+        };
+
+        let mut image = BitMatrix::new(image_width, image_height)
+            .map_err(|e| CliError::EncodeFailure(format!("Couldn't scale symbol: {e}")))?;
+        let top = if one_d { 0 } else { quiet_zone };
+        for y in 0..symbol.getHeight() {
+            for x in 0..symbol.getWidth() {
+                if !symbol.get(x, y) {
+                    continue;
+                }
+                let (left, width) = ((x + quiet_zone) * module_size, module_size);
+                let (top, height) = if one_d {
+                    (0, image_height)
+                } else {
+                    ((y + top) * module_size, module_size)
+                };
+                image
+                    .setRegion(left, top, width, height)
+                    .map_err(|e| CliError::EncodeFailure(format!("Couldn't scale symbol: {e}")))?;
+            }
+        }
+        Ok(image)
+    }
+
+    /// The explicit --module-size, checked against any bounds, or the largest module size
+    /// that fits the bounds. `full_height` is `None` for one dimensional barcodes, whose
+    /// height does not depend on the module size.
+    fn module_size(
+        &self,
+        format: &BarcodeFormat,
+        full_width: u32,
+        full_height: Option<u32>,
+    ) -> Result<u32, CliError> {
+        let name = format_name(format);
+        let bounds = [
+            ("width", "wide", self.width, Some(full_width)),
+            ("height", "high", self.height, full_height),
+        ];
+
+        if let Some(module_size) = self.module_size {
+            for (flag, extent, bound, modules) in bounds {
+                if let (Some(bound), Some(modules)) = (bound, modules) {
+                    if modules * module_size > bound {
+                        return Err(CliError::InvalidHint(format!(
+                            "This {name} symbol is {} pixels {extent} with --module-size {module_size}, more than --{flag} {bound}",
+                            modules * module_size
+                        )));
+                    }
+                }
+            }
+            return Ok(module_size);
+        }
+
+        let mut module_size = None;
+        for (flag, _, bound, modules) in bounds {
+            if let (Some(bound), Some(modules)) = (bound, modules) {
+                let fits = bound / modules;
+                if fits == 0 {
+                    return Err(CliError::InvalidHint(format!(
+                        "--{flag} {bound} is too small, this {name} symbol needs at least {modules} pixels including its quiet zone"
+                    )));
+                }
+                module_size = Some(module_size.map_or(fits, |size: u32| size.min(fits)));
+            }
+        }
+        Ok(module_size.unwrap_or(DEFAULT_MODULE_SIZE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dot() -> BitMatrix {
+        let mut image = BitMatrix::new(2, 1).unwrap();
+        image.set(1, 0);
+        image
+    }
+
+    fn sizing(module_size: Option<u32>, width: Option<u32>, height: Option<u32>) -> Sizing {
+        Sizing {
+            module_size,
+            quiet_zone: None,
+            width,
+            height,
+        }
+    }
+
+    /// A 3x3 symbol with only its centre module set.
+    fn centre() -> BitMatrix {
+        let mut symbol = BitMatrix::new(3, 3).unwrap();
+        symbol.set(1, 1);
+        symbol
+    }
+
+    #[test]
+    fn default_module_size_and_quiet_zone() {
+        let image = sizing(None, None, None)
+            .layout(&BarcodeFormat::QR_CODE, &centre())
+            .unwrap();
+        // 3 modules and 4 of quiet zone on each side, at 4 pixels per module
+        assert_eq!((image.getWidth(), image.getHeight()), (44, 44));
+        assert!(image.get(20, 20) && image.get(23, 23));
+        assert!(!image.get(19, 20) && !image.get(24, 23));
+    }
+
+    #[test]
+    fn explicit_quiet_zone() {
+        let layout = Sizing {
+            quiet_zone: Some(0),
+            ..sizing(Some(1), None, None)
+        };
+        let image = layout.layout(&BarcodeFormat::AZTEC, &centre()).unwrap();
+        assert_eq!((image.getWidth(), image.getHeight()), (3, 3));
+        assert!(image.get(1, 1));
+    }
+
+    #[test]
+    fn largest_module_size_that_fits() {
+        // a Data Matrix symbol of 3 modules is 5 with its quiet zone
+        let image = sizing(None, Some(100), Some(32))
+            .layout(&BarcodeFormat::DATA_MATRIX, &centre())
+            .unwrap();
+        assert_eq!((image.getWidth(), image.getHeight()), (30, 30));
+
+        let error = sizing(None, Some(4), None)
+            .layout(&BarcodeFormat::DATA_MATRIX, &centre())
+            .unwrap_err();
+        assert!(
+            error.to_string().starts_with("--width 4 is too small"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn module_size_beyond_the_bounds() {
+        let error = sizing(Some(10), None, Some(40))
+            .layout(&BarcodeFormat::DATA_MATRIX, &centre())
+            .unwrap_err();
+        assert!(matches!(error, CliError::InvalidHint(_)));
+        assert_eq!(
+            error.to_string(),
+            "This DATAMATRIX symbol is 50 pixels high with --module-size 10, more than --height 40"
+        );
+    }
+
+    #[test]
+    fn one_dimensional_bars() {
+        let image = sizing(Some(2), None, None)
+            .layout(&BarcodeFormat::CODE_128, &dot())
+            .unwrap();
+        // a quiet zone of 10 modules on the sides and bars of 50 modules
+        assert_eq!((image.getWidth(), image.getHeight()), (44, 100));
+        assert!(image.get(22, 0) && image.get(23, 99));
+        assert!(!image.get(21, 0) && !image.get(24, 0));
+
+        // the height is in pixels and does not bound the module size
+        let image = sizing(None, Some(88), Some(5))
+            .layout(&BarcodeFormat::CODE_128, &dot())
+            .unwrap();
+        assert_eq!((image.getWidth(), image.getHeight()), (88, 5));
+    }
+
+    #[test]
+    fn margin_in_pixels() {
+        let framed = add_margin(&BarcodeFormat::QR_CODE, &dot(), 3).unwrap();
+        assert_eq!((framed.getWidth(), framed.getHeight()), (8, 7));
+        assert!(framed.get(4, 3));
+        // one dimensional barcodes only get it on their sides
+        let framed = add_margin(&BarcodeFormat::CODE_128, &dot(), 3).unwrap();
+        assert_eq!((framed.getWidth(), framed.getHeight()), (8, 1));
+        assert!(framed.get(4, 0));
+    }
+}
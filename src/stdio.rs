@@ -0,0 +1,300 @@
+use std::{
+    io::{Cursor, Read, Write},
+    rc::Rc,
+};
+
+use image::codecs::jpeg::{JpegEncoder, PixelDensity};
+use rxing::{
+    common::{BitMatrix, HybridBinarizer, Result},
+    multi::{GenericMultipleBarcodeReader, MultipleBarcodeReader},
+    BinaryBitmap, BufferedImageLuminanceSource, DecodeHintType, DecodeHintValue,
+    DecodingHintDictionary, Exceptions, LuminanceSource, MultiFormatReader, RXingResult, Reader,
+    SVGLuminanceSource,
+};
+
+use crate::{colour::Palette, units::MM_PER_INCH};
+
+/// File name used to read from stdin when decoding, or write to stdout when encoding.
+pub const STDIO: &str = "-";
+
+/// Image formats that `encode` can write without relying on the file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    Tiff,
+    Tga,
+    Ico,
+    Svg,
+    /// The bit matrix as plain text.
+    Txt,
+}
+
+impl ImageFormat {
+    /// The format matching the extension of `file_name`, if it is one of ours.
+    pub fn from_path(file_name: &str) -> Option<Self> {
+        let extension = std::path::Path::new(file_name)
+            .extension()?
+            .to_string_lossy()
+            .to_lowercase();
+        Some(match extension.as_str() {
+            "png" => ImageFormat::Png,
+            "jpg" | "jpeg" => ImageFormat::Jpeg,
+            "gif" => ImageFormat::Gif,
+            "bmp" => ImageFormat::Bmp,
+            "tif" | "tiff" => ImageFormat::Tiff,
+            "tga" => ImageFormat::Tga,
+            "ico" => ImageFormat::Ico,
+            "svg" => ImageFormat::Svg,
+            "txt" => ImageFormat::Txt,
+            _ => return None,
+        })
+    }
+
+    /// Whether the print resolution can be stored in files of this format.
+    pub fn stores_resolution(self) -> bool {
+        matches!(
+            self,
+            ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Svg
+        )
+    }
+
+    pub fn supports_colour(self) -> bool {
+        self != ImageFormat::Txt
+    }
+
+    pub fn supports_transparency(self) -> bool {
+        !matches!(self, ImageFormat::Jpeg | ImageFormat::Txt)
+    }
+}
+
+pub fn read_stdin() -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    std::io::stdin()
+        .lock()
+        .read_to_end(&mut data)
+        .map_err(|e| Exceptions::illegalArgumentWith(format!("could not read stdin: {e}")))?;
+    Ok(data)
+}
+
+/// Raster formats are recognised by the `image` crate from their magic bytes, SVG documents
+/// have none so look for the start of an XML or `<svg>` element instead.
+pub fn is_svg(data: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(&data[..data.len().min(1024)]);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with("<svg") || (text.starts_with("<?xml") && text.contains("<svg"))
+}
+
+fn luminance_source(data: &[u8]) -> Result<Box<dyn LuminanceSource>> {
+    if is_svg(data) {
+        Ok(Box::new(SVGLuminanceSource::new(data)?))
+    } else {
+        let img = image::load_from_memory(data).map_err(|e| {
+            Exceptions::unsupportedOperationWith(format!("image data cannot be read: {e}"))
+        })?;
+        Ok(Box::new(BufferedImageLuminanceSource::new(img)))
+    }
+}
+
+pub fn detect_in_bytes_with_hints(
+    data: &[u8],
+    hints: &mut DecodingHintDictionary,
+) -> Result<RXingResult> {
+    let mut multi_format_reader = MultiFormatReader::default();
+
+    hints
+        .entry(DecodeHintType::TRY_HARDER)
+        .or_insert(DecodeHintValue::TryHarder(true));
+
+    multi_format_reader.decode_with_hints(
+        &mut BinaryBitmap::new(Rc::new(HybridBinarizer::new(luminance_source(data)?))),
+        hints,
+    )
+}
+
+pub fn detect_multiple_in_bytes_with_hints(
+    data: &[u8],
+    hints: &mut DecodingHintDictionary,
+) -> Result<Vec<RXingResult>> {
+    let multi_format_reader = MultiFormatReader::default();
+    let mut scanner = GenericMultipleBarcodeReader::new(multi_format_reader);
+
+    hints
+        .entry(DecodeHintType::TRY_HARDER)
+        .or_insert(DecodeHintValue::TryHarder(true));
+
+    scanner.decode_multiple_with_hints(
+        &mut BinaryBitmap::new(Rc::new(HybridBinarizer::new(luminance_source(data)?))),
+        hints,
+    )
+}
+
+/// Render a bit matrix in the requested format and colours. With a `dpi` the resolution is
+/// stored in formats that support it, see [`ImageFormat::stores_resolution`].
+pub fn render(
+    bit_matrix: &BitMatrix,
+    image_format: ImageFormat,
+    dpi: Option<u32>,
+    palette: &Palette,
+) -> Result<Vec<u8>> {
+    let raster_format = match image_format {
+        ImageFormat::Svg => {
+            let mut svg = if palette.is_default() {
+                bit_matrix.into()
+            } else {
+                coloured_svg(bit_matrix, palette)
+            };
+            if let Some(dpi) = dpi {
+                let mm = |pixels: u32| format!("{:.3}mm", pixels as f64 * MM_PER_INCH / dpi as f64);
+                svg = svg
+                    .set("width", mm(bit_matrix.getWidth()))
+                    .set("height", mm(bit_matrix.getHeight()));
+            }
+            return Ok(svg.to_string().into_bytes());
+        }
+        ImageFormat::Txt => return Ok(bit_matrix.to_string().into_bytes()),
+        ImageFormat::Png => image::ImageOutputFormat::Png,
+        ImageFormat::Jpeg => image::ImageOutputFormat::Jpeg(100),
+        ImageFormat::Gif => image::ImageOutputFormat::Gif,
+        ImageFormat::Bmp => image::ImageOutputFormat::Bmp,
+        ImageFormat::Tiff => image::ImageOutputFormat::Tiff,
+        ImageFormat::Tga => image::ImageOutputFormat::Tga,
+        ImageFormat::Ico => image::ImageOutputFormat::Ico,
+    };
+
+    let image = if palette.is_default() {
+        bit_matrix.into()
+    } else {
+        coloured_image(bit_matrix, palette)
+    };
+    let mut buffer = Cursor::new(Vec::new());
+    let render_error = |e: &dyn std::fmt::Display| {
+        Exceptions::illegalArgumentWith(format!("could not render image: {e}"))
+    };
+    match (image_format, dpi) {
+        (ImageFormat::Png, Some(dpi)) => {
+            let (pixels, color) = if palette.is_default() {
+                (image.to_luma8().into_raw(), png::ColorType::Grayscale)
+            } else {
+                (image.to_rgba8().into_raw(), png::ColorType::Rgba)
+            };
+            let mut encoder = png::Encoder::new(&mut buffer, image.width(), image.height());
+            encoder.set_color(color);
+            encoder.set_depth(png::BitDepth::Eight);
+            let pixels_per_metre = (dpi as f64 / MM_PER_INCH * 1000.0).round() as u32;
+            encoder.set_pixel_dims(Some(png::PixelDimensions {
+                xppu: pixels_per_metre,
+                yppu: pixels_per_metre,
+                unit: png::Unit::Meter,
+            }));
+            encoder
+                .write_header()
+                .and_then(|mut writer| writer.write_image_data(&pixels))
+                .map_err(|e| render_error(&e))?;
+        }
+        (ImageFormat::Jpeg, Some(dpi)) => {
+            let mut encoder = JpegEncoder::new_with_quality(&mut buffer, 100);
+            encoder.set_pixel_density(PixelDensity::dpi(dpi as u16));
+            encoder
+                .encode_image(&image.to_rgb8())
+                .map_err(|e| render_error(&e))?;
+        }
+        // JPEG has no alpha channel
+        (ImageFormat::Jpeg, None) => image::DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_to(&mut buffer, raster_format)
+            .map_err(|e| render_error(&e))?,
+        _ => image
+            .write_to(&mut buffer, raster_format)
+            .map_err(|e| render_error(&e))?,
+    }
+    Ok(buffer.into_inner())
+}
+
+fn coloured_image(bit_matrix: &BitMatrix, palette: &Palette) -> image::DynamicImage {
+    let foreground = image::Rgba(palette.foreground.rgba());
+    let background = image::Rgba(palette.background.rgba());
+    image::RgbaImage::from_fn(bit_matrix.getWidth(), bit_matrix.getHeight(), |x, y| {
+        if bit_matrix.get(x, y) {
+            foreground
+        } else {
+            background
+        }
+    })
+    .into()
+}
+
+/// Like rxing's own conversion, with a background rectangle and the modules filled with the
+/// foreground colour.
+fn coloured_svg(bit_matrix: &BitMatrix, palette: &Palette) -> svg::Document {
+    use svg::node::element::{Group, Rectangle};
+
+    let (width, height) = (bit_matrix.getWidth(), bit_matrix.getHeight());
+    let mut document = svg::Document::new().set("viewBox", (0, 0, width, height));
+    if palette.background.a > 0 {
+        document = document.add(
+            Rectangle::new()
+                .set("width", width)
+                .set("height", height)
+                .set("fill", palette.background.hex())
+                .set("fill-opacity", palette.background.opacity()),
+        );
+    }
+
+    let mut modules = Group::new()
+        .set("fill", palette.foreground.hex())
+        .set("fill-opacity", palette.foreground.opacity());
+    for x in 0..width {
+        for y in 0..height {
+            if bit_matrix.get(x, y) {
+                modules = modules.add(
+                    Rectangle::new()
+                        .set("x", x)
+                        .set("y", y)
+                        .set("width", 1)
+                        .set("height", 1),
+                );
+            }
+        }
+    }
+    document.add(modules)
+}
+
+/// Save a bit matrix to `file_name`, or to stdout when it is [`STDIO`]. Without an explicit
+/// `image_format` the format is inferred from the file extension.
+pub fn save(
+    file_name: &str,
+    bit_matrix: &BitMatrix,
+    image_format: Option<ImageFormat>,
+    dpi: Option<u32>,
+    palette: &Palette,
+) -> Result<()> {
+    // only render ourselves when needed, rxing knows a few more extensions
+    let image_format = image_format.or_else(|| {
+        if dpi.is_some() || !palette.is_default() {
+            ImageFormat::from_path(file_name)
+        } else {
+            None
+        }
+    });
+    let Some(image_format) = image_format else {
+        if file_name == STDIO {
+            return Err(Exceptions::illegalArgumentWith(
+                "--image-format is required when writing to stdout",
+            ));
+        }
+        return rxing::helpers::save_file(file_name, bit_matrix);
+    };
+
+    let data = render(bit_matrix, image_format, dpi, palette)?;
+    let written = if file_name == STDIO {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&data).and_then(|_| stdout.flush())
+    } else {
+        std::fs::write(file_name, data)
+    };
+    written.map_err(|e| {
+        Exceptions::illegalArgumentWith(format!("could not save file '{file_name}': {e}"))
+    })
+}
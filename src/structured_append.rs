@@ -0,0 +1,380 @@
+use rxing::{
+    common::{BitArray, BitMatrix},
+    qrcode::{
+        decoder::{ErrorCorrectionLevel, Mode, Version},
+        encoder::{matrix_util, qrcode_encoder, ByteMatrix, QRCode},
+    },
+};
+
+use crate::{
+    encode_options::{EncodeOptions, QrErrorCorrection, SymbologyOptions},
+    error::CliError,
+};
+
+/// The most symbols a Structured Append sequence can have, the count is written in four bits.
+pub const MAX_SYMBOLS: u8 = 16;
+
+/// ECI assignment numbers of the two character sets a sequence can be written in.
+const ISO_8859_1_ECI: u32 = 3;
+const UTF_8_ECI: u32 = 26;
+
+/// The structured append header, mode indicator, position, count and parity, in bits.
+const HEADER_BITS: u32 = 4 + 4 + 4 + 8;
+const ECI_BITS: u32 = 4 + 8;
+
+/// A character and its bytes in the character set of the sequence.
+type EncodedChar = (char, Vec<u8>);
+
+/// One symbol of a sequence and the text a reader returns for it.
+pub struct Symbol {
+    pub matrix: BitMatrix,
+    pub text: String,
+}
+
+/// How the symbols of a sequence are written.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Layout {
+    /// One image per symbol, numbered like name-01-of-05.png.
+    #[default]
+    Files,
+    /// All symbols side by side in one image.
+    Row,
+    /// All symbols stacked from top to bottom in one image.
+    Column,
+}
+
+/// Split `contents` over `count` QR code symbols of one Structured Append sequence, each of
+/// one pixel per module without a quiet zone.
+///
+/// The rxing writer has no structured append mode, so the segments are built here in byte
+/// mode and only the error correction, mask and module placement are left to rxing. Every
+/// symbol has the same version, the smallest that holds the largest part unless --qr-version
+/// is given. The text is split on character boundaries, so each symbol also reads on its own.
+pub fn encode(contents: &str, count: u8, options: &EncodeOptions) -> Result<Vec<Symbol>, CliError> {
+    let SymbologyOptions::QrCode {
+        error_correction,
+        version,
+        mask_pattern,
+        ..
+    } = options.symbology
+    else {
+        return Err(CliError::InvalidHint(
+            "--structured-append is only available for QR codes".to_owned(),
+        ));
+    };
+    let ec_level = match error_correction.unwrap_or(QrErrorCorrection::L) {
+        QrErrorCorrection::L => ErrorCorrectionLevel::L,
+        QrErrorCorrection::M => ErrorCorrectionLevel::M,
+        QrErrorCorrection::Q => ErrorCorrectionLevel::Q,
+        QrErrorCorrection::H => ErrorCorrectionLevel::H,
+    };
+
+    let (latin_1, eci) = character_set(contents, options.character_set.as_deref())?;
+    let chars: Vec<EncodedChar> = contents
+        .chars()
+        .map(|c| {
+            let bytes = if latin_1 {
+                vec![c as u8]
+            } else {
+                c.to_string().into_bytes()
+            };
+            (c, bytes)
+        })
+        .collect();
+    let parts = split(&chars, count)?;
+    let parity = chars
+        .iter()
+        .flat_map(|(_, bytes)| bytes)
+        .fold(0, |parity, byte| parity ^ byte);
+
+    let largest = parts
+        .iter()
+        .map(|part| part.iter().map(|(_, bytes)| bytes.len()).sum::<usize>())
+        .max()
+        .unwrap_or_default() as u32;
+    let version = choose_version(largest, eci.is_some(), version, &ec_level)?;
+
+    parts
+        .iter()
+        .enumerate()
+        .map(|(position, part)| {
+            let mut bits = BitArray::new();
+            append(&mut bits, Mode::STRUCTURED_APPEND.getBits() as u32, 4)?;
+            append(&mut bits, position as u32, 4)?;
+            append(&mut bits, count as u32 - 1, 4)?;
+            append(&mut bits, parity as u32, 8)?;
+            if let Some(eci) = eci {
+                append(&mut bits, Mode::ECI.getBits() as u32, 4)?;
+                append(&mut bits, eci, 8)?;
+            }
+            qrcode_encoder::appendModeInfo(Mode::BYTE, &mut bits)
+                .map_err(CliError::from_encode_error)?;
+            let bytes: Vec<u8> = part.iter().flat_map(|(_, bytes)| bytes.clone()).collect();
+            qrcode_encoder::appendLengthInfo(bytes.len() as u32, version, Mode::BYTE, &mut bits)
+                .map_err(CliError::from_encode_error)?;
+            for byte in bytes {
+                append(&mut bits, byte as u32, 8)?;
+            }
+            Ok(Symbol {
+                matrix: draw(&bits, version, &ec_level, mask_pattern)?,
+                text: part.iter().map(|(c, _)| c).collect(),
+            })
+        })
+        .collect()
+}
+
+/// Whether the text is written in ISO-8859-1 rather than UTF-8, and the ECI to announce it
+/// with. Plain ASCII needs no ECI.
+fn character_set(
+    contents: &str,
+    character_set: Option<&str>,
+) -> Result<(bool, Option<u32>), CliError> {
+    let latin_1 = match character_set.map(|name| name.to_ascii_lowercase().replace('_', "-")) {
+        None => contents.chars().all(|c| c <= '\u{ff}'),
+        Some(name) if matches!(name.as_str(), "utf-8" | "utf8") => false,
+        Some(name) if matches!(name.as_str(), "iso-8859-1" | "iso8859-1" | "latin1") => {
+            if let Some(c) = contents.chars().find(|c| *c > '\u{ff}') {
+                return Err(CliError::InvalidHint(format!(
+                    "'{c}' can not be encoded in ISO-8859-1"
+                )));
+            }
+            true
+        }
+        Some(_) => {
+            return Err(CliError::InvalidHint(format!(
+                "--structured-append writes ISO-8859-1 or UTF-8, not --character-set {}",
+                character_set.unwrap_or_default()
+            )))
+        }
+    };
+    let eci = match (contents.is_ascii(), latin_1) {
+        (true, _) => None,
+        (false, true) => Some(ISO_8859_1_ECI),
+        (false, false) => Some(UTF_8_ECI),
+    };
+    Ok((latin_1, eci))
+}
+
+/// Cut the characters into `count` parts of about the same number of bytes.
+fn split(chars: &[EncodedChar], count: u8) -> Result<Vec<&[EncodedChar]>, CliError> {
+    if chars.len() < count as usize {
+        return Err(CliError::InvalidHint(format!(
+            "The data has {} characters, too few to split over {count} symbols",
+            chars.len()
+        )));
+    }
+    let total: usize = chars.iter().map(|(_, bytes)| bytes.len()).sum();
+    let mut parts = Vec::with_capacity(count as usize);
+    let (mut start, mut end, mut bytes) = (0, 0, 0);
+    for part in 1..=count as usize {
+        // leave at least one character for each of the parts still to come
+        let last_end = chars.len() - (count as usize - part);
+        let target = total * part / count as usize;
+        while end < last_end && (end == start || bytes + chars[end].1.len() <= target) {
+            bytes += chars[end].1.len();
+            end += 1;
+        }
+        parts.push(&chars[start..end]);
+        start = end;
+    }
+    Ok(parts)
+}
+
+/// The requested version, or the smallest that holds `largest` bytes with the headers.
+fn choose_version(
+    largest: u32,
+    eci: bool,
+    requested: Option<u32>,
+    ec_level: &ErrorCorrectionLevel,
+) -> Result<&'static Version, CliError> {
+    let fits = |version: &'static Version| {
+        let bits = HEADER_BITS
+            + if eci { ECI_BITS } else { 0 }
+            + 4
+            + Mode::BYTE.getCharacterCountBits(version) as u32
+            + 8 * largest;
+        qrcode_encoder::willFit(bits, version, ec_level)
+    };
+    let versions = match requested {
+        Some(number) => number..=number,
+        None => 1..=40,
+    };
+    for number in versions {
+        let version = Version::getVersionForNumber(number).map_err(CliError::from_encode_error)?;
+        if fits(version) {
+            return Ok(version);
+        }
+    }
+    Err(CliError::InvalidHint(match requested {
+        Some(number) => format!(
+            "The largest part is {largest} bytes, too big for a version {number} QR code, use more symbols or a higher --qr-version"
+        ),
+        None => format!(
+            "The largest part is {largest} bytes, too big for any QR code version, use more symbols or lower --error-correction"
+        ),
+    }))
+}
+
+/// Terminate and pad the data, add the error correction and place the modules with the
+/// requested or least penalised mask.
+fn draw(
+    bits: &BitArray,
+    version: &'static Version,
+    ec_level: &ErrorCorrectionLevel,
+    mask_pattern: Option<u32>,
+) -> Result<BitMatrix, CliError> {
+    let ec_blocks = version.getECBlocksForLevel(*ec_level);
+    let num_data_bytes = version.getTotalCodewords() - ec_blocks.getTotalECCodewords();
+    let mut bits = bits.clone();
+    qrcode_encoder::terminateBits(num_data_bytes, &mut bits)
+        .map_err(CliError::from_encode_error)?;
+    let final_bits = qrcode_encoder::interleaveWithECBytes(
+        &bits,
+        version.getTotalCodewords(),
+        num_data_bytes,
+        ec_blocks.getNumBlocks(),
+    )
+    .map_err(CliError::from_encode_error)?;
+
+    let dimension = version.getDimensionForVersion();
+    let build = |mask_pattern: u32| {
+        let mut matrix = ByteMatrix::new(dimension, dimension);
+        matrix_util::buildMatrix(
+            &final_bits,
+            ec_level,
+            version,
+            mask_pattern as i32,
+            &mut matrix,
+        )
+        .map(|_| matrix)
+        .map_err(CliError::from_encode_error)
+    };
+    let matrix = match mask_pattern {
+        Some(mask_pattern) => build(mask_pattern)?,
+        None => (0..QRCode::NUM_MASK_PATTERNS as u32)
+            .map(build)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .min_by_key(qrcode_encoder::calculateMaskPenalty)
+            .ok_or_else(|| CliError::EncodeFailure("Couldn't encode: no mask pattern".into()))?,
+    };
+
+    let mut symbol = BitMatrix::new(dimension, dimension).map_err(CliError::from_encode_error)?;
+    for y in 0..dimension {
+        for x in 0..dimension {
+            if matrix.get(x, y) == 1 {
+                symbol.set(x, y);
+            }
+        }
+    }
+    Ok(symbol)
+}
+
+fn append(bits: &mut BitArray, value: u32, count: usize) -> Result<(), CliError> {
+    bits.appendBits(value, count)
+        .map_err(CliError::from_encode_error)
+}
+
+/// `file_name` with the position of the symbol before the extension, name-01-of-05.png.
+pub fn numbered_file_name(file_name: &str, position: usize, count: usize) -> String {
+    let path = std::path::Path::new(file_name);
+    let numbered = match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!(
+            "{}-{position:02}-of-{count:02}.{}",
+            stem.to_string_lossy(),
+            extension.to_string_lossy()
+        ),
+        _ => format!("{file_name}-{position:02}-of-{count:02}"),
+    };
+    path.with_file_name(numbered).to_string_lossy().into_owned()
+}
+
+/// Put the symbols next to or under each other, `gap` modules apart, for one image.
+pub fn combine(symbols: &[Symbol], layout: Layout, gap: u32) -> Result<BitMatrix, CliError> {
+    let size = symbols
+        .first()
+        .map(|symbol| symbol.matrix.getWidth())
+        .unwrap_or_default();
+    let extent = symbols.len() as u32 * (size + gap) - gap;
+    let (width, height) = match layout {
+        Layout::Column => (size, extent),
+        Layout::Files | Layout::Row => (extent, size),
+    };
+    let mut combined = BitMatrix::new(width, height).map_err(CliError::from_encode_error)?;
+    for (position, symbol) in symbols.iter().enumerate() {
+        let offset = position as u32 * (size + gap);
+        let (left, top) = match layout {
+            Layout::Column => (0, offset),
+            Layout::Files | Layout::Row => (offset, 0),
+        };
+        for y in 0..size {
+            for x in 0..size {
+                if symbol.matrix.get(x, y) {
+                    combined.set(left + x, top + y);
+                }
+            }
+        }
+    }
+    Ok(combined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf_8(text: &str) -> Vec<EncodedChar> {
+        text.chars()
+            .map(|c| (c, c.to_string().into_bytes()))
+            .collect()
+    }
+
+    fn texts(parts: &[&[EncodedChar]]) -> Vec<String> {
+        parts
+            .iter()
+            .map(|part| part.iter().map(|(c, _)| c).collect())
+            .collect()
+    }
+
+    #[test]
+    fn split_into_equal_parts() {
+        let chars = utf_8("abcdefghij");
+        assert_eq!(texts(&split(&chars, 2).unwrap()), ["abcde", "fghij"]);
+        assert_eq!(texts(&split(&chars, 3).unwrap()), ["abc", "def", "ghij"]);
+        assert_eq!(texts(&split(&chars, 10).unwrap()).len(), 10);
+    }
+
+    #[test]
+    fn split_on_character_boundaries() {
+        let chars = utf_8("ééééa");
+        let parts = split(&chars, 2).unwrap();
+        assert_eq!(texts(&parts), ["éé", "ééa"]);
+        assert!(parts.iter().all(|part| !part.is_empty()));
+    }
+
+    #[test]
+    fn too_few_characters() {
+        assert!(split(&utf_8("ab"), 3).is_err());
+    }
+
+    #[test]
+    fn eci_for_the_character_set() {
+        assert_eq!(character_set("abc", None).unwrap(), (true, None));
+        assert_eq!(character_set("Grüße", None).unwrap(), (true, Some(3)));
+        assert_eq!(character_set("€", None).unwrap(), (false, Some(26)));
+        assert_eq!(
+            character_set("Grüße", Some("UTF_8")).unwrap(),
+            (false, Some(26))
+        );
+        assert!(character_set("€", Some("ISO-8859-1")).is_err());
+        assert!(character_set("abc", Some("Shift_JIS")).is_err());
+    }
+
+    #[test]
+    fn numbered_file_names() {
+        assert_eq!(
+            numbered_file_name("out/code.png", 1, 5),
+            "out/code-01-of-05.png"
+        );
+        assert_eq!(numbered_file_name("code", 12, 16), "code-12-of-16");
+    }
+}
@@ -0,0 +1,657 @@
+use rxing::common::BitMatrix;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::{checksum, error::CliError};
+
+pub const QR_TYPE: &str = "SPC";
+pub const VERSION: &str = "0200";
+/// Coding type 1, UTF-8 restricted to the Latin character set.
+const CODING: &str = "1";
+const TRAILER: &str = "EPD";
+
+/// The QR-bill allows QR codes up to version 25, of 117 modules.
+pub const MAX_MODULES: u32 = 117;
+const MAX_PAYLOAD: usize = 997;
+
+/// The Swiss cross is 7 mm wide in a 46 mm symbol.
+const CROSS_RATIO: (u32, u32) = (7, 46);
+
+/// The lines of the payload: header, account, creditor, the reserved ultimate creditor,
+/// amount, debtor, reference and additional information.
+const LINES: usize = 31;
+const MAX_ALTERNATIVE_SCHEMES: usize = 2;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Address {
+    pub name: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub street: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub building_number: String,
+    pub postal_code: String,
+    pub town: String,
+    pub country: String,
+    /// The two free lines of a combined (K) address, which is only read, never written.
+    #[serde(skip_serializing_if = "Vec::is_empty", skip_deserializing)]
+    pub address_lines: Vec<String>,
+}
+
+impl Address {
+    fn is_empty(&self) -> bool {
+        self.name.is_empty()
+            && self.street.is_empty()
+            && self.building_number.is_empty()
+            && self.postal_code.is_empty()
+            && self.town.is_empty()
+            && self.country.is_empty()
+    }
+
+    /// The seven address lines, always as a structured (S) address.
+    fn lines(&self) -> [&str; 7] {
+        [
+            "S",
+            &self.name,
+            &self.street,
+            &self.building_number,
+            &self.postal_code,
+            &self.town,
+            &self.country,
+        ]
+    }
+
+    fn check(&self, role: &str, errors: &mut Vec<String>) {
+        for (field, value, max) in [
+            ("name", &self.name, 70),
+            ("street", &self.street, 70),
+            ("building_number", &self.building_number, 16),
+            ("postal_code", &self.postal_code, 16),
+            ("town", &self.town, 35),
+        ] {
+            check_text(&format!("{role} {field}"), value, max, errors);
+        }
+        if !self.address_lines.is_empty() {
+            errors.push(format!(
+                "the {role} has a combined (K) address, only structured (S) addresses are accepted"
+            ));
+            return;
+        }
+        for (field, value) in [
+            ("name", &self.name),
+            ("postal_code", &self.postal_code),
+            ("town", &self.town),
+        ] {
+            if value.is_empty() {
+                errors.push(format!("the {role} {field} is missing"));
+            }
+        }
+        if self.country.len() != 2 || !self.country.bytes().all(|b| b.is_ascii_uppercase()) {
+            errors.push(format!(
+                "the {role} country '{}' is not a two letter ISO 3166 code",
+                self.country
+            ));
+        }
+    }
+}
+
+/// A Swiss QR-bill, as read from a JSON file or the command line, or parsed from a payload.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Bill {
+    /// The creditor's IBAN or QR-IBAN, CH or LI.
+    pub iban: String,
+    pub creditor: Address,
+    /// The amount with two decimals, left open when missing.
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "number_or_string"
+    )]
+    pub amount: Option<String>,
+    /// CHF or EUR.
+    pub currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debtor: Option<Address>,
+    /// QRR for a QR-IBAN, SCOR for a creditor reference or NON, worked out when left empty.
+    pub reference_type: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub reference: String,
+    /// The unstructured message to the creditor.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub message: String,
+    /// Structured billing information, e.g. //S1/10/10201409/11/190512.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub bill_information: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub alternative_schemes: Vec<String>,
+}
+
+fn number_or_string<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => None,
+        Some(Value::String(amount)) => Some(amount),
+        Some(other) => Some(other.to_string()),
+    })
+}
+
+impl Bill {
+    /// Complete the bill the way the payload needs it: the IBAN without spaces, an amount of at
+    /// most two decimals padded to two, the reference type from the IBAN and the reference, and
+    /// check digits added to a QR reference of 26 digits or a creditor reference given without
+    /// its RF prefix.
+    pub fn normalise(&mut self) {
+        self.iban = compact(&self.iban);
+        if self.currency.is_empty() {
+            self.currency = "CHF".to_owned();
+        }
+        if let Some(amount) = self.amount.as_deref().and_then(pad_amount) {
+            self.amount = Some(amount);
+        }
+        self.reference = compact(&self.reference);
+        if self.reference_type.is_empty() {
+            self.reference_type = if is_qr_iban(&self.iban) {
+                "QRR"
+            } else if self.reference.is_empty() {
+                "NON"
+            } else {
+                "SCOR"
+            }
+            .to_owned();
+        }
+        match self.reference_type.as_str() {
+            "QRR" if self.reference.len() == 26 => {
+                if let Some(check) = qr_reference_check_digit(&self.reference) {
+                    self.reference.push(check);
+                }
+            }
+            "SCOR" if !self.reference.starts_with("RF") => {
+                if let Some(check) = checksum::creditor_reference_check_digits(&self.reference) {
+                    self.reference = format!("RF{check:02}{}", self.reference);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Everything the implementation guidelines require of a bill, one message per problem.
+    pub fn check(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        check_iban(&self.iban, &mut errors);
+        self.creditor.check("creditor", &mut errors);
+        if let Some(debtor) = &self.debtor {
+            debtor.check("debtor", &mut errors);
+        }
+
+        if let Some(amount) = &self.amount {
+            let valid = amount.split_once('.').is_some_and(|(whole, cents)| {
+                !whole.is_empty()
+                    && whole.len() <= 9
+                    && cents.len() == 2
+                    && whole
+                        .bytes()
+                        .chain(cents.bytes())
+                        .all(|b| b.is_ascii_digit())
+            });
+            if !valid || amount.parse::<f64>().is_ok_and(|value| value < 0.01) {
+                errors.push(format!(
+                    "the amount '{amount}' is not from 0.01 to 999999999.99 with two decimals"
+                ));
+            }
+        }
+        if !matches!(self.currency.as_str(), "CHF" | "EUR") {
+            errors.push(format!(
+                "the currency '{}' is not CHF or EUR",
+                self.currency
+            ));
+        }
+
+        let qr_iban = is_qr_iban(&self.iban);
+        match self.reference_type.as_str() {
+            "QRR" => {
+                if !qr_iban {
+                    errors.push("a QR reference (QRR) needs a QR-IBAN".to_owned());
+                }
+                if self.reference.len() != 27 || !self.reference.bytes().all(|b| b.is_ascii_digit())
+                {
+                    errors.push(format!(
+                        "the QR reference '{}' is not 27 digits",
+                        self.reference
+                    ));
+                } else if qr_reference_check_digit(&self.reference[..26])
+                    != self.reference.chars().last()
+                {
+                    errors.push(format!(
+                        "the QR reference '{}' has the wrong check digit",
+                        self.reference
+                    ));
+                }
+            }
+            "SCOR" => {
+                if qr_iban {
+                    errors.push("a QR-IBAN needs a QR reference (QRR)".to_owned());
+                }
+                errors.extend(checksum::creditor_reference_error(&self.reference));
+            }
+            "NON" => {
+                if qr_iban {
+                    errors.push("a QR-IBAN needs a QR reference (QRR)".to_owned());
+                }
+                if !self.reference.is_empty() {
+                    errors.push("a reference is given with reference type NON".to_owned());
+                }
+            }
+            other => errors.push(format!(
+                "the reference type '{other}' is not QRR, SCOR or NON"
+            )),
+        }
+
+        check_text("message", &self.message, 140, &mut errors);
+        check_text("bill_information", &self.bill_information, 140, &mut errors);
+        if self.message.chars().count() + self.bill_information.chars().count() > 140 {
+            errors.push(
+                "the message and bill_information are longer than 140 characters together"
+                    .to_owned(),
+            );
+        }
+        if self.alternative_schemes.len() > MAX_ALTERNATIVE_SCHEMES {
+            errors.push(format!(
+                "there are {} alternative schemes, at most {MAX_ALTERNATIVE_SCHEMES} are allowed",
+                self.alternative_schemes.len()
+            ));
+        }
+        for scheme in &self.alternative_schemes {
+            check_text("alternative scheme", scheme, 100, &mut errors);
+        }
+        errors
+    }
+
+    /// The SPC payload, one field per line separated by line feeds.
+    pub fn to_payload(&self) -> String {
+        let debtor = self.debtor.clone().unwrap_or_default();
+        let mut lines = vec![QR_TYPE, VERSION, CODING, &self.iban];
+        lines.extend(self.creditor.lines());
+        lines.extend([""; 7]);
+        lines.extend([self.amount.as_deref().unwrap_or(""), &self.currency]);
+        if debtor.is_empty() {
+            lines.extend([""; 7]);
+        } else {
+            lines.extend(debtor.lines());
+        }
+        lines.extend([
+            self.reference_type.as_str(),
+            &self.reference,
+            &self.message,
+            TRAILER,
+        ]);
+        if !self.bill_information.is_empty() || !self.alternative_schemes.is_empty() {
+            lines.push(&self.bill_information);
+        }
+        lines.extend(self.alternative_schemes.iter().map(String::as_str));
+        lines.join("\n")
+    }
+}
+
+/// Build the payload of `bill`, failing with every problem found.
+pub fn build(mut bill: Bill) -> Result<String, CliError> {
+    bill.normalise();
+    let errors = bill.check();
+    let payload = bill.to_payload();
+    invalid(errors, &payload)?;
+    Ok(payload)
+}
+
+/// Check a payload given as is with --data.
+pub fn validate(payload: &str) -> Result<(), CliError> {
+    let parsed = parse(payload);
+    invalid(parsed.errors, payload)
+}
+
+fn invalid(mut errors: Vec<String>, payload: &str) -> Result<(), CliError> {
+    if payload.chars().count() > MAX_PAYLOAD {
+        errors.push(format!(
+            "the payload is {} characters, at most {MAX_PAYLOAD} are allowed",
+            payload.chars().count()
+        ));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::InvalidHint(format!(
+            "Invalid Swiss QR-bill:\n  {}",
+            errors.join("\n  ")
+        )))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SwissQrBill {
+    pub version: String,
+    #[serde(flatten)]
+    pub bill: Bill,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+    pub valid: bool,
+}
+
+impl SwissQrBill {
+    /// One line per field, for the text output.
+    pub fn to_text(&self) -> String {
+        let Ok(Value::Object(fields)) = serde_json::to_value(self) else {
+            return String::new();
+        };
+        let mut text = "Swiss QR-bill:".to_owned();
+        // a payload that could not be split leaves fields empty
+        let fields = fields
+            .into_iter()
+            .filter(|(_, value)| value.as_str() != Some(""));
+        for (name, value) in fields {
+            match value {
+                Value::Object(address) => {
+                    let lines: String = address
+                        .iter()
+                        .filter(|(_, value)| value.as_str() != Some(""))
+                        .map(|(field, value)| format!("\n    {field}: {}", plain(value)))
+                        .collect();
+                    if !lines.is_empty() {
+                        text.push_str(&format!("\n  {name}:{lines}"));
+                    }
+                }
+                Value::Array(items) if name == "errors" => {
+                    for error in items {
+                        text.push_str(&format!("\n  error: {}", plain(&error)));
+                    }
+                }
+                value => text.push_str(&format!("\n  {name}: {}", plain(&value))),
+            }
+        }
+        text
+    }
+}
+
+fn plain(value: &Value) -> String {
+    value.as_str().map_or(value.to_string(), str::to_owned)
+}
+
+/// Parse an SPC payload and check it against the implementation guidelines.
+pub fn parse(payload: &str) -> SwissQrBill {
+    let lines: Vec<&str> = payload
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+    let mut parsed = SwissQrBill {
+        version: lines.get(1).copied().unwrap_or_default().to_owned(),
+        bill: Bill::default(),
+        errors: Vec::new(),
+        valid: false,
+    };
+    let errors = &mut parsed.errors;
+
+    if lines[0] != QR_TYPE {
+        errors.push(format!("the payload does not start with '{QR_TYPE}'"));
+        return parsed;
+    }
+    if !parsed.version.starts_with("02") {
+        errors.push(format!(
+            "version '{}' is not a 2.x version (02xx)",
+            parsed.version
+        ));
+    }
+    // a trailing line feed is tolerated
+    let end = lines.len() - usize::from(lines.len() > LINES && lines[lines.len() - 1].is_empty());
+    if end < LINES {
+        errors.push(format!(
+            "the payload has {end} lines, at least {LINES} are needed"
+        ));
+        return parsed;
+    }
+    if lines[2] != CODING {
+        errors.push(format!("coding type '{}' is not 1 (UTF-8)", lines[2]));
+    }
+
+    let address = |start: usize| {
+        let field = |i: usize| lines[start + i].to_owned();
+        let mut address = Address {
+            name: field(1),
+            postal_code: field(4),
+            town: field(5),
+            country: field(6),
+            ..Address::default()
+        };
+        match lines[start] {
+            "K" => address.address_lines = vec![field(2), field(3)],
+            _ => {
+                address.street = field(2);
+                address.building_number = field(3);
+            }
+        }
+        (lines[start], address)
+    };
+    let bill = &mut parsed.bill;
+    bill.iban = lines[3].to_owned();
+    let (creditor_type, creditor) = address(4);
+    bill.creditor = creditor;
+    if lines[11..18].iter().any(|line| !line.is_empty()) {
+        errors.push("the ultimate creditor is reserved and must be empty".to_owned());
+    }
+    bill.amount = Some(lines[18].to_owned()).filter(|amount| !amount.is_empty());
+    bill.currency = lines[19].to_owned();
+    let (debtor_type, debtor) = address(20);
+    bill.debtor = (!lines[20..27].iter().all(|line| line.is_empty())).then_some(debtor);
+    bill.reference_type = lines[27].to_owned();
+    bill.reference = lines[28].to_owned();
+    bill.message = lines[29].to_owned();
+    if lines[30] != TRAILER {
+        errors.push(format!("the trailer '{}' is not {TRAILER}", lines[30]));
+    }
+    bill.bill_information = lines.get(31).copied().unwrap_or_default().to_owned();
+    bill.alternative_schemes = lines[LINES.min(end)..end]
+        .iter()
+        .skip(1)
+        .map(|line| line.to_string())
+        .collect();
+
+    for (role, address_type) in [("creditor", creditor_type), ("debtor", debtor_type)] {
+        if !matches!(address_type, "S" | "K" | "")
+            || (role == "creditor" && address_type.is_empty())
+        {
+            errors.push(format!(
+                "the {role} address type '{address_type}' is not S or K"
+            ));
+        }
+    }
+    errors.extend(bill.check());
+    parsed.valid = parsed.errors.is_empty();
+    parsed
+}
+
+/// Clear the centre of the symbol and draw the Swiss cross on it, a white cross on a black
+/// square with a white border, in the proportions of the Swiss flag. `image` is the laid out
+/// symbol, whose symbol area (without the quiet zone) is `symbol_width` pixels wide.
+pub fn draw_swiss_cross(image: &mut BitMatrix, symbol_width: u32) {
+    let size = symbol_width * CROSS_RATIO.0 / CROSS_RATIO.1;
+    let (centre_x, centre_y) = (image.getWidth() / 2, image.getHeight() / 2);
+    let left = centre_x - size / 2;
+    let top = centre_y - size / 2;
+    // the border is 0.5 mm of the 7 mm, the flag's arms are 6/32 wide and 20/32 long
+    let border = (size / 14).max(1);
+    let square = size - 2 * border;
+    let arm_width = square * 6 / 32;
+    let arm_length = square * 20 / 32;
+
+    for y in top..top + size {
+        for x in left..left + size {
+            let (dx, dy) = (x.abs_diff(centre_x), y.abs_diff(centre_y));
+            let in_square = x >= left + border
+                && x < left + size - border
+                && y >= top + border
+                && y < top + size - border;
+            let in_cross = (dx <= arm_width / 2 && dy <= arm_length / 2)
+                || (dy <= arm_width / 2 && dx <= arm_length / 2);
+            if in_square && !in_cross {
+                image.set(x, y);
+            } else {
+                image.unset(x, y);
+            }
+        }
+    }
+}
+
+fn check_text(field: &str, value: &str, max: usize, errors: &mut Vec<String>) {
+    let length = value.chars().count();
+    if length > max {
+        errors.push(format!(
+            "the {field} is {length} characters, at most {max} are allowed"
+        ));
+    }
+    if let Some(c) = value.chars().find(|c| !is_permitted(*c)) {
+        errors.push(format!(
+            "the {field} contains '{}', which is not in the permitted Latin character set",
+            c.escape_default()
+        ));
+    }
+}
+
+/// The characters of the Swiss Payment Standards: printable Basic Latin and Latin-1
+/// Supplement, and the few Latin Extended-A letters and the euro sign of the list.
+fn is_permitted(c: char) -> bool {
+    matches!(c, ' '..='~' | '\u{a0}'..='\u{ff}') || "ŠšŽžŒœŸȘșȚț€".contains(c)
+}
+
+/// Digits with at most two decimals, written with exactly two. Anything else is left for
+/// `check` to report rather than rounded.
+fn pad_amount(amount: &str) -> Option<String> {
+    let amount = amount.trim();
+    let (whole, cents) = amount.split_once('.').unwrap_or((amount, ""));
+    let digits = whole
+        .bytes()
+        .chain(cents.bytes())
+        .all(|b| b.is_ascii_digit());
+    (!whole.is_empty() && cents.len() <= 2 && digits).then(|| format!("{whole}.{cents:0<2}"))
+}
+
+fn compact(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+fn check_iban(iban: &str, errors: &mut Vec<String>) {
+    if !(iban.starts_with("CH") || iban.starts_with("LI")) {
+        errors.push(format!("the IBAN '{iban}' is not a CH or LI IBAN"));
+    } else if iban.len() != 21 || !iban.bytes().all(|b| b.is_ascii_alphanumeric()) {
+        errors.push(format!("the IBAN '{iban}' is not 21 letters and digits"));
+    } else if !checksum::iban_is_valid(iban) {
+        errors.push(format!(
+            "the IBAN '{iban}' has the wrong check digits (mod 97)"
+        ));
+    }
+}
+
+/// A QR-IBAN has an institution identification (IID) from 30000 to 31999.
+fn is_qr_iban(iban: &str) -> bool {
+    iban.get(4..9)
+        .and_then(|iid| iid.parse::<u32>().ok())
+        .is_some_and(|iid| (30000..=31999).contains(&iid))
+}
+
+/// The check digit of a QR reference, by the recursive modulo 10 algorithm.
+fn qr_reference_check_digit(digits: &str) -> Option<char> {
+    const TABLE: [u32; 10] = [0, 9, 4, 6, 8, 2, 7, 1, 3, 5];
+    let carry = digits.chars().try_fold(0, |carry, c| {
+        Some(TABLE[((carry + c.to_digit(10)?) % 10) as usize])
+    })?;
+    char::from_digit((10 - carry) % 10, 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bill() -> Bill {
+        Bill {
+            iban: "CH44 3199 9123 0008 8901 2".to_owned(),
+            creditor: Address {
+                name: "Robert Schneider AG".to_owned(),
+                street: "Rue du Lac".to_owned(),
+                building_number: "1268".to_owned(),
+                postal_code: "2501".to_owned(),
+                town: "Biel".to_owned(),
+                country: "CH".to_owned(),
+                ..Address::default()
+            },
+            amount: Some("1949.75".to_owned()),
+            reference: "21 00000 00003 13947 14300 0901".to_owned(),
+            ..Bill::default()
+        }
+    }
+
+    #[test]
+    fn qr_reference_check_digits() {
+        assert_eq!(
+            qr_reference_check_digit("21000000000313947143000901"),
+            Some('7')
+        );
+        assert_eq!(
+            qr_reference_check_digit("00000000000000000000000000"),
+            Some('0')
+        );
+        assert_eq!(qr_reference_check_digit("2100000000031394714300090A"), None);
+    }
+
+    #[test]
+    fn build_and_parse() {
+        let payload = build(bill()).unwrap();
+        let parsed = parse(&payload);
+        assert!(parsed.valid, "{:?}", parsed.errors);
+        assert_eq!(parsed.bill.iban, "CH4431999123000889012");
+        assert_eq!(parsed.bill.reference_type, "QRR");
+        assert_eq!(parsed.bill.reference, "210000000003139471430009017");
+        assert_eq!(parsed.bill.currency, "CHF");
+        assert!(parsed.bill.debtor.is_none());
+        assert!(validate(&payload).is_ok());
+    }
+
+    #[test]
+    fn amounts_are_padded_not_rounded() {
+        assert_eq!(pad_amount(" 12.3").as_deref(), Some("12.30"));
+        assert_eq!(pad_amount("1949").as_deref(), Some("1949.00"));
+        assert_eq!(pad_amount("12.345"), None);
+        assert_eq!(pad_amount("1e3"), None);
+        assert_eq!(pad_amount(".5"), None);
+    }
+
+    #[test]
+    fn invalid_bills() {
+        let mut wrong_check_digit = bill();
+        wrong_check_digit.reference = "210000000003139471430009018".to_owned();
+        let mut not_swiss = bill();
+        not_swiss.iban = "DE89370400440532013000".to_owned();
+        let mut creditor_reference = bill();
+        creditor_reference.reference_type = "SCOR".to_owned();
+        let mut rounded = bill();
+        rounded.amount = Some("12.345".to_owned());
+        let mut exponent = bill();
+        exponent.amount = Some("1e3".to_owned());
+        for bill in [
+            wrong_check_digit,
+            not_swiss,
+            creditor_reference,
+            rounded,
+            exponent,
+        ] {
+            assert!(build(bill).is_err());
+        }
+    }
+
+    #[test]
+    fn invalid_payloads() {
+        assert!(!parse("BCD\n002").valid);
+        assert!(!parse("SPC\n0200\n1").valid);
+        let payload = build(bill()).unwrap().replace("EPD", "END");
+        assert!(validate(&payload).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt, str::FromStr};
+
+use crate::error::CliError;
+
+pub const MM_PER_INCH: f64 = 25.4;
+
+/// Largest relative difference between a requested X-dimension and the one that can be
+/// printed with whole dots before a warning is shown.
+const X_DIMENSION_TOLERANCE: f64 = 0.05;
+
+/// A length on the command line, either in pixels (no unit or `px`) or physical (`mm`, `cm`,
+/// `in` or `mil`, a thousandth of an inch).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Pixels(u32),
+    Millimetres(f64),
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+
+        if unit.is_empty() || unit == "px" {
+            return number
+                .parse()
+                .map(Length::Pixels)
+                .map_err(|_| format!("'{s}' is not a whole number of pixels"));
+        }
+
+        let number: f64 = number
+            .parse()
+            .map_err(|_| format!("'{s}' does not start with a number"))?;
+        let mm = match unit {
+            "mm" => number,
+            "cm" => number * 10.0,
+            "in" => number * MM_PER_INCH,
+            "mil" => number * MM_PER_INCH / 1000.0,
+            _ => {
+                return Err(format!(
+                    "'{unit}' is not a known unit, expected px, mm, cm, in or mil"
+                ))
+            }
+        };
+        if mm <= 0.0 {
+            return Err(format!("'{s}' must be larger than zero"));
+        }
+        Ok(Length::Millimetres(mm))
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Pixels(pixels) => write!(f, "{pixels}px"),
+            Length::Millimetres(mm) => write!(f, "{mm}mm"),
+        }
+    }
+}
+
+/// Parse a length that must have a physical unit.
+pub fn parse_physical(s: &str) -> Result<f64, String> {
+    match s.parse()? {
+        Length::Millimetres(mm) => Ok(mm),
+        Length::Pixels(_) => Err(format!("'{s}' needs a unit: mm, cm, in or mil")),
+    }
+}
+
+impl Length {
+    /// The number of whole pixels that fit in this length when printed at `dpi`.
+    pub fn to_pixels(self, flag: &str, dpi: Option<u32>) -> Result<u32, CliError> {
+        match (self, dpi) {
+            (Length::Pixels(pixels), _) => Ok(pixels),
+            (Length::Millimetres(mm), Some(dpi)) => Ok((mm / MM_PER_INCH * dpi as f64) as u32),
+            (Length::Millimetres(_), None) => Err(CliError::InvalidHint(format!(
+                "--{flag} {self} is a physical size, --dpi is needed to convert it to pixels"
+            ))),
+        }
+    }
+}
+
+/// The whole number of printer dots closest to an X-dimension of `mm` at `dpi`, and a warning
+/// when the rounding changes the bar widths noticeably.
+pub fn x_dimension_dots(mm: f64, dpi: u32) -> (u32, Option<String>) {
+    let exact = mm / MM_PER_INCH * dpi as f64;
+    let dots = (exact.round() as u32).max(1);
+    let printed = dots as f64 * MM_PER_INCH / dpi as f64;
+    let deviation = (printed - mm) / mm;
+
+    let warning = (deviation.abs() > X_DIMENSION_TOLERANCE).then(|| {
+        format!(
+            "Warning, an X-dimension of {mm:.3}mm is {exact:.2} dots at {dpi} dpi, it will be printed as {dots} dots ({printed:.3}mm, {:+.1}%)",
+            deviation * 100.0
+        )
+    });
+    (dots, warning)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths() {
+        assert_eq!("300".parse(), Ok(Length::Pixels(300)));
+        assert_eq!(" 300px ".parse(), Ok(Length::Pixels(300)));
+        assert_eq!("2.5cm".parse(), Ok(Length::Millimetres(25.0)));
+        assert_eq!("1in".parse(), Ok(Length::Millimetres(25.4)));
+        assert_eq!(parse_physical("10mil"), Ok(0.254));
+        assert!("1.5px".parse::<Length>().is_err());
+        assert!("0mm".parse::<Length>().is_err());
+        assert!("5pt".parse::<Length>().is_err());
+        assert!("mm".parse::<Length>().is_err());
+        assert!(parse_physical("300").is_err());
+    }
+
+    #[test]
+    fn pixels_at_a_resolution() {
+        assert_eq!(
+            Length::Millimetres(25.4)
+                .to_pixels("size", Some(300))
+                .unwrap(),
+            300
+        );
+        assert!(Length::Millimetres(25.4).to_pixels("size", None).is_err());
+    }
+
+    #[test]
+    fn x_dimension_in_whole_dots() {
+        assert_eq!(x_dimension_dots(0.254, 300), (3, None));
+        let (dots, warning) = x_dimension_dots(0.33, 203);
+        assert_eq!(dots, 3);
+        assert!(warning.is_some());
+    }
+}
@@ -0,0 +1,113 @@
+/// Percent-encode everything but the unreserved characters of RFC 3986, byte by byte in UTF-8.
+pub fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Percent-encode a path, keeping the @ of email addresses readable.
+pub fn path_encode(value: &str) -> String {
+    percent_encode(value).replace("%40", "@")
+}
+
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The decoded names and values of a query string, in order.
+pub fn query_pairs(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect()
+}
+
+/// A query string of `pairs`, leaving out those without a value. Besides the unreserved
+/// characters, the values keep the ones RFC 3986 allows in a query that do not separate
+/// pairs, so addresses like `name@bank` and URLs stay readable.
+pub fn query(pairs: &[(&str, Option<&str>)]) -> String {
+    pairs
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .filter(|value| !value.is_empty())
+                .map(|value| format!("{name}={}", query_encode(value)))
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn query_encode(value: &str) -> String {
+    value
+        .split_inclusive(['@', ':', '/', '?'])
+        .map(|part| match part.char_indices().last() {
+            Some((i, '@' | ':' | '/' | '?')) => percent_encode(&part[..i]) + &part[i..],
+            _ => percent_encode(part),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percent_encoding() {
+        assert_eq!(percent_encode("a b/ü~"), "a%20b%2F%C3%BC~");
+        assert_eq!(path_encode("john@example.com"), "john@example.com");
+        assert_eq!(percent_decode("a%20b%2f%C3%BC"), "a b/ü");
+        // a stray or broken escape is kept as it is
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn query_strings() {
+        assert_eq!(
+            query(&[
+                ("pa", Some("a@b")),
+                ("pn", Some("A & B")),
+                ("x", None),
+                ("y", Some(""))
+            ]),
+            "pa=a@b&pn=A%20%26%20B"
+        );
+        assert_eq!(
+            query_pairs("a=1&&b=x%3Dy&c"),
+            [
+                ("a".to_owned(), "1".to_owned()),
+                ("b".to_owned(), "x=y".to_owned()),
+                ("c".to_owned(), String::new()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet};
+
+use rxing::{
+    common::BitMatrix, BarcodeFormat, DecodeHintType, DecodeHintValue, RXingResult,
+    RXingResultMetadataType, RXingResultMetadataValue,
+};
+
+use crate::{
+    colour::Palette,
+    encode_options::format_name,
+    error::CliError,
+    gs1,
+    stdio::{self, ImageFormat},
+};
+
+/// Bytes shown on either side of the first difference.
+const DIFF_CONTEXT: usize = 8;
+
+/// Render `bit_matrix` the way it will be saved, decode it again with the reader for `format`
+/// and check that the decoded text is byte for byte the encoded `input`.
+///
+/// The raw bytes are checked too where the reader returns them: QR Code, Data Matrix and Aztec
+/// report the bytes of data written in byte mode, which must be the input in `character_set`
+/// when they hold the whole text. The other formats, text written in other modes, GS1 symbols
+/// and character sets other than UTF-8 and ISO-8859-1 are only checked by their text.
+///
+/// Plain text and TGA files can not be read back, so those are checked as a PNG instead. For
+/// `gs1` symbols the `input` is the element string with group separators.
+pub fn verify(
+    format: &BarcodeFormat,
+    bit_matrix: &BitMatrix,
+    image_format: Option<ImageFormat>,
+    palette: &Palette,
+    input: &str,
+    character_set: Option<&str>,
+    gs1: bool,
+) -> Result<(), CliError> {
+    let name = format_name(format);
+    let image_format = match image_format {
+        Some(ImageFormat::Txt | ImageFormat::Tga) | None => ImageFormat::Png,
+        Some(image_format) => image_format,
+    };
+    let image = stdio::render(bit_matrix, image_format, None, palette)
+        .map_err(|e| CliError::VerifyFailure(format!("Verification failed, {e}")))?;
+
+    let mut hints = HashMap::from([
+        (
+            DecodeHintType::POSSIBLE_FORMATS,
+            DecodeHintValue::PossibleFormats(HashSet::from([*format])),
+        ),
+        (
+            DecodeHintType::RETURN_CODABAR_START_END,
+            DecodeHintValue::ReturnCodabarStartEnd(true),
+        ),
+    ]);
+    if gs1 {
+        hints.insert(DecodeHintType::ASSUME_GS1, DecodeHintValue::AssumeGs1(true));
+    }
+    let result = stdio::detect_in_bytes_with_hints(&image, &mut hints).map_err(|e| {
+        CliError::VerifyFailure(format!(
+            "Verification failed, the {name} symbol could not be decoded: {e}"
+        ))
+    })?;
+
+    let decoded = if gs1 {
+        gs1::strip_symbology_identifier(result.getText())
+    } else {
+        result.getText()
+    };
+    if decoded != input && !is_appended_check_digit(format, input, decoded) {
+        return Err(CliError::VerifyFailure(format!(
+            "Verification failed, the decoded {name} text differs from the input\n{}",
+            byte_diff(input.as_bytes(), decoded.as_bytes())
+        )));
+    }
+    let expected = expected_bytes(input, character_set).filter(|_| !gs1);
+    match (expected, byte_segments(&result)) {
+        (Some(expected), Some(raw)) if holds_text(&raw, input) && raw != expected => {
+            Err(CliError::VerifyFailure(format!(
+                "Verification failed, the raw bytes of the {name} symbol differ from the input in {}\n{}",
+                character_set.unwrap_or("the default character set"),
+                byte_diff(&expected, &raw)
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The bytes of `input` in the character set the 2D writers use for it: ISO-8859-1 unless
+/// UTF-8 is asked for or the text does not fit. `None` for the other character sets.
+fn expected_bytes(input: &str, character_set: Option<&str>) -> Option<Vec<u8>> {
+    let name = character_set.map(|name| name.to_ascii_lowercase().replace('_', "-"));
+    let latin_1 = match name.as_deref() {
+        None => input.chars().all(|c| c <= '\u{ff}'),
+        Some("utf-8" | "utf8") => false,
+        Some("iso-8859-1" | "iso8859-1" | "latin1") => true,
+        Some(_) => return None,
+    };
+    Some(if latin_1 {
+        input.chars().map(|c| c as u8).collect()
+    } else {
+        input.as_bytes().to_vec()
+    })
+}
+
+/// The bytes of the byte mode segments of `result`, if the reader reports any.
+fn byte_segments(result: &RXingResult) -> Option<Vec<u8>> {
+    match result
+        .getRXingResultMetadata()
+        .get(&RXingResultMetadataType::BYTE_SEGMENTS)
+    {
+        Some(RXingResultMetadataValue::ByteSegments(segments)) => Some(segments.concat()),
+        _ => None,
+    }
+}
+
+/// Whether `raw` is the whole of `text` in UTF-8 or ISO-8859-1, rather than the part of it that
+/// was written in byte mode.
+fn holds_text(raw: &[u8], text: &str) -> bool {
+    std::str::from_utf8(raw) == Ok(text) || raw.iter().map(|b| char::from(*b)).eq(text.chars())
+}
+
+/// EAN and UPC writers add the check digit when it is left out of the input.
+fn is_appended_check_digit(format: &BarcodeFormat, input: &str, decoded: &str) -> bool {
+    matches!(
+        format,
+        BarcodeFormat::EAN_8 | BarcodeFormat::EAN_13 | BarcodeFormat::UPC_A | BarcodeFormat::UPC_E
+    ) && decoded.len() == input.len() + 1
+        && decoded.starts_with(input)
+}
+
+/// Describe the first difference between `expected` and `actual` as hex and text.
+fn byte_diff(expected: &[u8], actual: &[u8]) -> String {
+    let offset = expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or(expected.len().min(actual.len()));
+    let start = offset.saturating_sub(DIFF_CONTEXT);
+    let window = |bytes: &[u8]| {
+        let end = bytes.len().min(offset + DIFF_CONTEXT + 1);
+        let bytes = &bytes[start.min(bytes.len())..end];
+        format!(
+            "{} \"{}\"",
+            bytes
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            String::from_utf8_lossy(bytes).escape_default()
+        )
+    };
+
+    format!(
+        "first difference at byte {offset} (input is {} bytes, decoded is {} bytes), showing bytes from {start}:\n  input:   {}\n  decoded: {}",
+        expected.len(),
+        actual.len(),
+        window(expected),
+        window(actual)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rxing::{EncodeHintType, EncodeHintValue, MultiFormatWriter, Writer};
+
+    use super::*;
+
+    fn qr_code(contents: &str, character_set: Option<&str>) -> BitMatrix {
+        let mut hints = HashMap::new();
+        if let Some(character_set) = character_set {
+            hints.insert(
+                EncodeHintType::CHARACTER_SET,
+                EncodeHintValue::CharacterSet(character_set.to_owned()),
+            );
+        }
+        MultiFormatWriter
+            .encode_with_hints(contents, &BarcodeFormat::QR_CODE, 200, 200, &hints)
+            .unwrap()
+    }
+
+    fn check(matrix: &BitMatrix, input: &str, character_set: Option<&str>) -> Result<(), CliError> {
+        let format = BarcodeFormat::QR_CODE;
+        let png = Some(ImageFormat::Png);
+        verify(
+            &format,
+            matrix,
+            png,
+            &Palette::default(),
+            input,
+            character_set,
+            false,
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        assert!(check(&qr_code("hello, world", None), "hello, world", None).is_ok());
+        assert!(check(&qr_code("Grüße", None), "Grüße", None).is_ok());
+        let utf_8 = qr_code("Grüße", Some("UTF-8"));
+        assert!(check(&utf_8, "Grüße", Some("UTF-8")).is_ok());
+    }
+
+    #[test]
+    fn different_text() {
+        let Err(CliError::VerifyFailure(message)) = check(&qr_code("hello", None), "hellp", None)
+        else {
+            panic!("the text was not compared");
+        };
+        assert!(message.contains("first difference at byte 4"), "{message}");
+    }
+
+    #[test]
+    fn different_raw_bytes() {
+        // the reader finds the same text, but the symbol holds UTF-8 instead of ISO-8859-1
+        let utf_8 = qr_code("Grüße", Some("UTF-8"));
+        let Err(CliError::VerifyFailure(message)) = check(&utf_8, "Grüße", Some("ISO-8859-1"))
+        else {
+            panic!("the raw bytes were not compared");
+        };
+        assert!(message.contains("raw bytes"), "{message}");
+    }
+
+    #[test]
+    fn expected_bytes_per_character_set() {
+        assert_eq!(expected_bytes("é", None), Some(vec![0xe9]));
+        assert_eq!(expected_bytes("é€", None), Some("é€".as_bytes().to_vec()));
+        assert_eq!(expected_bytes("é", Some("utf_8")), Some(vec![0xc3, 0xa9]));
+        assert_eq!(expected_bytes("é", Some("Shift_JIS")), None);
+        assert!(holds_text(&[0xe9], "é"));
+        assert!(holds_text("é".as_bytes(), "é"));
+        assert!(!holds_text(b"abc", "123abc"));
+    }
+
+    #[test]
+    fn check_digit_allowance() {
+        let ean_13 = BarcodeFormat::EAN_13;
+        assert!(is_appended_check_digit(
+            &ean_13,
+            "590123412345",
+            "5901234123457"
+        ));
+        assert!(is_appended_check_digit(
+            &BarcodeFormat::UPC_E,
+            "0123456",
+            "01234565"
+        ));
+        assert!(!is_appended_check_digit(
+            &ean_13,
+            "590123412345",
+            "5901234123458x"
+        ));
+        assert!(!is_appended_check_digit(
+            &ean_13,
+            "590123412345",
+            "6901234123457"
+        ));
+        let code_128 = BarcodeFormat::CODE_128;
+        assert!(!is_appended_check_digit(&code_128, "12345", "123456"));
+    }
+
+    #[test]
+    fn diff_of_the_first_difference() {
+        assert_eq!(
+            byte_diff(b"abcdef", b"abXdef"),
+            "first difference at byte 2 (input is 6 bytes, decoded is 6 bytes), showing bytes from 0:\n  input:   61 62 63 64 65 66 \"abcdef\"\n  decoded: 61 62 58 64 65 66 \"abXdef\""
+        );
+        let long = byte_diff(b"0123456789abcdefghij", b"0123456789abcdefghiJ");
+        assert!(long.contains("at byte 19"), "{long}");
+        assert!(long.contains("showing bytes from 11"), "{long}");
+        // a shorter decoded text differs where it ends
+        let short = byte_diff(b"abc", b"ab");
+        assert!(
+            short.starts_with("first difference at byte 2 (input is 3 bytes, decoded is 2 bytes)")
+        );
+        assert!(short.ends_with("decoded: 61 62 \"ab\""), "{short}");
+    }
+}